@@ -0,0 +1,158 @@
+//! A kernel-style work queue built from pinned, in-place initialized items.
+//!
+//! In the Linux kernel, a driver embeds a `struct work_struct` in its own
+//! device structure, initializes it in place with `INIT_WORK`, and hands it to
+//! a work queue, which links it into an intrusive list. When the work runs,
+//! the callback receives a pointer to the `work_struct`, and recovers the
+//! device with `container_of`. The work item must never move while it is
+//! queued, which is the problem with Rust initialization described in the LWN
+//! article cited by the sketch.
+//!
+//! Here the same pattern is built from `pin_init!` and the intrusive list in
+//! this crate. The only unsafe code is the equivalent of `container_of`, and a
+//! projection to the pinned work item.
+
+// Without the global allocation error handler, there is no `Box::pin_emplace`
+// to run the example with, but the rest of it is still checked.
+#![cfg_attr(inplace_no_global_oom_handling, allow(dead_code))]
+
+use core::cell::Cell;
+use core::mem::offset_of;
+use core::pin::Pin;
+
+use inplace::intrusive::list::{Linked, Links, ListHead};
+#[cfg(not(inplace_no_global_oom_handling))]
+use inplace::BoxExt;
+use inplace::{pin_data, pin_init, PinInit};
+
+/// A unit of deferred work, embedded in the structure it operates on.
+#[pin_data]
+struct Work {
+    #[pin]
+    links: Links,
+    func: fn(Pin<&Work>),
+}
+
+unsafe impl Linked for Work {
+    const LINKS_OFFSET: usize = offset_of!(Work, links);
+}
+
+impl Work {
+    /// The equivalent of `INIT_WORK`.
+    fn new(func: fn(Pin<&Work>)) -> impl PinInit<Work> {
+        pin_init!(Work {
+            links <- Links::new(),
+            func,
+        })
+    }
+}
+
+/// A queue of work items, run in the order they were queued.
+struct WorkQueue<'a> {
+    pending: Pin<Box<ListHead<'a, Work>>>,
+}
+
+impl<'a> WorkQueue<'a> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn new() -> Self {
+        WorkQueue {
+            pending: Box::pin_emplace(ListHead::new()),
+        }
+    }
+
+    /// Queues a work item, returning false if it was already queued, as
+    /// `queue_work` does.
+    fn queue(&self, work: Pin<&'a Work>) -> bool {
+        if work.links.is_linked() {
+            return false;
+        }
+        self.pending.as_ref().push_back(work);
+        true
+    }
+
+    /// Runs the queued work items, returning how many were run.
+    fn run(&self) -> usize {
+        let mut count = 0;
+        while let Some(work) = self.pending.as_ref().pop_front() {
+            (work.func)(work);
+            count += 1;
+        }
+        count
+    }
+}
+
+/// A device whose interrupt handler defers processing to a work item.
+#[pin_data]
+struct Device {
+    id: u32,
+    interrupts: Cell<u32>,
+    handled: Cell<u32>,
+    #[pin]
+    work: Work,
+}
+
+impl Device {
+    fn new(id: u32) -> impl PinInit<Device> {
+        pin_init!(Device {
+            id,
+            interrupts: Cell::new(0),
+            handled: Cell::new(0),
+            work <- Work::new(Device::handle_work),
+        })
+    }
+
+    fn work(self: Pin<&Self>) -> Pin<&Work> {
+        // `work` is structurally pinned, as it is marked `#[pin]`.
+        unsafe { self.map_unchecked(|device| &device.work) }
+    }
+
+    /// Records an interrupt, and schedules the work to handle it.
+    fn interrupt<'a>(self: Pin<&'a Self>, queue: &WorkQueue<'a>) {
+        self.interrupts.set(self.interrupts.get() + 1);
+        queue.queue(self.work());
+    }
+
+    fn handle_work(work: Pin<&Work>) {
+        // The equivalent of `container_of(work, struct device, work)`.
+        let device = unsafe {
+            &*(&*work as *const Work)
+                .byte_sub(offset_of!(Device, work))
+                .cast::<Device>()
+        };
+        let pending = device.interrupts.get() - device.handled.get();
+        println!("device {}: handling {} interrupt(s)", device.id, pending);
+        device.handled.set(device.interrupts.get());
+    }
+}
+
+#[cfg(not(inplace_no_global_oom_handling))]
+fn main() {
+    let devices: Vec<Pin<Box<Device>>> =
+        (0..3).map(|id| Box::pin_emplace(Device::new(id))).collect();
+    let addresses: Vec<*const Work> = devices
+        .iter()
+        .map(|device| &*device.as_ref().work() as *const Work)
+        .collect();
+
+    let queue = WorkQueue::new();
+    devices[2].as_ref().interrupt(&queue);
+    devices[0].as_ref().interrupt(&queue);
+    // A second interrupt before the work runs is coalesced.
+    devices[2].as_ref().interrupt(&queue);
+
+    // The queue links the work items where they were constructed.
+    let queued: Vec<*const Work> = queue
+        .pending
+        .iter()
+        .map(|work| &*work as *const Work)
+        .collect();
+    assert_eq!(queued, [addresses[2], addresses[0]]);
+
+    assert_eq!(queue.run(), 2);
+    assert_eq!(devices[2].handled.get(), 2);
+    assert_eq!(devices[1].handled.get(), 0);
+    assert!(devices.iter().all(|device| !device.work.links.is_linked()));
+}
+
+#[cfg(inplace_no_global_oom_handling)]
+fn main() {}
@@ -0,0 +1,289 @@
+//! Implementation of `#[derive(Init)]` and `#[derive(PinInit)]`.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{Data, DeriveInput, Fields, Type};
+
+use crate::init::{self, FieldInit, FieldKind, StructInit};
+use crate::pin_data::{self, has_pinned_drop, is_pinned};
+
+/// Returns the element type of an unsized tail field, for `[T]` and `str`.
+fn tail_element(ty: &Type) -> Option<Type> {
+    match ty {
+        Type::Slice(slice) => Some((*slice.elem).clone()),
+        Type::Path(path) if path.qself.is_none() && path.path.is_ident("str") => {
+            Some(syn::parse_quote!(u8))
+        }
+        _ => None,
+    }
+}
+
+/// The representation hints of a struct which affect its initializer.
+struct Repr {
+    c: bool,
+    /// The `packed` hint, if the struct has one.
+    packed: Option<syn::Path>,
+}
+
+/// Parses the `#[repr]` attributes of a struct.
+fn repr(input: &DeriveInput) -> syn::Result<Repr> {
+    let mut repr = Repr {
+        c: false,
+        packed: None,
+    };
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            repr.c |= meta.path.is_ident("C");
+            if meta.path.is_ident("packed") {
+                repr.packed = Some(meta.path.clone());
+            }
+            // Skip the arguments of hints such as `align(8)` or `packed(2)`.
+            if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<TokenStream>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr)
+}
+
+pub fn expand(input: DeriveInput, pinned: bool) -> syn::Result<TokenStream> {
+    let derive = if pinned { "PinInit" } else { "Init" };
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                format!("`#[derive({derive})]` is only supported on structs"),
+            ))
+        }
+    };
+    // Fields are initialized through pointers to them, which must be aligned.
+    let repr = repr(&input)?;
+    if let Some(packed) = &repr.packed {
+        return Err(syn::Error::new_spanned(
+            packed,
+            format!("`#[derive({derive})]` does not support `#[repr(packed)]`"),
+        ));
+    }
+    if !pinned {
+        if let Some(field) = fields.iter().find(|field| is_pinned(field)) {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`#[pin]` fields require `#[derive(PinInit)]`",
+            ));
+        }
+        if let Some(attr) = has_pinned_drop(&input) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "`#[pinned_drop]` requires `#[derive(PinInit)]`",
+            ));
+        }
+    }
+
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let init_ident = format_ident!("{}Init", ident);
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    // The impls are generic over the struct's own parameters, plus one
+    // parameter for the initializer of each field. Pinned fields accept
+    // pinned initializers.
+    let params: Vec<_> = (0..fields.len())
+        .map(|index| format_ident!("__Field{}", index))
+        .collect();
+    let mut generics = input.generics.clone();
+    for (param, field) in params.iter().zip(fields) {
+        let ty = &field.ty;
+        generics.params.push(syn::parse_quote!(#param));
+        generics
+            .make_where_clause()
+            .predicates
+            .push(if is_pinned(field) {
+                syn::parse_quote!(#param: ::inplace::PinInit<#ty>)
+            } else {
+                syn::parse_quote!(#param: ::inplace::Init<#ty>)
+            });
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    let doc = format!("Initializer for [`{ident}`], generated by `#[derive({derive})]`.");
+    let definition = match fields {
+        Fields::Named(_) => {
+            let defs = fields.iter().zip(&params).map(|(field, param)| {
+                let vis = &field.vis;
+                let name = &field.ident;
+                quote! { #vis #name: #param }
+            });
+            quote! { #vis struct #init_ident<#(#params),*> { #(#defs),* } }
+        }
+        Fields::Unnamed(_) => {
+            let defs = fields.iter().zip(&params).map(|(field, param)| {
+                let vis = &field.vis;
+                quote! { #vis #param }
+            });
+            quote! { #vis struct #init_ident<#(#params),*>(#(#defs),*); }
+        }
+        Fields::Unit => quote! { #vis struct #init_ident; },
+    };
+
+    // Write through the same code as `init!`, having first moved each field
+    // initializer out of `self` into the locals that code expects.
+    let members = pin_data::members(fields);
+    let values = (0..fields.len()).map(init::value_ident);
+    let struct_init = StructInit {
+        path: syn::parse_quote!(#ident),
+        fields: members
+            .iter()
+            .zip(fields)
+            .map(|(member, field)| FieldInit {
+                member: member.clone(),
+                kind: if is_pinned(field) {
+                    FieldKind::PinInit
+                } else {
+                    FieldKind::Init
+                },
+                expr: syn::parse_quote!(()),
+            })
+            .collect::<Punctuated<_, _>>(),
+    };
+    let dest = syn::Ident::new("__dest", Span::mixed_site());
+    let write = init::write_fields(&struct_init, &dest);
+    let destructure = quote! {
+        let #init_ident { #(#members: #values),* } = self;
+    };
+
+    // A struct ending in a slice or `str` is laid out from the length of the
+    // tail initializer. Its layout can only be computed without a value if
+    // the field order is fixed, so `#[repr(C)]` is required, and the layout
+    // is made available to other code through `LayoutProvider`.
+    let tail = fields
+        .iter()
+        .zip(&members)
+        .next_back()
+        .and_then(|(field, member)| Some((field, member, tail_element(&field.ty)?)));
+    let (layout, dest_ptr, provider) = match tail {
+        Some((field, member, element)) => {
+            if !repr.c {
+                let message =
+                    format!("`#[derive({derive})]` with an unsized field requires `#[repr(C)]`");
+                return Err(syn::Error::new_spanned(field, message));
+            }
+            let tail_ty = &field.ty;
+            let sized_tys = fields.iter().take(fields.len() - 1).map(|field| &field.ty);
+            let (struct_impl_generics, _, struct_where_clause) = input.generics.split_for_impl();
+            let len = quote! {
+                ::inplace::__private::tail_len::<#tail_ty>(
+                    ::inplace::PinInit::<#tail_ty>::dest(&self.#member, ::core::ptr::null_mut()),
+                )
+            };
+            let provider = quote! {
+                unsafe impl #struct_impl_generics ::inplace::dst::LayoutProvider
+                    for #ident #ty_generics
+                #struct_where_clause
+                {
+                    type Params = usize;
+
+                    fn layout(len: &usize) -> ::core::alloc::Layout {
+                        let prefix = ::core::alloc::Layout::new::<()>();
+                        #(
+                            let prefix = prefix
+                                .extend(::core::alloc::Layout::new::<#sized_tys>())
+                                .unwrap()
+                                .0;
+                        )*
+                        ::inplace::dst::tail_layout::<#element>(prefix, *len)
+                    }
+
+                    fn dest(addr: *mut u8, len: &usize) -> *mut Self {
+                        ::core::ptr::slice_from_raw_parts_mut(addr.cast::<#element>(), *len) as *mut Self
+                    }
+                }
+            };
+            (
+                quote! {
+                    <#ident #ty_generics as ::inplace::dst::LayoutProvider>::layout(&#len)
+                },
+                quote! {
+                    <#ident #ty_generics as ::inplace::dst::LayoutProvider>::dest(addr, &#len)
+                },
+                provider,
+            )
+        }
+        None => (
+            quote! { ::core::alloc::Layout::new::<#ident #ty_generics>() },
+            quote! { addr.cast() },
+            quote! {},
+        ),
+    };
+
+    let impls = if pinned {
+        let support = pin_data::pin_support(&input, fields);
+        quote! {
+            unsafe impl #impl_generics ::inplace::PinInit<#ident #ty_generics>
+                for #init_ident<#(#params),*>
+            #where_clause
+            {
+                fn layout(&self) -> ::core::alloc::Layout {
+                    #layout
+                }
+
+                fn dest(&self, addr: *mut u8) -> *mut #ident #ty_generics {
+                    #dest_ptr
+                }
+
+                unsafe fn pin_initialize(self, #dest: *mut #ident #ty_generics) {
+                    #destructure
+                    #write
+                }
+            }
+
+            #support
+        }
+    } else {
+        quote! {
+            unsafe impl #impl_generics ::inplace::PinInit<#ident #ty_generics>
+                for #init_ident<#(#params),*>
+            #where_clause
+            {
+                fn layout(&self) -> ::core::alloc::Layout {
+                    #layout
+                }
+
+                fn dest(&self, addr: *mut u8) -> *mut #ident #ty_generics {
+                    #dest_ptr
+                }
+
+                unsafe fn pin_initialize(self, dest: *mut #ident #ty_generics) {
+                    ::inplace::Init::initialize(self, dest)
+                }
+            }
+
+            unsafe impl #impl_generics ::inplace::Init<#ident #ty_generics>
+                for #init_ident<#(#params),*>
+            #where_clause
+            {
+                unsafe fn initialize(self, #dest: *mut #ident #ty_generics) {
+                    #destructure
+                    #write
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #[doc = #doc]
+        #definition
+
+        #impls
+
+        #provider
+    })
+}
@@ -0,0 +1,196 @@
+//! Implementation of the `init!` and `pin_init!` macros.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, Expr, Member, Path, Token};
+
+/// A struct expression whose fields are given as values or initializers.
+pub struct StructInit {
+    pub path: Path,
+    pub fields: Punctuated<FieldInit, Token![,]>,
+}
+
+/// How a single field is to be initialized.
+pub struct FieldInit {
+    pub member: Member,
+    pub kind: FieldKind,
+    pub expr: Expr,
+}
+
+pub enum FieldKind {
+    /// `field: value`, or the shorthand `field`.
+    Value,
+    /// `field <- init`.
+    Init,
+    /// `field <- init` where the initializer may be pinned.
+    PinInit,
+    /// `field <- init` in `pin_init!`, written through the struct's pin data
+    /// so that only `#[pin]` fields accept pinned initializers.
+    Project,
+}
+
+impl Parse for StructInit {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let content;
+        braced!(content in input);
+        let fields = content.parse_terminated(FieldInit::parse, Token![,])?;
+        Ok(StructInit { path, fields })
+    }
+}
+
+impl Parse for FieldInit {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let member: Member = input.parse()?;
+        if input.peek(Token![<-]) {
+            input.parse::<Token![<-]>()?;
+            let expr = input.parse()?;
+            Ok(FieldInit {
+                member,
+                kind: FieldKind::Init,
+                expr,
+            })
+        } else if input.peek(Token![:]) {
+            input.parse::<Token![:]>()?;
+            let expr = input.parse()?;
+            Ok(FieldInit {
+                member,
+                kind: FieldKind::Value,
+                expr,
+            })
+        } else if let Member::Named(ident) = &member {
+            let expr = syn::parse_quote!(#ident);
+            Ok(FieldInit {
+                member,
+                kind: FieldKind::Value,
+                expr,
+            })
+        } else {
+            Err(input.error("expected `:` or `<-` after field index"))
+        }
+    }
+}
+
+/// Generates the statements which write each field through `dest`, guarding
+/// each one so that it is dropped if a later field panics.
+///
+/// The field expressions must already have been evaluated into the locals
+/// named by `value_ident`.
+pub fn write_fields(input: &StructInit, dest: &syn::Ident) -> TokenStream {
+    let mut writes = Vec::new();
+    let mut guards = Vec::new();
+    for (index, field) in input.fields.iter().enumerate() {
+        let member = &field.member;
+        let value = value_ident(index);
+        let place = format_ident!("__place_{}", index, span = Span::mixed_site());
+        let guard = format_ident!("__guard_{}", index, span = Span::mixed_site());
+        let write = match field.kind {
+            FieldKind::Value => quote! { ::core::ptr::write(#place, #value); },
+            FieldKind::Init => quote! { ::inplace::Init::initialize(#value, #place); },
+            FieldKind::PinInit => {
+                quote! { ::inplace::PinInit::pin_initialize(#value, #place); }
+            }
+            FieldKind::Project => {
+                let method = projection_ident(member);
+                let pin_data = pin_data_ident();
+                quote! { #pin_data.#method(#place, #value); }
+            }
+        };
+        writes.push(quote! {
+            let #place = ::core::ptr::addr_of_mut!((*#dest).#member);
+            #write
+            let #guard = ::inplace::__private::DropGuard::new(#place);
+        });
+        guards.push(guard);
+    }
+    // Each field is written through a pointer to it, so it must be aligned.
+    // References to fields which may be unaligned in a packed struct are
+    // rejected by the compiler, even in code which never runs.
+    let members = input.fields.iter().map(|field| &field.member);
+    quote! {
+        if false {
+            #(let _ = &(*#dest).#members;)*
+        }
+        #(#writes)*
+        #(::core::mem::forget(#guards);)*
+    }
+}
+
+/// Generates the statements which evaluate each field expression in order.
+pub fn evaluate_fields(input: &StructInit) -> TokenStream {
+    let values = input.fields.iter().enumerate().map(|(index, field)| {
+        let value = value_ident(index);
+        let expr = &field.expr;
+        quote! { let #value = #expr; }
+    });
+    quote! { #(#values)* }
+}
+
+/// Generates a closure returning the struct, which is never called but checks
+/// that each field is named exactly once.
+pub fn check_fields(input: &StructInit) -> TokenStream {
+    let path = &input.path;
+    let members = input.fields.iter().map(|field| &field.member);
+    quote! {
+        || #path { #(#members: ::core::panic!(),)* }
+    }
+}
+
+pub fn value_ident(index: usize) -> syn::Ident {
+    format_ident!("__value_{}", index, span = Span::mixed_site())
+}
+
+/// Returns the name of the pin data method which initializes a field.
+pub fn projection_ident(member: &Member) -> syn::Ident {
+    match member {
+        Member::Named(ident) => ident.clone(),
+        Member::Unnamed(index) => format_ident!("_{}", index.index, span = index.span),
+    }
+}
+
+fn pin_data_ident() -> syn::Ident {
+    syn::Ident::new("__pin_data", Span::mixed_site())
+}
+
+pub fn expand(mut input: StructInit, pinned: bool) -> TokenStream {
+    // In a pinned initializer, fields given initializers are written through
+    // the pin data generated by `#[derive(PinInit)]` or `#[pin_data]`, which
+    // only accepts pinned initializers for `#[pin]` fields.
+    let mut project = false;
+    if pinned {
+        for field in &mut input.fields {
+            if let FieldKind::Init = field.kind {
+                field.kind = FieldKind::Project;
+                project = true;
+            }
+        }
+    }
+    let dest = syn::Ident::new("__dest", Span::mixed_site());
+    let evaluate = evaluate_fields(&input);
+    let check = check_fields(&input);
+    let write = write_fields(&input, &dest);
+    let constructor = if pinned {
+        quote!(pin_init_struct)
+    } else {
+        quote!(init_struct)
+    };
+    let pin_data = if project {
+        let pin_data = pin_data_ident();
+        quote! { let #pin_data = ::inplace::__private::pin_data(&__check); }
+    } else {
+        quote! {}
+    };
+    quote! {{
+        #evaluate
+        #[allow(unreachable_code, clippy::diverging_sub_expression)]
+        let __check = #check;
+        #pin_data
+        unsafe {
+            ::inplace::__private::#constructor(__check, move |#dest| {
+                #write
+            })
+        }
+    }}
+}
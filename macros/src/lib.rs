@@ -0,0 +1,392 @@
+//! Procedural macros for the `inplace` crate.
+//!
+//! These are re-exported by `inplace` and should be used from there; the
+//! generated code refers to items in that crate by absolute path.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod derive;
+mod init;
+mod pin_data;
+mod self_ref;
+
+/// Creates an initializer for a struct which constructs it field by field.
+///
+/// Each field is given either a value, with `field: value`, or an
+/// initializer for the field's type, with `field <- init`. The expressions are
+/// evaluated immediately, in the order written, and only the construction of
+/// the struct is deferred. When the resulting initializer is run, each field
+/// is written directly to its place in the destination, so a struct built from
+/// nested initializers is constructed in place as a whole, as described in the
+/// "Composition" section of the sketch.
+///
+/// Every field must be given exactly once. If a field initializer panics, the
+/// fields already written are dropped before the panic continues.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::{init, Init};
+///
+/// struct Bar {
+///     id: u32,
+///     data: [u8; 4096],
+/// }
+///
+/// fn create_data() -> impl Init<[u8; 4096]> {
+///     init::with(|| [0xff; 4096])
+/// }
+///
+/// let bar = init!(Bar {
+///     id: 1,
+///     data <- create_data(),
+/// });
+///
+/// let mut slot = MaybeUninit::<Bar>::uninit();
+/// unsafe { Init::<Bar>::initialize(bar, slot.as_mut_ptr()) };
+/// let bar = unsafe { slot.assume_init() };
+/// assert_eq!(bar.id, 1);
+/// assert!(bar.data.iter().all(|&b| b == 0xff));
+/// ```
+#[proc_macro]
+pub fn init(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as init::StructInit);
+    init::expand(input, false).into()
+}
+
+/// Creates a pinned initializer for a struct which constructs it field by
+/// field.
+///
+/// This accepts the same syntax as [`init!`], but produces a `PinInit`. This
+/// allows `!Unpin` values to be composed and constructed directly at their
+/// final address.
+///
+/// Fields given with `<-` are initialized through the pin data generated by
+/// [`#[derive(PinInit)]`](derive@PinInit) or [`#[pin_data]`](macro@pin_data),
+/// so the struct must use one of them. Fields
+/// marked `#[pin]` then accept pinned initializers, and other fields accept
+/// only ordinary initializers, so a pinned initializer can only be used for a
+/// field which the struct keeps structurally pinned. Structs which are given
+/// only values need no pin data.
+///
+/// # Example
+///
+/// ```
+/// use core::marker::PhantomPinned;
+/// use core::mem::MaybeUninit;
+/// use core::pin::Pin;
+/// use inplace::{pin_init, PinInit};
+///
+/// struct ListHead {
+///     len: usize,
+///     _pin: PhantomPinned,
+/// }
+///
+/// let head = pin_init!(ListHead {
+///     len: 0,
+///     _pin: PhantomPinned,
+/// });
+///
+/// let mut slot = MaybeUninit::<ListHead>::uninit();
+/// let head: Pin<&mut ListHead> = unsafe {
+///     PinInit::<ListHead>::pin_initialize(head, slot.as_mut_ptr());
+///     Pin::new_unchecked(slot.assume_init_mut())
+/// };
+/// assert_eq!(head.len, 0);
+/// # unsafe { slot.assume_init_drop() };
+/// ```
+///
+/// Pinned values nest by giving their initializers to `#[pin]` fields:
+///
+/// ```
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use inplace::{init, pin_init, BoxExt, PinInit};
+///
+/// struct Waiter {
+///     _pin: PhantomPinned,
+/// }
+///
+/// #[derive(PinInit)]
+/// struct Queue {
+///     #[pin]
+///     head: Waiter,
+///     buf: [u8; 256],
+/// }
+///
+/// let queue: Pin<Box<Queue>> = Box::pin_emplace(pin_init!(Queue {
+///     head <- pin_init!(Waiter { _pin: PhantomPinned }),
+///     buf <- init::with(|| [0; 256]),
+/// }));
+/// assert_eq!(queue.buf.len(), 256);
+/// ```
+///
+/// A pinned initializer for a field which is not `#[pin]` is rejected:
+///
+/// ```compile_fail
+/// # use core::marker::PhantomPinned;
+/// # use inplace::{pin_init, PinInit};
+/// # struct Waiter {
+/// #     _pin: PhantomPinned,
+/// # }
+/// #[derive(PinInit)]
+/// struct Queue {
+///     head: Waiter,
+/// }
+///
+/// let queue = pin_init!(Queue {
+///     head <- pin_init!(Waiter { _pin: PhantomPinned }),
+/// });
+/// ```
+#[proc_macro]
+pub fn pin_init(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as init::StructInit);
+    init::expand(input, true).into()
+}
+
+/// Derives an initializer type for a struct.
+///
+/// For a struct `Foo`, this generates a struct `FooInit` with the same
+/// visibility and the same fields, except that each field of `FooInit` holds
+/// an initializer for the corresponding field of `Foo`. Since any value is an
+/// initializer for itself, each field can be given either a value or a nested
+/// initializer. `FooInit` implements `Init<Foo>`, writing each field directly
+/// to its place in the destination. Fields are written through pointers to
+/// them, so `#[repr(packed)]` structs are not supported.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::{init, Init};
+///
+/// #[derive(Init)]
+/// struct Packet {
+///     header: u32,
+///     payload: [u8; 1500],
+/// }
+///
+/// let packet = PacketInit {
+///     header: 1,
+///     payload: init::with(|| [0xaa; 1500]),
+/// };
+///
+/// let mut slot = MaybeUninit::<Packet>::uninit();
+/// unsafe { Init::<Packet>::initialize(packet, slot.as_mut_ptr()) };
+/// let packet = unsafe { slot.assume_init() };
+/// assert_eq!(packet.header, 1);
+/// assert_eq!(packet.payload[1499], 0xaa);
+/// ```
+///
+/// # Unsized tails
+///
+/// The last field of the struct may be a slice or `str`, as in the kernel
+/// style of slice-tailed struct. The length of the value is then taken from
+/// the initializer for that field, and the combined layout is computed from
+/// it. Since this requires the fields to be laid out in order, the struct
+/// must be `#[repr(C)]`. The struct also gets an implementation of
+/// `inplace::dst::LayoutProvider`, parameterized by the tail length.
+///
+/// ```
+/// use inplace::{boxed_slice, BoxExt, Init};
+///
+/// #[derive(Init)]
+/// #[repr(C)]
+/// struct Message {
+///     id: u16,
+///     flags: u32,
+///     body: [u64],
+/// }
+///
+/// let message: Box<Message> = Box::emplace(MessageInit {
+///     id: 3,
+///     flags: 0,
+///     body: boxed_slice::from_fn(4, |i, slot| slot.write(i as u64)),
+/// });
+/// assert_eq!(message.id, 3);
+/// assert_eq!(&message.body, &[0, 1, 2, 3]);
+/// ```
+#[proc_macro_derive(Init, attributes(pin, pinned_drop))]
+pub fn derive_init(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::expand(input, false)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives a pinned initializer type for a struct.
+///
+/// This generates a `FooInit` struct in the same way as `#[derive(Init)]`,
+/// but which implements only `PinInit<Foo>`. Fields marked `#[pin]` are
+/// structurally pinned, and accept pinned initializers; other fields accept
+/// ordinary initializers, and are never considered pinned.
+///
+/// The struct also gets pin data, which allows it to be constructed with
+/// [`pin_init!`] using the same rules for each field.
+///
+/// As with `pin-project`, `Foo` is made `Unpin` only if all of its `#[pin]`
+/// fields are `Unpin`, and `Foo` may not implement `Drop`, since a destructor
+/// receiving `&mut Foo` could move its pinned fields. A struct which needs a
+/// destructor can instead be marked `#[pinned_drop]` and implement
+/// `inplace::PinnedDrop`, which receives `Pin<&mut Foo>`.
+///
+/// # Example
+///
+/// ```
+/// use core::marker::PhantomPinned;
+/// use core::mem::MaybeUninit;
+/// use core::pin::Pin;
+/// use inplace::{pin_init, PinInit};
+///
+/// struct Node {
+///     _pin: PhantomPinned,
+/// }
+///
+/// #[derive(PinInit)]
+/// struct Entry {
+///     #[pin]
+///     node: Node,
+///     value: u32,
+/// }
+///
+/// let entry = EntryInit {
+///     node: pin_init!(Node { _pin: PhantomPinned }),
+///     value: 7,
+/// };
+///
+/// let mut slot = MaybeUninit::<Entry>::uninit();
+/// let entry: Pin<&mut Entry> = unsafe {
+///     PinInit::<Entry>::pin_initialize(entry, slot.as_mut_ptr());
+///     Pin::new_unchecked(slot.assume_init_mut())
+/// };
+/// assert_eq!(entry.value, 7);
+/// ```
+#[proc_macro_derive(PinInit, attributes(pin, pinned_drop))]
+pub fn derive_pin_init(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::expand(input, true)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Adds pin support to a struct, without generating an initializer type.
+///
+/// This provides everything that `#[derive(PinInit)]` does except the
+/// `FooInit` struct: fields marked `#[pin]` are structurally pinned, the
+/// struct is `Unpin` only if they are, it may not implement `Drop` unless
+/// marked `#[pinned_drop]`, and it gets pin data so that it can be constructed
+/// in place with [`pin_init!`]. In addition, a `project` method is generated,
+/// which turns a `Pin<&mut Foo>` into a `FooProjection` holding a
+/// `Pin<&mut _>` for each `#[pin]` field and a `&mut _` for every other.
+///
+/// The struct should not also derive `PinInit`. A `#[pinned_drop]` attribute
+/// must come after `#[pin_data]`.
+///
+/// # Example
+///
+/// ```
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use inplace::{pin_data, pin_init, BoxExt};
+///
+/// struct Timer {
+///     _pin: PhantomPinned,
+/// }
+///
+/// #[pin_data]
+/// struct Work {
+///     #[pin]
+///     timer: Timer,
+///     runs: u32,
+/// }
+///
+/// let mut work: Pin<Box<Work>> = Box::pin_emplace(pin_init!(Work {
+///     timer <- pin_init!(Timer { _pin: PhantomPinned }),
+///     runs: 0,
+/// }));
+///
+/// let work = work.as_mut().project();
+/// let _timer: Pin<&mut Timer> = work.timer;
+/// *work.runs += 1;
+/// ```
+#[proc_macro_attribute]
+pub fn pin_data(args: TokenStream, input: TokenStream) -> TokenStream {
+    parse_macro_input!(args as syn::parse::Nothing);
+    let input = parse_macro_input!(input as DeriveInput);
+    pin_data::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Makes a struct whose fields can borrow its earlier fields.
+///
+/// A field marked `#[borrows(a, b)]` may hold references to the fields `a`
+/// and `b`, written with the lifetime `'this`. Such a struct can only exist at
+/// a fixed address, so rather than moving its owned fields to the heap, as
+/// crates like `ouroboros` do, this generates a pinned in-place constructor:
+///
+/// - `new` takes an initializer for each owned field, and a builder closure
+///   named `field_builder` for each borrowing field. When the initializer is
+///   run, the fields are constructed in order at their final address, and each
+///   builder receives references to the fields it borrows there.
+/// - `borrow_field` returns a reference to an owned field.
+/// - `with_field` calls a closure with a reference to a borrowing field. The
+///   closure is generic over `'this`, so the borrows cannot escape it.
+///
+/// No field can be accessed mutably, since a borrowed field must not change.
+/// Fields are dropped in the reverse of their declared order, so that each is
+/// dropped before the fields it borrows. The struct is never `Unpin`, and
+/// implementing `Unpin` or `Drop` for it is rejected, as either would allow
+/// its fields to be moved while borrowed.
+///
+/// As with `ouroboros`, the struct is declared in a hidden module and
+/// re-exported, so that its fields cannot be named, even in the module where
+/// it is written, and are only reached through the generated methods:
+///
+/// ```compile_fail
+/// use inplace::self_referencing;
+///
+/// #[self_referencing]
+/// struct Document {
+///     text: String,
+///     #[borrows(text)]
+///     words: Vec<&'this str>,
+/// }
+///
+/// // The borrows would outlive the document.
+/// fn leak(doc: &Document) -> Vec<&'static str> {
+///     doc.words.clone()
+/// }
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use core::pin::Pin;
+/// use inplace::{self_referencing, BoxExt};
+///
+/// #[self_referencing]
+/// struct Document {
+///     text: String,
+///     #[borrows(text)]
+///     words: Vec<&'this str>,
+/// }
+///
+/// let doc: Pin<Box<Document>> = Box::pin_emplace(Document::new(
+///     String::from("in place at last"),
+///     |text| text.split(' ').collect(),
+/// ));
+/// assert_eq!(doc.borrow_text(), "in place at last");
+/// assert_eq!(doc.with_words(|words| words.len()), 4);
+/// doc.with_words(|words| assert_eq!(words[2], "at"));
+/// ```
+#[proc_macro_attribute]
+pub fn self_referencing(args: TokenStream, input: TokenStream) -> TokenStream {
+    parse_macro_input!(args as syn::parse::Nothing);
+    let input = parse_macro_input!(input as DeriveInput);
+    self_ref::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
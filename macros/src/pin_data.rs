@@ -0,0 +1,261 @@
+//! Implementation of `#[pin_data]`, and the pin support it shares with
+//! `#[derive(PinInit)]`.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Field, Fields, Member};
+
+use crate::init;
+
+/// Returns whether a field is marked `#[pin]`.
+pub fn is_pinned(field: &Field) -> bool {
+    field.attrs.iter().any(|attr| attr.path().is_ident("pin"))
+}
+
+/// Returns whether a struct is marked `#[pinned_drop]`.
+pub fn has_pinned_drop(input: &DeriveInput) -> Option<&syn::Attribute> {
+    input
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("pinned_drop"))
+}
+
+/// Returns the member used to access each field.
+pub fn members(fields: &Fields) -> Vec<Member> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(name) => Member::Named(name.clone()),
+            None => Member::Unnamed(index.into()),
+        })
+        .collect()
+}
+
+/// Generates the `Unpin` impl, the `Drop` check or `PinnedDrop` glue, and the
+/// pin data of a pinned struct.
+pub fn pin_support(input: &DeriveInput, fields: &Fields) -> TokenStream {
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let members = members(fields);
+    let pinned_types = fields
+        .iter()
+        .filter(|field| is_pinned(field))
+        .map(|field| &field.ty);
+    let (struct_impl_generics, ty_generics, struct_where_clause) = input.generics.split_for_impl();
+    let mut unpin_generics = input.generics.clone();
+    unpin_generics.params.insert(0, syn::parse_quote!('__pin));
+    unpin_generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(
+            ::inplace::__private::PinnedFields<'__pin, (#(#pinned_types,)*)>: ::core::marker::Unpin
+        ));
+    let (unpin_impl_generics, _, unpin_where_clause) = unpin_generics.split_for_impl();
+    let must_not_drop = format_ident!("{}MustNotImplDrop", ident);
+    let drop = if has_pinned_drop(input).is_some() {
+        quote! {
+            impl #struct_impl_generics ::core::ops::Drop for #ident #ty_generics
+            #struct_where_clause
+            {
+                fn drop(&mut self) {
+                    // The value is never used again, so it can be treated
+                    // as pinned until it is gone.
+                    let this = unsafe { ::core::pin::Pin::new_unchecked(self) };
+                    let token = unsafe { ::inplace::pin::OnlyCallFromDrop::new() };
+                    ::inplace::PinnedDrop::drop(this, token);
+                }
+            }
+        }
+    } else {
+        quote! {
+            trait #must_not_drop {}
+            #[allow(drop_bounds)]
+            impl<T: ?::core::marker::Sized + ::core::ops::Drop> #must_not_drop for T {}
+            impl #struct_impl_generics #must_not_drop for #ident #ty_generics
+            #struct_where_clause
+            {
+            }
+        }
+    };
+    let pin_data = format_ident!("{}PinData", ident);
+    let struct_generics = &input.generics;
+    let projections = fields.iter().zip(&members).map(|(field, member)| {
+        let vis = &field.vis;
+        let ty = &field.ty;
+        let method = init::projection_ident(member);
+        if is_pinned(field) {
+            quote! {
+                #vis unsafe fn #method<__I: ::inplace::PinInit<#ty>>(self, place: *mut #ty, init: __I) {
+                    ::inplace::PinInit::pin_initialize(init, place)
+                }
+            }
+        } else {
+            quote! {
+                #vis unsafe fn #method<__I: ::inplace::Init<#ty>>(self, place: *mut #ty, init: __I) {
+                    ::inplace::Init::initialize(init, place)
+                }
+            }
+        }
+    });
+    quote! {
+        // As with pin-project, the struct is only `Unpin` if its pinned
+        // fields are, and providing another `Unpin` impl, or a `Drop` impl
+        // which could move pinned fields, is rejected as a conflict. A
+        // `#[pinned_drop]` struct gets a `Drop` impl calling `PinnedDrop`.
+        const _: () = {
+            impl #unpin_impl_generics ::core::marker::Unpin for #ident #ty_generics
+            #unpin_where_clause
+            {
+            }
+
+            #drop
+
+            // Lets `pin_init!` initialize each field in place, accepting
+            // pinned initializers only for the `#[pin]` fields.
+            #vis struct #pin_data #struct_generics (
+                ::core::marker::PhantomData<fn() -> #ident #ty_generics>,
+            )
+            #struct_where_clause;
+
+            impl #struct_impl_generics ::core::clone::Clone for #pin_data #ty_generics
+            #struct_where_clause
+            {
+                fn clone(&self) -> Self {
+                    *self
+                }
+            }
+
+            impl #struct_impl_generics ::core::marker::Copy for #pin_data #ty_generics
+            #struct_where_clause
+            {
+            }
+
+            #[allow(dead_code)]
+            impl #struct_impl_generics #pin_data #ty_generics
+            #struct_where_clause
+            {
+                #(#projections)*
+            }
+
+            unsafe impl #struct_impl_generics ::inplace::__private::HasPinData
+                for #ident #ty_generics
+            #struct_where_clause
+            {
+                type PinData = #pin_data #ty_generics;
+
+                fn pin_data() -> Self::PinData {
+                    #pin_data(::core::marker::PhantomData)
+                }
+            }
+        };
+    }
+}
+
+/// Generates the `project` method of a pinned struct and the type it returns.
+fn projection(input: &DeriveInput, fields: &Fields) -> TokenStream {
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let projection = format_ident!("{}Projection", ident);
+    let members = members(fields);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut projection_generics = input.generics.clone();
+    projection_generics
+        .params
+        .insert(0, syn::parse_quote!('__pin));
+    let (_, projection_ty_generics, _) = projection_generics.split_for_impl();
+
+    let types = fields.iter().map(|field| {
+        let ty = &field.ty;
+        if is_pinned(field) {
+            quote! { ::core::pin::Pin<&'__pin mut #ty> }
+        } else {
+            quote! { &'__pin mut #ty }
+        }
+    });
+    let values = fields.iter().zip(&members).map(|(field, member)| {
+        if is_pinned(field) {
+            quote! { #member: unsafe { ::core::pin::Pin::new_unchecked(&mut this.#member) } }
+        } else {
+            quote! { #member: &mut this.#member }
+        }
+    });
+    let definition = match fields {
+        Fields::Named(_) => {
+            let defs = fields.iter().zip(types).map(|(field, ty)| {
+                let vis = &field.vis;
+                let name = &field.ident;
+                quote! { #vis #name: #ty }
+            });
+            quote! {
+                #vis struct #projection #projection_generics #where_clause { #(#defs),* }
+            }
+        }
+        Fields::Unnamed(_) => {
+            let defs = fields.iter().zip(types).map(|(field, ty)| {
+                let vis = &field.vis;
+                quote! { #vis #ty }
+            });
+            quote! {
+                #vis struct #projection #projection_generics (#(#defs),*) #where_clause;
+            }
+        }
+        Fields::Unit => quote! { #vis struct #projection #projection_generics #where_clause; },
+    };
+
+    let doc =
+        format!("Projection of a pinned [`{ident}`] to its fields, generated by `#[pin_data]`.");
+    quote! {
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #definition
+
+        #[allow(dead_code)]
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Projects a pinned reference to references to each field,
+            /// pinned only for `#[pin]` fields.
+            #vis fn project<'__pin>(
+                self: ::core::pin::Pin<&'__pin mut Self>,
+            ) -> #projection #projection_ty_generics {
+                // Only `#[pin]` fields are exposed pinned, and they are never
+                // moved, since the struct only has a destructor through
+                // `PinnedDrop`.
+                let this = unsafe { ::core::pin::Pin::get_unchecked_mut(self) };
+                #projection { #(#values),* }
+            }
+        }
+    }
+}
+
+pub fn expand(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => data.fields.clone(),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`#[pin_data]` is only supported on structs",
+            ))
+        }
+    };
+    let support = pin_support(&input, &fields);
+    let projection = projection(&input, &fields);
+
+    // The helper attributes are not registered by an attribute macro, so
+    // they are removed from the struct once they have been used.
+    input
+        .attrs
+        .retain(|attr| !attr.path().is_ident("pinned_drop"));
+    if let Data::Struct(data) = &mut input.data {
+        for field in data.fields.iter_mut() {
+            field.attrs.retain(|attr| !attr.path().is_ident("pin"));
+        }
+    }
+
+    Ok(quote! {
+        #input
+
+        #support
+
+        #projection
+    })
+}
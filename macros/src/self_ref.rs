@@ -0,0 +1,306 @@
+//! Implementation of `#[self_referencing]`.
+
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::{Data, DeriveInput, Fields, Ident, Token, Type, Visibility};
+
+/// A field of a self-referencing struct.
+struct SelfRefField {
+    ident: Ident,
+    ty: Type,
+    /// The earlier fields this field borrows, from `#[borrows(...)]`, or
+    /// `None` if it is an owned field.
+    borrows: Option<Vec<Ident>>,
+}
+
+/// Replaces the lifetime `'this` in a type with `lifetime`, returning whether
+/// it was found.
+fn replace_this(tokens: TokenStream, lifetime: &str, found: &mut bool) -> TokenStream {
+    let mut output = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '\'' => match tokens.peek() {
+                Some(TokenTree::Ident(ident)) if ident == "this" => {
+                    *found = true;
+                    let ident = Ident::new(lifetime, ident.span());
+                    tokens.next();
+                    output.push(TokenTree::Punct(punct));
+                    output.push(TokenTree::Ident(ident));
+                }
+                _ => output.push(TokenTree::Punct(punct)),
+            },
+            TokenTree::Group(group) => {
+                let stream = replace_this(group.stream(), lifetime, found);
+                let mut replaced = Group::new(group.delimiter(), stream);
+                replaced.set_span(group.span());
+                output.push(TokenTree::Group(replaced));
+            }
+            token => output.push(token),
+        }
+    }
+    output.into_iter().collect()
+}
+
+/// Returns a type with `'this` replaced by `'static`, for storage.
+fn erase_this(ty: &Type) -> syn::Result<Type> {
+    let mut found = false;
+    syn::parse2(replace_this(ty.to_token_stream(), "static", &mut found))
+}
+
+fn mentions_this(ty: &Type) -> bool {
+    let mut found = false;
+    replace_this(ty.to_token_stream(), "this", &mut found);
+    found
+}
+
+/// Returns the name of the module the struct is declared in, such as
+/// `__self_referencing_my_struct` for `MyStruct`.
+fn module_name(ident: &Ident) -> Ident {
+    let mut name = String::from("__self_referencing");
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() || i == 0 {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+    Ident::new(&name, Span::mixed_site())
+}
+
+/// Returns the visibility inside the struct's module which reaches as far as
+/// `vis` does outside it.
+fn widen(vis: &Visibility) -> Visibility {
+    match vis {
+        Visibility::Inherited => syn::parse_quote!(pub(super)),
+        Visibility::Public(_) => vis.clone(),
+        Visibility::Restricted(restricted) => {
+            let path = &restricted.path;
+            let first = path
+                .segments
+                .first()
+                .map(|segment| segment.ident.to_string());
+            match first.as_deref() {
+                Some("crate") if path.leading_colon.is_none() => vis.clone(),
+                Some("self") if path.segments.len() == 1 => syn::parse_quote!(pub(super)),
+                Some("self") => {
+                    let rest = path.segments.iter().skip(1);
+                    syn::parse_quote!(pub(in super #(::#rest)*))
+                }
+                _ => syn::parse_quote!(pub(in super::#path)),
+            }
+        }
+    }
+}
+
+pub fn expand(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let named = match &mut input.data {
+        Data::Struct(data) => match &mut data.fields {
+            Fields::Named(named) => named,
+            fields => {
+                return Err(syn::Error::new_spanned(
+                    fields,
+                    "`#[self_referencing]` requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`#[self_referencing]` is only supported on structs",
+            ))
+        }
+    };
+
+    // Collect the fields, checking that each only borrows earlier fields.
+    let mut fields: Vec<SelfRefField> = Vec::new();
+    for field in &named.named {
+        let ident = field.ident.clone().unwrap();
+        let mut borrows = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("borrows"))
+        {
+            let names = attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?;
+            for name in &names {
+                if !fields.iter().any(|field| field.ident == *name) {
+                    return Err(syn::Error::new_spanned(
+                        name,
+                        "a field can only borrow fields declared before it",
+                    ));
+                }
+            }
+            borrows = Some(names.into_iter().collect());
+        }
+        if borrows.is_none() && mentions_this(&field.ty) {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "a field using `'this` must say which fields it borrows with `#[borrows(...)]`",
+            ));
+        }
+        fields.push(SelfRefField {
+            ident,
+            ty: field.ty.clone(),
+            borrows,
+        });
+    }
+
+    // The stored fields are private, have `'this` erased, and are declared
+    // in reverse, so that each is dropped before the fields it borrows.
+    let mut stored = Punctuated::<syn::Field, Token![,]>::new();
+    for field in named.named.iter().rev() {
+        let mut field = field.clone();
+        field.attrs.retain(|attr| !attr.path().is_ident("borrows"));
+        field.vis = syn::Visibility::Inherited;
+        field.ty = erase_this(&field.ty)?;
+        stored.push(field);
+    }
+    let pin = Ident::new("__pin", Span::mixed_site());
+    stored.push(syn::parse_quote!(#pin: ::core::marker::PhantomPinned));
+    named.named = stored;
+
+    // The struct is declared in a module of its own and re-exported, as
+    // `ouroboros` does, so that its fields, whose `'this` is stored as
+    // `'static`, cannot be named even in the module it was written in.
+    let outer_vis = input.vis.clone();
+    input.vis = widen(&input.vis);
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let module = module_name(ident);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // The constructor takes an initializer for each owned field, and a
+    // builder for each borrowing field, which receives the fields it borrows
+    // at their final address.
+    let dest = Ident::new("__dest", Span::mixed_site());
+    let mut params = Vec::new();
+    let mut writes = Vec::new();
+    let mut guards = Vec::new();
+    for field in &fields {
+        let name = &field.ident;
+        let ty = &field.ty;
+        let place = format_ident!("__place_{}", name, span = Span::mixed_site());
+        let guard = format_ident!("__guard_{}", name, span = Span::mixed_site());
+        match &field.borrows {
+            None => {
+                params.push(quote! { #name: impl ::inplace::Init<#ty> });
+                writes.push(quote! {
+                    let #place = ::core::ptr::addr_of_mut!((*#dest).#name);
+                    ::inplace::Init::initialize(#name, #place);
+                });
+            }
+            Some(borrows) => {
+                let builder = format_ident!("{}_builder", name);
+                let borrowed_tys = borrows.iter().map(|borrow| {
+                    let ty = &fields
+                        .iter()
+                        .find(|field| field.ident == *borrow)
+                        .unwrap()
+                        .ty;
+                    quote! { &'this #ty }
+                });
+                params.push(quote! {
+                    #builder: impl for<'this> ::core::ops::FnOnce(#(#borrowed_tys),*) -> #ty
+                });
+                let args = borrows.iter().map(|borrow| {
+                    quote! { ::inplace::__private::ref_unbound(::core::ptr::addr_of!((*#dest).#borrow)) }
+                });
+                writes.push(quote! {
+                    let #place = ::core::ptr::addr_of_mut!((*#dest).#name);
+                    ::inplace::__private::write_unbound(#place, #builder(#(#args),*));
+                });
+            }
+        }
+        writes.push(quote! {
+            let #guard = ::inplace::__private::DropGuard::new(#place);
+        });
+        guards.push(guard);
+    }
+
+    let accessors = fields.iter().map(|field| {
+        let name = &field.ident;
+        let ty = &field.ty;
+        if field.borrows.is_none() && !mentions_this(ty) {
+            let borrow = format_ident!("borrow_{}", name);
+            let doc = format!("Returns a reference to `{name}`.");
+            quote! {
+                #[doc = #doc]
+                #vis fn #borrow(&self) -> &#ty {
+                    &self.#name
+                }
+            }
+        } else {
+            let with = format_ident!("with_{}", name);
+            let doc = format!(
+                "Calls `f` with a reference to `{name}`, whose borrows of the other fields \
+                 cannot outlive the call."
+            );
+            quote! {
+                #[doc = #doc]
+                #vis fn #with<__R>(
+                    &self,
+                    f: impl for<'this> ::core::ops::FnOnce(&'this #ty) -> __R,
+                ) -> __R {
+                    f(unsafe { ::inplace::__private::ref_unbound(::core::ptr::addr_of!(self.#name)) })
+                }
+            }
+        }
+    });
+
+    // The borrowing fields point into the owned fields, so the struct must
+    // never move or be moved out of once constructed. As for `#[pin_data]`,
+    // an `Unpin` impl which never applies makes any other `Unpin` impl a
+    // conflict, and so does a `Drop` impl, which could move fields.
+    let mut unpin_generics = input.generics.clone();
+    unpin_generics.params.insert(0, syn::parse_quote!('__pin));
+    unpin_generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(
+            ::inplace::__private::PinnedFields<'__pin, (::core::marker::PhantomPinned,)>:
+                ::core::marker::Unpin
+        ));
+    let (unpin_impl_generics, _, unpin_where_clause) = unpin_generics.split_for_impl();
+    let must_not_drop = format_ident!("{}MustNotImplDrop", ident);
+
+    Ok(quote! {
+        #[doc(hidden)]
+        mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #input
+
+            const _: () = {
+                impl #unpin_impl_generics ::core::marker::Unpin for #ident #ty_generics
+                #unpin_where_clause
+                {
+                }
+
+                trait #must_not_drop {}
+                #[allow(drop_bounds)]
+                impl<T: ?::core::marker::Sized + ::core::ops::Drop> #must_not_drop for T {}
+                impl #impl_generics #must_not_drop for #ident #ty_generics #where_clause {}
+            };
+
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// Creates a pinned initializer for the struct, which constructs
+                /// each field in order at its final address.
+                #vis fn new(#(#params),*) -> impl ::inplace::PinInit<Self> {
+                    unsafe {
+                        ::inplace::init::pin_from_raw(move |#dest: *mut Self| {
+                            #(#writes)*
+                            ::core::ptr::addr_of_mut!((*#dest).#pin).write(::core::marker::PhantomPinned);
+                            #(::core::mem::forget(#guards);)*
+                        })
+                    }
+                }
+
+                #(#accessors)*
+            }
+        }
+
+        #outer_vis use #module::#ident;
+    })
+}
@@ -0,0 +1,187 @@
+//! Support code for the macros in this crate. Not public API.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+
+use crate::init::{self, FromRaw, PinFromRaw};
+use crate::slot::Slot;
+
+/// Drops a value in place unless forgotten.
+///
+/// Used while a struct is being initialized field by field, so that fields
+/// which were written are dropped if initialization of a later field panics.
+pub struct DropGuard<T: ?Sized>(*mut T);
+
+impl<T: ?Sized> DropGuard<T> {
+    /// # Safety
+    ///
+    /// `ptr` must point to an initialized value which is not otherwise dropped
+    /// unless the guard is forgotten.
+    pub unsafe fn new(ptr: *mut T) -> Self {
+        DropGuard(ptr)
+    }
+}
+
+impl<T: ?Sized> Drop for DropGuard<T> {
+    fn drop(&mut self) {
+        unsafe { self.0.drop_in_place() }
+    }
+}
+
+/// Aborts the process if dropped during unwinding, by panicking again.
+///
+/// Held while a place is left without a value that its owner could drop.
+pub(crate) struct AbortOnUnwind;
+
+impl Drop for AbortOnUnwind {
+    fn drop(&mut self) {
+        panic!("panicked while replacing a value in place; aborting");
+    }
+}
+
+/// Creates an initializer for the struct returned by `_check`.
+///
+/// The closure is never called. It names the struct being initialized, so
+/// that its type, including any inferred generic parameters, is known when
+/// type checking `f`.
+///
+/// # Safety
+///
+/// As for [`init::from_raw`].
+pub unsafe fn init_struct<T, C, F>(_check: C, f: F) -> FromRaw<T, F>
+where
+    C: FnOnce() -> T,
+    F: FnOnce(*mut T),
+{
+    init::from_raw(f)
+}
+
+/// Creates a pinned initializer for the struct returned by `_check`.
+///
+/// # Safety
+///
+/// As for [`init::pin_from_raw`].
+pub unsafe fn pin_init_struct<T, C, F>(_check: C, f: F) -> PinFromRaw<T, F>
+where
+    C: FnOnce() -> T,
+    F: FnOnce(*mut T),
+{
+    init::pin_from_raw(f)
+}
+
+/// Structs with pin data, generated by `#[derive(PinInit)]` or `#[pin_data]`.
+///
+/// The pin data has a method for each field, named after it, which
+/// initializes that field in place. The methods for `#[pin]` fields accept
+/// pinned initializers, and the others accept only ordinary initializers.
+///
+/// # Safety
+///
+/// Each method must initialize the place it is given as the corresponding
+/// field of `Self`, and may only accept a pinned initializer if that field
+/// is structurally pinned.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` has no pin data for `pin_init!`",
+    note = "derive `PinInit` or add `#[pin_data]` to the struct, marking its structurally pinned fields `#[pin]`"
+)]
+pub unsafe trait HasPinData {
+    type PinData: Copy;
+
+    fn pin_data() -> Self::PinData;
+}
+
+/// Returns the pin data of the struct returned by `_check`.
+pub fn pin_data<T, C>(_check: &C) -> T::PinData
+where
+    T: HasPinData,
+    C: FnOnce() -> T,
+{
+    T::pin_data()
+}
+
+/// `Unpin` exactly when `T` is.
+///
+/// Used by `#[derive(PinInit)]` in the bounds of the `Unpin` impl it
+/// generates. The lifetime keeps the bound from being trivially false, which
+/// would be rejected when the struct is not generic.
+pub struct PinnedFields<'a, T: ?Sized>(PhantomData<&'a ()>, PhantomData<T>);
+
+/// Unsized types which can end a struct with a derived initializer.
+pub trait Tail {
+    /// Returns the length of the tail from a pointer to it.
+    fn len(ptr: *mut Self) -> usize;
+}
+
+impl<T> Tail for [T] {
+    fn len(ptr: *mut [T]) -> usize {
+        ptr.len()
+    }
+}
+
+impl Tail for str {
+    fn len(ptr: *mut str) -> usize {
+        (ptr as *mut [u8]).len()
+    }
+}
+
+/// Returns the length of a tail field from a pointer to it.
+pub fn tail_len<T: ?Sized + Tail>(ptr: *mut T) -> usize {
+    T::len(ptr)
+}
+
+/// Writes a value through a pointer to a type which differs from it only in
+/// lifetimes.
+///
+/// Used by `#[self_referencing]` to store fields whose `'this` lifetime has
+/// been erased.
+///
+/// # Safety
+///
+/// `T` and `U` must differ only in lifetimes, and `place` must be valid for
+/// writes.
+pub unsafe fn write_unbound<T, U>(place: *mut T, value: U) {
+    place.cast::<U>().write(value)
+}
+
+/// Borrows a value as a type which differs from it only in lifetimes.
+///
+/// # Safety
+///
+/// `T` and `U` must differ only in lifetimes, and `ptr` must point to a value
+/// which remains valid and unchanged for `'a`.
+pub unsafe fn ref_unbound<'a, T, U>(ptr: *const T) -> &'a U {
+    &*ptr.cast::<U>()
+}
+
+/// Storage for a slot declared by `slot!`, which drops any value left in it.
+pub struct SlotStorage<T> {
+    value: MaybeUninit<T>,
+    live: Cell<bool>,
+}
+
+impl<T> SlotStorage<T> {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        SlotStorage {
+            value: MaybeUninit::uninit(),
+            live: Cell::new(false),
+        }
+    }
+
+    pub fn slot(&mut self) -> Slot<'_, T> {
+        Slot {
+            storage: &mut self.value,
+            live: Some(&self.live),
+            pinnable: true,
+        }
+    }
+}
+
+impl<T> Drop for SlotStorage<T> {
+    fn drop(&mut self) {
+        if self.live.get() {
+            unsafe { self.value.assume_init_drop() }
+        }
+    }
+}
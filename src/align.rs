@@ -0,0 +1,168 @@
+//! Values placed at a stricter alignment than their type requires.
+//!
+//! Every placement backend in this crate allocates with the layout of the
+//! value being constructed, so the way to ask for a cache-line or page
+//! boundary is to construct a type with that alignment. [`Aligned`] wraps a
+//! value in such a type, with the alignment taken from a zero-sized marker,
+//! and [`aligned`] wraps an initializer to construct it in place. The
+//! alignment is then honored by `Box`, `Rc`, `Vec`, the arenas and the memory
+//! mappings alike, and the value is freed with the same layout it was
+//! allocated with.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(not(inplace_no_global_oom_handling))]
+//! # fn main() {
+//! use inplace::align::{self, Aligned, CacheLine, Page};
+//! use inplace::arena::Arena;
+//! use inplace::{init, BoxExt};
+//!
+//! let counters: Box<Aligned<CacheLine, [u64; 4]>> = Box::emplace(align::aligned([0; 4]));
+//! assert_eq!(counters.as_ptr() as usize % 64, 0);
+//!
+//! let arena = Arena::new();
+//! let _ = arena.emplace::<u8, _>(1);
+//! let buf: &mut Aligned<Page, [u8; 8192]> =
+//!     arena.emplace(align::aligned(init::with(|| [0; 8192])));
+//! assert_eq!(buf.as_ptr() as usize % 4096, 0);
+//!
+//! let slots: Vec<Aligned<CacheLine, u32>> = (0..8).map(Aligned::new).collect();
+//! assert!(slots.iter().all(|slot| slot as *const _ as usize % 64 == 0));
+//! # }
+//! # #[cfg(inplace_no_global_oom_handling)]
+//! # fn main() {}
+//! ```
+
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+
+use crate::init::{Init, PinInit, TryInit};
+
+/// A marker for alignment to 64 bytes, the size of a cache line on most
+/// processors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(align(64))]
+pub struct CacheLine;
+
+/// A marker for alignment to 4096 bytes, the size of the smallest page on
+/// most processors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(align(4096))]
+pub struct Page;
+
+/// A `T` aligned to at least the alignment of the marker type `A`.
+///
+/// Any type can serve as the marker, so other alignments are available by
+/// declaring a unit struct with `#[repr(align(N))]`. The marker takes no
+/// space, but the size of an `Aligned` is rounded up to a multiple of its
+/// alignment.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Aligned<A, T> {
+    align: [A; 0],
+    value: T,
+}
+
+impl<A, T> Aligned<A, T> {
+    /// Wraps a value.
+    pub const fn new(value: T) -> Self {
+        Aligned { align: [], value }
+    }
+
+    /// Unwraps the value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns a pinned reference to the value.
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        unsafe { self.map_unchecked_mut(|this| &mut this.value) }
+    }
+}
+
+impl<A, T> Deref for Aligned<A, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<A, T> DerefMut for Aligned<A, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<A, T: fmt::Debug> fmt::Debug for Aligned<A, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.value, f)
+    }
+}
+
+/// An initializer for an [`Aligned`] value.
+///
+/// Created by [`aligned`].
+pub struct AlignedInit<A, I> {
+    init: I,
+    _marker: PhantomData<fn() -> A>,
+}
+
+/// Creates an initializer which constructs the value of `init` inside an
+/// [`Aligned`], at the alignment of `A`.
+///
+/// Since the value is at the start of the wrapper, this constructs it in
+/// place, and accepts pinned and fallible initializers as well.
+pub fn aligned<A, I>(init: I) -> AlignedInit<A, I> {
+    AlignedInit {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<A, T, I> PinInit<Aligned<A, T>> for AlignedInit<A, I>
+where
+    I: PinInit<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<Aligned<A, T>>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut Aligned<A, T> {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut Aligned<A, T>) {
+        self.init.pin_initialize(dest.cast())
+    }
+}
+
+unsafe impl<A, T, I> Init<Aligned<A, T>> for AlignedInit<A, I>
+where
+    I: Init<T>,
+{
+    unsafe fn initialize(self, dest: *mut Aligned<A, T>) {
+        self.init.initialize(dest.cast())
+    }
+}
+
+unsafe impl<A, T, E, I> TryInit<Aligned<A, T>, E> for AlignedInit<A, I>
+where
+    I: TryInit<T, E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<Aligned<A, T>>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut Aligned<A, T> {
+        addr.cast()
+    }
+
+    unsafe fn try_initialize(self, dest: *mut Aligned<A, T>) -> Result<(), E> {
+        self.init.try_initialize(dest.cast())
+    }
+}
@@ -0,0 +1,122 @@
+//! Emplacement of type-erased values.
+//!
+//! A `Box<dyn Any>` can be emplaced by constructing the concrete value in
+//! place and unsizing it, and `Box::downcast` recovers the concrete type by
+//! reinterpreting the pointer, so the value never moves in either direction.
+//! This module adds the pieces that the standard library does not cover.
+
+use core::any::Any;
+use core::mem;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+use crate::__private::AbortOnUnwind;
+#[cfg(feature = "alloc")]
+use crate::boxed::BoxExt;
+#[cfg(feature = "alloc")]
+use crate::error::InplaceError;
+use crate::init::Init;
+#[cfg(feature = "alloc")]
+use crate::init::TryInit;
+
+/// Allocates a value of type `T` on the heap, constructs it in place, and
+/// returns it as a `Box<dyn Any>`.
+///
+/// # Example
+///
+/// ```
+/// use inplace::{any, init};
+///
+/// let value = any::emplace::<[u8; 4096], _>(init::with(|| [9; 4096]));
+/// let array: Box<[u8; 4096]> = value.downcast().unwrap();
+/// assert_eq!(array[4095], 9);
+/// ```
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+pub fn emplace<T, I>(init: I) -> Box<dyn Any>
+where
+    T: Any,
+    I: Init<T>,
+{
+    Box::<T>::emplace(init)
+}
+
+/// Allocates a value of type `T` on the heap, constructs it in place, and
+/// returns it as a `Box<dyn Any + Send>`.
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+pub fn emplace_send<T, I>(init: I) -> Box<dyn Any + Send>
+where
+    T: Any + Send,
+    I: Init<T>,
+{
+    Box::<T>::emplace(init)
+}
+
+/// Attempts to allocate a value of type `T` on the heap and construct it in
+/// place, returning it as a `Box<dyn Any>`.
+///
+/// Errors are reported as for [`BoxExt::try_emplace`].
+#[cfg(feature = "alloc")]
+pub fn try_emplace<T, I, E>(init: I) -> Result<Box<dyn Any>, InplaceError<E>>
+where
+    T: Any,
+    I: TryInit<T, E>,
+{
+    Ok(Box::<T>::try_emplace(init)?)
+}
+
+/// Attempts to allocate a value of type `T` on the heap and construct it in
+/// place, returning it as a `Box<dyn Any + Send>`.
+///
+/// Errors are reported as for [`BoxExt::try_emplace`].
+#[cfg(feature = "alloc")]
+pub fn try_emplace_send<T, I, E>(init: I) -> Result<Box<dyn Any + Send>, InplaceError<E>>
+where
+    T: Any + Send,
+    I: TryInit<T, E>,
+{
+    Ok(Box::<T>::try_emplace(init)?)
+}
+
+/// Replaces a type-erased value with a new value of the same concrete type,
+/// constructed in place.
+///
+/// If `value` is a `T`, it is dropped and a new `T` is constructed at the
+/// same address, and a reference to it is returned. Otherwise `value` is
+/// untouched and the initializer is returned.
+///
+/// There is no value at the address while the old value is dropped and the
+/// initializer runs, so if either panics the process is aborted.
+///
+/// # Example
+///
+/// ```
+/// use std::any::Any;
+/// use inplace::{any, init};
+///
+/// let mut value: Box<dyn Any> = Box::new([0u32; 256]);
+/// let array: &mut [u32; 256] = any::downcast_emplace(&mut *value, init::with(|| [1; 256]))
+///     .ok()
+///     .unwrap();
+/// assert_eq!(array[255], 1);
+/// assert!(any::downcast_emplace::<u8, _>(&mut *value, 0).is_err());
+/// ```
+pub fn downcast_emplace<T, I>(value: &mut dyn Any, init: I) -> Result<&mut T, I>
+where
+    T: Any,
+    I: Init<T>,
+{
+    let Some(value) = value.downcast_mut::<T>() else {
+        return Err(init);
+    };
+    let dest: *mut T = value;
+    unsafe {
+        // The old value is gone as soon as its destructor starts, so a panic
+        // from it must not unwind past the reference either.
+        let guard = AbortOnUnwind;
+        dest.drop_in_place();
+        init.initialize(dest);
+        mem::forget(guard);
+        Ok(&mut *dest)
+    }
+}
@@ -0,0 +1,23 @@
+//! Emplacement into arenas.
+//!
+//! Arenas suit values which are created in bulk and freed together, such as
+//! the nodes built while handling one request or compiling one function.
+//! Values are constructed directly in the arena's chunks, and can be unsized.
+//!
+//! [`Arena`] is the crate's own arena, which drops its values when it is
+//! reset. The `bumpalo` and `typed-arena` features add the same emplacement
+//! to the arenas of those crates.
+
+#[cfg(feature = "bumpalo")]
+mod bump;
+#[cfg(feature = "alloc")]
+mod chunks;
+#[cfg(all(feature = "typed-arena", not(inplace_no_global_oom_handling)))]
+mod typed;
+
+#[cfg(feature = "bumpalo")]
+pub use bump::BumpExt;
+#[cfg(feature = "alloc")]
+pub use chunks::Arena;
+#[cfg(all(feature = "typed-arena", not(inplace_no_global_oom_handling)))]
+pub use typed::TypedArenaExt;
@@ -0,0 +1,107 @@
+#[cfg(not(inplace_no_global_oom_handling))]
+use core::mem::MaybeUninit;
+
+use bumpalo::Bump;
+
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::boxed_slice;
+use crate::error::InplaceError;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::Init;
+use crate::init::TryInit;
+
+/// Extension methods for constructing values in place in a `bumpalo` arena.
+///
+/// As with `Bump::alloc`, values in the arena are never dropped, and their
+/// memory is reclaimed when the arena is reset or dropped. If an initializer
+/// panics or fails, the space allocated for it is not reused until then.
+// Each call allocates new space, so the references given out are unique.
+#[allow(clippy::mut_from_ref)]
+pub trait BumpExt {
+    /// Allocates space in the arena and constructs the value there.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use bumpalo::Bump;
+    /// use inplace::arena::BumpExt;
+    /// use inplace::init;
+    ///
+    /// let frame = Bump::new();
+    /// let verts: &mut [f32; 4096] = frame.emplace(init::with(|| [0.0; 4096]));
+    /// let name: &mut str = frame.emplace("frame");
+    /// verts[0] = 1.0;
+    /// assert_eq!(name, "frame");
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<T: ?Sized, I>(&self, init: I) -> &mut T
+    where
+        I: Init<T>;
+
+    /// Attempts to allocate space in the arena and construct the value there.
+    ///
+    /// If the arena cannot allocate, [`InplaceError::Alloc`] is returned and
+    /// the initializer is not run. If the initializer fails, its error is
+    /// returned as [`InplaceError::Init`].
+    fn try_emplace<T: ?Sized, I, E>(&self, init: I) -> Result<&mut T, InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+
+    /// Allocates a slice of `len` elements in the arena, and fills each
+    /// element in place by calling `f` with its index and its uninitialized
+    /// slot, as [`boxed_slice::emplace`] does.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use bumpalo::Bump;
+    /// use inplace::arena::BumpExt;
+    ///
+    /// let request = Bump::new();
+    /// let rows = request.emplace_slice(8, |i, slot| slot.write([i as u16; 1024]));
+    /// assert_eq!(rows[7][1023], 7);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_slice<T, F>(&self, len: usize, f: F) -> &mut [T]
+    where
+        F: for<'a> FnMut(usize, &'a mut MaybeUninit<T>) -> &'a mut T;
+}
+
+#[allow(clippy::mut_from_ref)]
+impl BumpExt for Bump {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<T: ?Sized, I>(&self, init: I) -> &mut T
+    where
+        I: Init<T>,
+    {
+        let addr = self.alloc_layout(init.layout());
+        unsafe {
+            let dest = init.dest(addr.as_ptr());
+            init.initialize(dest);
+            &mut *dest
+        }
+    }
+
+    fn try_emplace<T: ?Sized, I, E>(&self, init: I) -> Result<&mut T, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let layout = init.layout();
+        let addr = self
+            .try_alloc_layout(layout)
+            .map_err(|_| InplaceError::Alloc(layout))?;
+        unsafe {
+            let dest = init.dest(addr.as_ptr());
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            Ok(&mut *dest)
+        }
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_slice<T, F>(&self, len: usize, f: F) -> &mut [T]
+    where
+        F: for<'a> FnMut(usize, &'a mut MaybeUninit<T>) -> &'a mut T,
+    {
+        self.emplace(boxed_slice::from_fn(len, f))
+    }
+}
@@ -0,0 +1,341 @@
+use core::alloc::Layout;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::marker::PhantomData;
+#[cfg(not(inplace_no_global_oom_handling))]
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+
+#[cfg(not(inplace_no_global_oom_handling))]
+use alloc::alloc::handle_alloc_error;
+use alloc::vec::Vec;
+
+use crate::error::InplaceError;
+use crate::init::TryInit;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::{Init, PinInit};
+use crate::raw;
+
+/// The size of the first chunk. Each later chunk is twice the size of the
+/// one before, or larger if needed for the value being allocated.
+const FIRST_CHUNK: usize = 4096;
+
+/// The minimum alignment of a chunk.
+const CHUNK_ALIGN: usize = 16;
+
+/// An arena which values of any type, sized or not, can be constructed in.
+///
+/// Values are constructed directly in chunks of memory owned by the arena,
+/// and are all dropped together, in the reverse of the order they were
+/// completed in, when the arena is [`reset`](Arena::reset) or dropped. To do
+/// that, the arena records a pointer to each value along with a function to
+/// drop it, which for unsized values carries the length or vtable.
+///
+/// An initializer may itself allocate from the arena, for example to build
+/// the children of a node. If it panics or fails, its space is not reused
+/// until the arena is reset, but nothing is recorded for it to be dropped.
+///
+/// Since the values are dropped by the arena, they must outlive it: every
+/// value must live for `'a`, which the arena itself must not outlive. A value
+/// may borrow from data declared before the arena, but not from the arena.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::fmt::Debug;
+/// use inplace::arena::Arena;
+/// use inplace::{init, unsize};
+///
+/// let arena = Arena::new();
+/// let table = arena.emplace::<[u32; 4096], _>(init::with(|| [7; 4096]));
+/// let name = arena.emplace::<str, _>("request");
+/// let item = arena.emplace::<dyn Debug, _>(unsize!(
+///     init::with(|| vec![1, 2]) => dyn Debug, from Vec<i32>
+/// ));
+/// table[0] = 0;
+/// assert_eq!(name, "request");
+/// assert_eq!(format!("{item:?}"), "[1, 2]");
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub struct Arena<'a> {
+    chunks: RefCell<Vec<Chunk>>,
+    next: Cell<*mut u8>,
+    end: Cell<*mut u8>,
+    drops: RefCell<Vec<DropEntry>>,
+    /// Makes the arena invariant in `'a`, so that it cannot be shortened to
+    /// admit values which do not outlive the arena.
+    values: PhantomData<Cell<&'a ()>>,
+}
+
+/// A chunk of memory owned by the arena.
+struct Chunk {
+    addr: NonNull<u8>,
+    layout: Layout,
+}
+
+/// A value to be dropped.
+struct DropEntry {
+    /// The address of a pointer to the value, itself stored in the arena, so
+    /// that its metadata is kept without knowing its type.
+    value: *mut u8,
+    drop: unsafe fn(*mut u8),
+}
+
+/// Drops the value pointed to by the `*mut T` at `value`.
+unsafe fn drop_value<T: ?Sized>(value: *mut u8) {
+    ptr::drop_in_place(value.cast::<*mut T>().read());
+}
+
+// Each value is given new space, so the references given out are unique.
+#[allow(clippy::mut_from_ref)]
+impl<'a> Arena<'a> {
+    /// Creates an empty arena, without allocating.
+    pub const fn new() -> Self {
+        Arena {
+            chunks: RefCell::new(Vec::new()),
+            next: Cell::new(ptr::null_mut()),
+            end: Cell::new(ptr::null_mut()),
+            drops: RefCell::new(Vec::new()),
+            values: PhantomData,
+        }
+    }
+
+    /// Returns the number of values in the arena.
+    pub fn len(&self) -> usize {
+        self.drops.borrow().len()
+    }
+
+    /// Returns true if the arena holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.drops.borrow().is_empty()
+    }
+
+    /// Returns the total size of the chunks the arena has allocated.
+    pub fn allocated_bytes(&self) -> usize {
+        self.chunks
+            .borrow()
+            .iter()
+            .map(|chunk| chunk.layout.size())
+            .sum()
+    }
+
+    /// Allocates space in the current chunk, or in a new one, returning
+    /// `None` if a new chunk cannot be allocated.
+    fn try_alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        if layout.size() == 0 {
+            return NonNull::new(ptr::without_provenance_mut(layout.align()));
+        }
+        let next = self.next.get();
+        let offset = next.align_offset(layout.align());
+        let available = self.end.get() as usize - next as usize;
+        if offset <= available && layout.size() <= available - offset {
+            let addr = unsafe { next.add(offset) };
+            self.next.set(unsafe { addr.add(layout.size()) });
+            return NonNull::new(addr);
+        }
+        let addr = self.grow(layout)?;
+        self.next.set(unsafe { addr.as_ptr().add(layout.size()) });
+        Some(addr)
+    }
+
+    /// Allocates a new chunk large enough for `layout`, and makes it the
+    /// current chunk.
+    fn grow(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let mut chunks = self.chunks.borrow_mut();
+        let size = chunks
+            .last()
+            .map_or(FIRST_CHUNK, |chunk| chunk.layout.size().saturating_mul(2))
+            .max(layout.size());
+        let chunk_layout = Layout::from_size_align(size, layout.align().max(CHUNK_ALIGN)).ok()?;
+        chunks.try_reserve(1).ok()?;
+        let addr = raw::try_allocate(chunk_layout)?;
+        raw::push_reserved(
+            &mut chunks,
+            Chunk {
+                addr,
+                layout: chunk_layout,
+            },
+        );
+        self.end.set(unsafe { addr.as_ptr().add(size) });
+        Some(addr)
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn alloc(&self, layout: Layout) -> NonNull<u8> {
+        self.try_alloc(layout)
+            .unwrap_or_else(|| handle_alloc_error(layout))
+    }
+
+    /// Records a value to be dropped with the arena, returning the layout
+    /// which could not be allocated on failure.
+    fn try_record<T: ?Sized + 'a>(&self, value: *mut T) -> Result<(), Layout> {
+        let slot_layout = Layout::new::<*mut T>();
+        let slot = self
+            .try_alloc(slot_layout)
+            .ok_or(slot_layout)?
+            .cast::<*mut T>();
+        let mut drops = self.drops.borrow_mut();
+        drops
+            .try_reserve(1)
+            .map_err(|_| Layout::new::<DropEntry>())?;
+        unsafe { slot.as_ptr().write(value) };
+        raw::push_reserved(
+            &mut drops,
+            DropEntry {
+                value: slot.as_ptr().cast(),
+                drop: drop_value::<T>,
+            },
+        );
+        Ok(())
+    }
+
+    /// Records a value to be dropped with the arena.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn record<T: ?Sized + 'a>(&self, value: *mut T) {
+        if let Err(layout) = self.try_record(value) {
+            handle_alloc_error(layout)
+        }
+    }
+
+    /// Constructs a value in the arena.
+    ///
+    /// If the initializer panics, the arena holds the same values as before.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn emplace<T: ?Sized + 'a, I>(&self, init: I) -> &mut T
+    where
+        I: Init<T>,
+    {
+        let addr = self.alloc(init.layout());
+        unsafe {
+            let dest = init.dest(addr.as_ptr());
+            init.initialize(dest);
+            self.record(dest);
+            &mut *dest
+        }
+    }
+
+    /// Attempts to construct a value in the arena.
+    ///
+    /// If a new chunk cannot be allocated, [`InplaceError::Alloc`] is
+    /// returned and the initializer is not run. If the initializer fails, its
+    /// error is returned as [`InplaceError::Init`]. In either case the arena
+    /// holds the same values as before. If the value is constructed but
+    /// there is no room to record it for dropping, it is dropped again and
+    /// [`InplaceError::Alloc`] is returned.
+    pub fn try_emplace<T: ?Sized + 'a, I, E>(&self, init: I) -> Result<&mut T, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let layout = init.layout();
+        let addr = self.try_alloc(layout).ok_or(InplaceError::Alloc(layout))?;
+        unsafe {
+            let dest = init.dest(addr.as_ptr());
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            if let Err(layout) = self.try_record(dest) {
+                ptr::drop_in_place(dest);
+                return Err(InplaceError::Alloc(layout));
+            }
+            Ok(&mut *dest)
+        }
+    }
+
+    /// Constructs a pinned value in the arena.
+    ///
+    /// The value is dropped before its memory is reused, when the arena is
+    /// reset or dropped, so it can be pinned.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn pin_emplace<T: ?Sized + 'a, I>(&self, init: I) -> Pin<&mut T>
+    where
+        I: PinInit<T>,
+    {
+        let addr = self.alloc(init.layout());
+        unsafe {
+            let dest = init.dest(addr.as_ptr());
+            init.pin_initialize(dest);
+            self.record(dest);
+            Pin::new_unchecked(&mut *dest)
+        }
+    }
+
+    /// Drops every value in the arena, in the reverse of the order they were
+    /// completed in.
+    fn drop_values(&mut self) {
+        // A value's drop may panic. The remaining values are then leaked,
+        // which is safe, rather than dropped twice.
+        let drops = core::mem::take(self.drops.get_mut());
+        for entry in drops.into_iter().rev() {
+            unsafe { (entry.drop)(entry.value) };
+        }
+    }
+
+    /// Drops every value in the arena, and frees all but its largest chunk
+    /// for reuse.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(not(inplace_no_global_oom_handling))]
+    /// # fn main() {
+    /// use inplace::arena::Arena;
+    /// use inplace::init;
+    ///
+    /// let mut arena = Arena::new();
+    /// for frame in 0..3 {
+    ///     let buf = arena.emplace::<[u8; 8192], _>(init::with(|| [frame as u8; 8192]));
+    ///     assert_eq!(buf[0], frame as u8);
+    ///     arena.reset();
+    /// }
+    /// assert!(arena.is_empty());
+    /// # }
+    /// # #[cfg(inplace_no_global_oom_handling)]
+    /// # fn main() {}
+    /// ```
+    pub fn reset(&mut self) {
+        self.drop_values();
+        let chunks = self.chunks.get_mut();
+        let last = chunks.pop();
+        for chunk in chunks.drain(..) {
+            unsafe { raw::deallocate(chunk.addr, chunk.layout) };
+        }
+        match last {
+            Some(chunk) => {
+                self.next.set(chunk.addr.as_ptr());
+                self.end
+                    .set(unsafe { chunk.addr.as_ptr().add(chunk.layout.size()) });
+                raw::push_reserved(chunks, chunk);
+            }
+            None => {
+                self.next.set(ptr::null_mut());
+                self.end.set(ptr::null_mut());
+            }
+        }
+    }
+}
+
+impl Default for Arena<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Arena<'_> {
+    fn drop(&mut self) {
+        self.drop_values();
+        for chunk in self.chunks.get_mut().drain(..) {
+            unsafe { raw::deallocate(chunk.addr, chunk.layout) };
+        }
+    }
+}
+
+impl fmt::Debug for Arena<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Arena")
+            .field("len", &self.len())
+            .field("allocated_bytes", &self.allocated_bytes())
+            .finish()
+    }
+}
@@ -0,0 +1,139 @@
+use core::marker::PhantomData;
+
+use typed_arena::Arena;
+
+use crate::init::{Init, TryInit};
+
+/// Extension methods for constructing values in place in a `typed-arena`
+/// arena.
+///
+/// The value is constructed in the arena's spare capacity, and only then
+/// added to the arena, so if the initializer panics or fails, the arena is
+/// unchanged. Values are dropped with the arena, as for `Arena::alloc`.
+///
+/// The initializer runs while the arena is borrowed, so if it allocates from
+/// the same arena, which would be given the space the value is being
+/// constructed in, that allocation panics instead. An initializer which only
+/// builds its own value, and refers to earlier values in the arena, is fine.
+// Each value is added to the arena once, so the references given out are
+// unique.
+#[allow(clippy::mut_from_ref)]
+pub trait TypedArenaExt<T> {
+    /// Constructs a value in place in the arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the initializer allocates from this arena.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use typed_arena::Arena;
+    /// use inplace::arena::TypedArenaExt;
+    /// use inplace::init;
+    ///
+    /// struct Node<'a> {
+    ///     parent: Option<&'a Node<'a>>,
+    ///     scratch: [u64; 512],
+    /// }
+    ///
+    /// let nodes = Arena::new();
+    /// let root: &Node = nodes.emplace(init::with(|| Node { parent: None, scratch: [0; 512] }));
+    /// let child = nodes.emplace(init::with(|| Node { parent: Some(root), scratch: [1; 512] }));
+    /// assert!(child.parent.is_some());
+    /// ```
+    ///
+    /// Allocating from the arena while a value is being constructed in it
+    /// panics:
+    ///
+    /// ```should_panic
+    /// use typed_arena::Arena;
+    /// use inplace::arena::TypedArenaExt;
+    /// use inplace::init;
+    ///
+    /// let arena: Arena<[u64; 8]> = Arena::new();
+    /// arena.emplace(init::with(|| *arena.alloc([1; 8])));
+    /// ```
+    fn emplace<I>(&self, init: I) -> &mut T
+    where
+        I: Init<T>;
+
+    /// Attempts to construct a value in place in the arena.
+    ///
+    /// If the initializer fails, its error is returned and the arena is
+    /// unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the initializer allocates from this arena.
+    fn try_emplace<I, E>(&self, init: I) -> Result<&mut T, E>
+    where
+        I: TryInit<T, E>;
+}
+
+#[allow(clippy::mut_from_ref)]
+impl<T> TypedArenaExt<T> for Arena<T> {
+    fn emplace<I>(&self, init: I) -> &mut T
+    where
+        I: Init<T>,
+    {
+        self.reserve_extend(1);
+        let slot = self.uninitialized_array().cast::<T>();
+        borrowed_while(self, || unsafe { init.initialize(slot) });
+        unsafe { confirm(self, slot) }
+    }
+
+    fn try_emplace<I, E>(&self, init: I) -> Result<&mut T, E>
+    where
+        I: TryInit<T, E>,
+    {
+        self.reserve_extend(1);
+        let slot = self.uninitialized_array().cast::<T>();
+        let mut result = Ok(());
+        borrowed_while(self, || result = unsafe { init.try_initialize(slot) });
+        result?;
+        Ok(unsafe { confirm(self, slot) })
+    }
+}
+
+/// Runs `f` while the arena's chunks are borrowed, so that any allocation
+/// from the arena made by `f` panics.
+///
+/// `alloc_extend` holds the borrow while it pulls items from its iterator,
+/// so `f` is run by an iterator which then yields nothing, leaving the arena
+/// unchanged.
+fn borrowed_while<T, F: FnOnce()>(arena: &Arena<T>, f: F) {
+    struct RunOnce<T, F>(Option<F>, PhantomData<fn() -> T>);
+
+    impl<T, F: FnOnce()> Iterator for RunOnce<T, F> {
+        type Item = T;
+
+        fn next(&mut self) -> Option<T> {
+            if let Some(f) = self.0.take() {
+                f();
+            }
+            None
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, Some(0))
+        }
+    }
+
+    let added = arena
+        .alloc_extend(RunOnce::<T, F>(Some(f), PhantomData))
+        .len();
+    debug_assert_eq!(added, 0);
+}
+
+/// Adds the value constructed at the start of the arena's spare capacity to
+/// the arena.
+#[allow(clippy::mut_from_ref)]
+unsafe fn confirm<T>(arena: &Arena<T>, slot: *mut T) -> &mut T {
+    let value = arena.alloc_uninitialized(1).as_mut_ptr().cast::<T>();
+    debug_assert_eq!(
+        value, slot,
+        "the arena changed while a value was constructed"
+    );
+    &mut *value
+}
@@ -0,0 +1,279 @@
+//! Emplacement into `Box`.
+
+#[cfg(feature = "nightly")]
+use core::alloc::Allocator;
+use core::mem;
+#[cfg(not(inplace_no_global_oom_handling))]
+use core::pin::Pin;
+
+#[cfg(all(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use alloc::alloc::handle_alloc_error;
+use alloc::boxed::Box;
+
+use crate::error::InplaceError;
+use crate::init::TryInit;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::{Init, PinInit};
+#[cfg(feature = "nightly")]
+use crate::raw::DeallocInGuard;
+use crate::raw::{self, DeallocGuard};
+
+mod thin;
+
+pub use thin::ThinBox;
+
+/// Extension methods for constructing a `Box` in place.
+pub trait BoxExt<T: ?Sized>: Sized {
+    /// Allocates space on the heap and constructs the value there.
+    ///
+    /// This is the most basic use of an initializer: the allocation is made
+    /// using the initializer's layout before the value exists, and the value
+    /// is then constructed directly in it, without ever being materialized on
+    /// the stack. If the initializer panics, the allocation is freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::{init, BoxExt};
+    ///
+    /// struct Frame {
+    ///     id: u32,
+    ///     pixels: [u32; 1 << 18],
+    /// }
+    ///
+    /// let frame: Box<Frame> = Box::emplace(init!(Frame { id: 7, pixels <- init::zeroed() }));
+    /// assert_eq!(frame.id, 7);
+    /// assert!(frame.pixels.iter().all(|&p| p == 0));
+    ///
+    /// let slice: Box<[u8]> = Box::emplace(&b"unsized"[..]);
+    /// assert_eq!(&*slice, b"unsized");
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<T>;
+
+    /// Attempts to allocate space on the heap and construct the value there.
+    ///
+    /// Unlike [`emplace`](BoxExt::emplace), failure to allocate is reported
+    /// as [`InplaceError::Alloc`] rather than aborting, in which case the
+    /// initializer is dropped without being run. If the initializer itself
+    /// fails, the allocation is freed and its error returned as
+    /// [`InplaceError::Init`]. An infallible initializer can be passed by
+    /// wrapping it with [`init::fallible`](crate::init::fallible).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::convert::Infallible;
+    /// use inplace::{init, BoxExt, InplaceError};
+    ///
+    /// let buf: Result<Box<[u64; 4096]>, InplaceError<Infallible>> =
+    ///     Box::try_emplace(init::fallible(init::with(|| [0; 4096])));
+    /// assert!(buf.is_ok());
+    /// ```
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+
+    /// Allocates space on the heap and constructs a pinned value there.
+    ///
+    /// The value is constructed at its final address and is never moved, so
+    /// this accepts pinned initializers, and can be used to build `!Unpin`
+    /// values such as self-referential structures.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::marker::PhantomPinned;
+    /// use core::pin::Pin;
+    /// use inplace::{pin_init, BoxExt};
+    ///
+    /// struct Waiter {
+    ///     id: u32,
+    ///     _pin: PhantomPinned,
+    /// }
+    ///
+    /// let waiter: Pin<Box<Waiter>> = Box::pin_emplace(pin_init!(Waiter {
+    ///     id: 3,
+    ///     _pin: PhantomPinned,
+    /// }));
+    /// assert_eq!(waiter.id, 3);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn pin_emplace<I>(init: I) -> Pin<Self>
+    where
+        I: PinInit<T>;
+}
+
+impl<T: ?Sized> BoxExt<T> for Box<T> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<T>,
+    {
+        let layout = init.layout();
+        let addr = raw::allocate(layout);
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            init.initialize(dest);
+            mem::forget(guard);
+            Box::from_raw(dest)
+        }
+    }
+
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let layout = init.layout();
+        let addr = raw::try_allocate(layout).ok_or(InplaceError::Alloc(layout))?;
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            mem::forget(guard);
+            Ok(Box::from_raw(dest))
+        }
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn pin_emplace<I>(init: I) -> Pin<Self>
+    where
+        I: PinInit<T>,
+    {
+        let layout = init.layout();
+        let addr = raw::allocate(layout);
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            init.pin_initialize(dest);
+            mem::forget(guard);
+            Pin::new_unchecked(Box::from_raw(dest))
+        }
+    }
+}
+
+/// Extension methods for constructing a `Box` in place with a custom
+/// allocator.
+///
+/// These are the [`BoxExt`] methods for boxes which use an [`Allocator`]
+/// other than the global one, such as an arena or a bump allocator, and
+/// require the unstable `allocator_api`. `Rc` and `Arc` have the same
+/// methods in [`RcInExt`](crate::RcInExt) and [`ArcInExt`](crate::ArcInExt).
+/// The other containers, including [`VecExt`](crate::VecExt) vectors,
+/// [`DstVec`](crate::containers::dst_vec::DstVec) and the arenas, always allocate
+/// from the global allocator.
+///
+/// # Example
+///
+/// ```
+/// #![feature(allocator_api)]
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::alloc::System;
+/// use inplace::{init, BoxInExt};
+///
+/// let buf: Box<[u8; 65536], System> = Box::emplace_in(init::with(|| [0; 65536]), System);
+/// assert_eq!(buf.len(), 65536);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "nightly")]
+pub trait BoxInExt<T: ?Sized, A: Allocator>: Sized {
+    /// Allocates space with `alloc` and constructs the value there.
+    ///
+    /// If the initializer panics, the allocation is freed.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_in<I>(init: I, alloc: A) -> Self
+    where
+        I: Init<T>;
+
+    /// Attempts to allocate space with `alloc` and construct the value there.
+    ///
+    /// Errors are reported as for [`BoxExt::try_emplace`].
+    fn try_emplace_in<I, E>(init: I, alloc: A) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+
+    /// Allocates space with `alloc` and constructs a pinned value there.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn pin_emplace_in<I>(init: I, alloc: A) -> Pin<Self>
+    where
+        I: PinInit<T>,
+        A: 'static;
+}
+
+#[cfg(feature = "nightly")]
+impl<T: ?Sized, A: Allocator> BoxInExt<T, A> for Box<T, A> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_in<I>(init: I, alloc: A) -> Self
+    where
+        I: Init<T>,
+    {
+        let layout = init.layout();
+        let addr = alloc
+            .allocate(layout)
+            .unwrap_or_else(|_| handle_alloc_error(layout))
+            .cast::<u8>();
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocInGuard {
+            addr,
+            layout,
+            alloc: &alloc,
+        };
+        unsafe {
+            init.initialize(dest);
+            mem::forget(guard);
+            Box::from_raw_in(dest, alloc)
+        }
+    }
+
+    fn try_emplace_in<I, E>(init: I, alloc: A) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let layout = init.layout();
+        let addr = alloc
+            .allocate(layout)
+            .map_err(|_| InplaceError::Alloc(layout))?
+            .cast::<u8>();
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocInGuard {
+            addr,
+            layout,
+            alloc: &alloc,
+        };
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            mem::forget(guard);
+            Ok(Box::from_raw_in(dest, alloc))
+        }
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn pin_emplace_in<I>(init: I, alloc: A) -> Pin<Self>
+    where
+        I: PinInit<T>,
+        A: 'static,
+    {
+        let layout = init.layout();
+        let addr = alloc
+            .allocate(layout)
+            .unwrap_or_else(|_| handle_alloc_error(layout))
+            .cast::<u8>();
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocInGuard {
+            addr,
+            layout,
+            alloc: &alloc,
+        };
+        unsafe {
+            init.pin_initialize(dest);
+            mem::forget(guard);
+            Pin::new_unchecked(Box::from_raw_in(dest, alloc))
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! A box which is a single pointer wide even for unsized values.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+#[cfg(not(inplace_no_global_oom_handling))]
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+use crate::error::InplaceError;
+use crate::init::TryInit;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::{Init, PinInit};
+use crate::raw::{self, DeallocGuard};
+
+/// A box which is a single pointer wide even for unsized values.
+///
+/// The pointer metadata of an unsized value, such as a slice length or a
+/// vtable, is stored at the start of the allocation rather than alongside
+/// the pointer. This makes `ThinBox<dyn Trait>` the same size as `usize`, so
+/// containers of unsized values can hold thin pointers to them.
+///
+/// Stable Rust cannot take a pointer apart into its address and metadata, so
+/// the whole wide pointer to the value is stored in the header. With the
+/// `nightly` feature, only the metadata is stored, and the pointer is rebuilt
+/// from it.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::fmt::Display;
+/// use std::mem::size_of;
+/// use inplace::boxed::ThinBox;
+/// use inplace::unsize;
+///
+/// let item: ThinBox<dyn Display> = ThinBox::emplace(unsize!(7u8 => dyn Display));
+/// assert_eq!(item.to_string(), "7");
+/// assert_eq!(size_of::<ThinBox<dyn Display>>(), size_of::<usize>());
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub struct ThinBox<T: ?Sized> {
+    header: NonNull<Header<T>>,
+    _marker: PhantomData<T>,
+}
+
+/// What is stored at the start of the allocation to recover the value.
+#[cfg(not(feature = "nightly"))]
+type Header<T> = *mut T;
+#[cfg(feature = "nightly")]
+type Header<T> = <T as core::ptr::Pointee>::Metadata;
+
+#[cfg(not(feature = "nightly"))]
+fn header_for<T: ?Sized>(value: *mut T) -> Header<T> {
+    value
+}
+
+#[cfg(feature = "nightly")]
+fn header_for<T: ?Sized>(value: *mut T) -> Header<T> {
+    core::ptr::metadata(value)
+}
+
+/// # Safety
+///
+/// `header` must be the header of a live `ThinBox<T>`.
+#[cfg(not(feature = "nightly"))]
+unsafe fn value_for<T: ?Sized>(header: NonNull<Header<T>>) -> *mut T {
+    header.as_ptr().read()
+}
+
+/// # Safety
+///
+/// `header` must be the header of a live `ThinBox<T>`.
+#[cfg(feature = "nightly")]
+unsafe fn value_for<T: ?Sized>(header: NonNull<Header<T>>) -> *mut T {
+    let metadata = header.as_ptr().read();
+    let dangling = core::ptr::from_raw_parts_mut::<T>(core::ptr::null_mut::<u8>(), metadata);
+    let (_, offset) = layout_with_header::<T>(Layout::for_value_raw(dangling));
+    core::ptr::from_raw_parts_mut(header.as_ptr().cast::<u8>().add(offset), metadata)
+}
+
+unsafe impl<T: ?Sized + Send> Send for ThinBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for ThinBox<T> {}
+
+/// Returns the layout of an allocation holding the header and a value with
+/// `value` layout, and the offset of the value in it.
+fn layout_with_header<T: ?Sized>(value: Layout) -> (Layout, usize) {
+    let (layout, offset) = Layout::new::<Header<T>>()
+        .extend(value)
+        .expect("value layout too large for a ThinBox");
+    (layout.pad_to_align(), offset)
+}
+
+impl<T: ?Sized> ThinBox<T> {
+    /// Allocates space on the heap and constructs the value there.
+    ///
+    /// If the initializer panics, the allocation is freed.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn emplace<I>(init: I) -> Self
+    where
+        I: Init<T>,
+    {
+        let (layout, offset) = layout_with_header::<T>(init.layout());
+        let addr = raw::allocate(layout);
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            let dest = init.dest(addr.as_ptr().add(offset));
+            init.initialize(dest);
+            mem::forget(guard);
+            Self::from_parts(addr, dest)
+        }
+    }
+
+    /// Attempts to allocate space on the heap and construct the value there.
+    ///
+    /// Errors are reported as by [`BoxExt::try_emplace`].
+    ///
+    /// [`BoxExt::try_emplace`]: crate::BoxExt::try_emplace
+    pub fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let (layout, offset) = layout_with_header::<T>(init.layout());
+        let addr = raw::try_allocate(layout).ok_or(InplaceError::Alloc(layout))?;
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            let dest = init.dest(addr.as_ptr().add(offset));
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            mem::forget(guard);
+            Ok(Self::from_parts(addr, dest))
+        }
+    }
+
+    /// Allocates space on the heap and constructs a value there which will
+    /// never be moved.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn pin_emplace<I>(init: I) -> Pin<Self>
+    where
+        I: PinInit<T>,
+    {
+        let (layout, offset) = layout_with_header::<T>(init.layout());
+        let addr = raw::allocate(layout);
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            let dest = init.dest(addr.as_ptr().add(offset));
+            init.pin_initialize(dest);
+            mem::forget(guard);
+            Pin::new_unchecked(Self::from_parts(addr, dest))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `addr` must be an allocation with the layout given by
+    /// [`layout_with_header`] for the value at `value`, which must be
+    /// initialized.
+    unsafe fn from_parts(addr: NonNull<u8>, value: *mut T) -> Self {
+        let header = addr.cast::<Header<T>>();
+        header.as_ptr().write(header_for(value));
+        ThinBox {
+            header,
+            _marker: PhantomData,
+        }
+    }
+
+    fn value(&self) -> *mut T {
+        unsafe { value_for(self.header) }
+    }
+}
+
+impl<T: ?Sized> Drop for ThinBox<T> {
+    fn drop(&mut self) {
+        let value = self.value();
+        let (layout, _) = layout_with_header::<T>(Layout::for_value(unsafe { &*value }));
+        let _guard = DeallocGuard {
+            addr: self.header.cast(),
+            layout,
+        };
+        unsafe { value.drop_in_place() }
+    }
+}
+
+impl<T: ?Sized> Deref for ThinBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.value() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ThinBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ThinBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for ThinBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
@@ -0,0 +1,114 @@
+//! Construction of `Box<[T]>` one element at a time.
+
+use core::alloc::Layout;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use alloc::boxed::Box;
+
+use crate::boxed::BoxExt;
+use crate::error::InplaceError;
+use crate::init::{self, Init, PinInit, Prefix};
+
+/// An initializer for a slice which fills each element with a closure.
+///
+/// Created by [`from_fn`].
+pub struct FromFn<T, F> {
+    len: usize,
+    f: F,
+    _marker: PhantomData<fn(&mut MaybeUninit<T>)>,
+}
+
+/// Creates an initializer for a slice of `len` elements, where each element
+/// is filled by calling `f` with its index and its uninitialized slot.
+///
+/// As with [`init::from_fn`], the closure proves that it has initialized the
+/// slot by returning the `&mut T` obtained from it. If the closure panics,
+/// the elements already initialized are dropped.
+///
+/// [`init::from_fn`]: crate::init::from_fn
+pub fn from_fn<T, F>(len: usize, f: F) -> FromFn<T, F>
+where
+    F: for<'a> FnMut(usize, &'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    FromFn {
+        len,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, F> PinInit<[T]> for FromFn<T, F>
+where
+    F: for<'a> FnMut(usize, &'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut [T]) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, F> Init<[T]> for FromFn<T, F>
+where
+    F: for<'a> FnMut(usize, &'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    unsafe fn initialize(mut self, dest: *mut [T]) {
+        let start = dest.cast::<T>();
+        let mut guard = Prefix { start, len: 0 };
+        for index in 0..self.len {
+            let slot = start.add(index);
+            let value: *mut T = (self.f)(index, &mut *slot.cast::<MaybeUninit<T>>());
+            assert!(
+                ptr::eq(value, slot),
+                "from_fn closure returned a reference to something other than its slot"
+            );
+            guard.len += 1;
+        }
+        mem::forget(guard);
+    }
+}
+
+/// Allocates a slice of `len` elements and fills each element in place by
+/// calling `f` with its index and its uninitialized slot.
+///
+/// The slice is allocated once, up front. If `f` panics, the elements
+/// already initialized are dropped and the allocation is freed.
+///
+/// # Example
+///
+/// ```
+/// use inplace::boxed_slice;
+///
+/// let rows: Box<[[u32; 256]]> = boxed_slice::emplace(4, |i, slot| {
+///     slot.write([i as u32; 256])
+/// });
+/// assert_eq!(rows[3][255], 3);
+/// ```
+#[cfg(not(inplace_no_global_oom_handling))]
+pub fn emplace<T, F>(len: usize, f: F) -> Box<[T]>
+where
+    F: for<'a> FnMut(usize, &'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    Box::emplace(from_fn(len, f))
+}
+
+/// Attempts to allocate a slice of `len` elements and fill each element in
+/// place by calling `f` with its index and its uninitialized slot.
+///
+/// If the slice cannot be allocated, [`InplaceError::Alloc`] is returned and
+/// `f` is not called. Otherwise this behaves as [`emplace`].
+pub fn try_emplace<T, F>(len: usize, f: F) -> Result<Box<[T]>, InplaceError<Infallible>>
+where
+    F: for<'a> FnMut(usize, &'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    Box::try_emplace(init::fallible(from_fn(len, f)))
+}
@@ -0,0 +1,99 @@
+//! Construction of bytes in place in `bytes` buffers.
+//!
+//! Protocol encoders built on [`BufMut`] usually assemble a large frame in a
+//! buffer of its own and then copy it in with `put_slice`. The extension
+//! methods of [`BufMutExt`] instead construct the frame directly in the
+//! buffer's uninitialized chunk, and advance the buffer past it.
+
+use bytes::BufMut;
+
+use crate::init::{Init, TryInit};
+
+/// Extension methods for constructing bytes in place in a [`BufMut`].
+///
+/// The bytes are constructed in the current [`chunk_mut`](BufMut::chunk_mut)
+/// of the buffer, which must be large enough to hold all of them, so space
+/// should be reserved first in buffers which grow, such as `BytesMut` and
+/// `Vec<u8>`.
+pub trait BufMutExt: BufMut {
+    /// Constructs bytes in place in the buffer, and advances past them.
+    ///
+    /// If the initializer panics, the buffer is not advanced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes don't fit in the current chunk of the buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use bytes::{BufMut, BytesMut};
+    /// use inplace::{bytes::BufMutExt, init};
+    ///
+    /// let mut frame = BytesMut::with_capacity(64 * 1024);
+    /// frame.put_u32(60 * 1024);
+    /// frame.put_init(init::repeat(0xa5_u8, 60 * 1024));
+    /// assert_eq!(frame.len(), 4 + 60 * 1024);
+    /// assert_eq!(frame[4 + 60 * 1024 - 1], 0xa5);
+    /// ```
+    fn put_init<I>(&mut self, init: I)
+    where
+        I: Init<[u8]>,
+    {
+        let size = init.layout().size();
+        let chunk = self.chunk_mut();
+        assert!(
+            size <= chunk.len(),
+            "bytes do not fit in the buffer's chunk"
+        );
+        let dest = init.dest(chunk.as_mut_ptr());
+        unsafe {
+            init.initialize(dest);
+            self.advance_mut(size);
+        }
+    }
+
+    /// Constructs bytes in place in the buffer with a fallible initializer,
+    /// and advances past them.
+    ///
+    /// If the initializer fails, the buffer is not advanced and the error is
+    /// returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes don't fit in the current chunk of the buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use bytes::BytesMut;
+    /// use inplace::{bytes::BufMutExt, init};
+    ///
+    /// let mut frame = BytesMut::with_capacity(1024);
+    /// let record = b"name=inplace".iter().map(|&byte| byte.to_ascii_uppercase());
+    /// frame.try_put_init(init::from_iter(12, record)).unwrap();
+    /// assert_eq!(&frame[..], b"NAME=INPLACE");
+    ///
+    /// assert!(frame.try_put_init(init::from_iter(4, [0_u8; 3])).is_err());
+    /// assert_eq!(frame.len(), 12);
+    /// ```
+    fn try_put_init<I, E>(&mut self, init: I) -> Result<(), E>
+    where
+        I: TryInit<[u8], E>,
+    {
+        let size = init.layout().size();
+        let chunk = self.chunk_mut();
+        assert!(
+            size <= chunk.len(),
+            "bytes do not fit in the buffer's chunk"
+        );
+        let dest = init.dest(chunk.as_mut_ptr());
+        unsafe {
+            init.try_initialize(dest)?;
+            self.advance_mut(size);
+        }
+        Ok(())
+    }
+}
+
+impl<B: BufMut + ?Sized> BufMutExt for B {}
@@ -0,0 +1,21 @@
+//! In-place initialization of cells and static storage.
+
+#[cfg(target_has_atomic = "8")]
+mod lazy;
+mod once;
+#[cfg(target_has_atomic = "8")]
+mod once_emplace;
+#[cfg(target_has_atomic = "8")]
+mod static_in_place;
+#[cfg(feature = "std")]
+mod tls;
+
+#[cfg(target_has_atomic = "8")]
+pub use lazy::LazyEmplace;
+pub use once::OnceExt;
+#[cfg(target_has_atomic = "8")]
+pub use once_emplace::OnceEmplace;
+#[cfg(target_has_atomic = "8")]
+pub use static_in_place::StaticInPlace;
+#[cfg(feature = "std")]
+pub use tls::{TlsKey, TlsSlot};
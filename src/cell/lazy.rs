@@ -0,0 +1,138 @@
+use core::cell::UnsafeCell;
+use core::hint;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::init::{FromFn, Init};
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+const POISONED: u8 = 3;
+
+/// A value which is constructed in place on first access.
+///
+/// This is the in-place form of `LazyLock`. It holds an initializer, rather
+/// than a closure returning the value, and runs it on the storage inside the
+/// `LazyEmplace` the first time the value is dereferenced. In a `static`, this
+/// means that a large table is constructed directly in static memory, and
+/// never passes through the stack.
+///
+/// By default, the initializer is an [`init::from_fn`](crate::init::from_fn)
+/// with a function pointer, which can be named in the type of a `static`.
+///
+/// If several threads access the value while it is being constructed, they
+/// spin until it is complete. If the initializer panics, the `LazyEmplace`
+/// is poisoned, and later accesses panic. An initializer which accesses the
+/// same `LazyEmplace` never completes.
+///
+/// # Example
+///
+/// ```
+/// use inplace::cell::LazyEmplace;
+/// use inplace::init;
+///
+/// static SQUARES: LazyEmplace<[u64; 1 << 17]> = LazyEmplace::new(init::from_fn(|slot| {
+///     // Zero the table in place, then fill it.
+///     let table = unsafe {
+///         slot.as_mut_ptr().write_bytes(0, 1);
+///         slot.assume_init_mut()
+///     };
+///     for (i, square) in table.iter_mut().enumerate() {
+///         *square = (i * i) as u64;
+///     }
+///     table
+/// }));
+///
+/// assert_eq!(SQUARES[1000], 1_000_000);
+/// ```
+pub struct LazyEmplace<T, I = FromFn<T, fn(&mut MaybeUninit<T>) -> &mut T>> {
+    state: AtomicU8,
+    init: UnsafeCell<ManuallyDrop<I>>,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send + Sync, I: Send> Sync for LazyEmplace<T, I> {}
+
+/// Poisons a `LazyEmplace` if its initializer panics.
+struct Poison<'a>(&'a AtomicU8);
+
+impl Drop for Poison<'_> {
+    fn drop(&mut self) {
+        self.0.store(POISONED, Ordering::Release);
+    }
+}
+
+impl<T, I> LazyEmplace<T, I>
+where
+    I: Init<T>,
+{
+    /// Creates a `LazyEmplace` which will construct its value with `init`.
+    pub const fn new(init: I) -> Self {
+        LazyEmplace {
+            state: AtomicU8::new(INCOMPLETE),
+            init: UnsafeCell::new(ManuallyDrop::new(init)),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Constructs the value if it has not been already, and returns a
+    /// reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the initializer panicked on an earlier access.
+    pub fn force(this: &Self) -> &T {
+        loop {
+            match this.state.compare_exchange_weak(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let poison = Poison(&this.state);
+                    unsafe {
+                        let init = ManuallyDrop::take(&mut *this.init.get());
+                        init.initialize((*this.value.get()).as_mut_ptr());
+                    }
+                    core::mem::forget(poison);
+                    this.state.store(COMPLETE, Ordering::Release);
+                }
+                Err(COMPLETE) => return unsafe { (*this.value.get()).assume_init_ref() },
+                Err(POISONED) => panic!("LazyEmplace instance has previously been poisoned"),
+                Err(_) => hint::spin_loop(),
+            }
+        }
+    }
+
+    /// Returns a reference to the value if it has been constructed.
+    pub fn get(this: &Self) -> Option<&T> {
+        match this.state.load(Ordering::Acquire) {
+            COMPLETE => Some(unsafe { (*this.value.get()).assume_init_ref() }),
+            _ => None,
+        }
+    }
+}
+
+impl<T, I> Deref for LazyEmplace<T, I>
+where
+    I: Init<T>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        LazyEmplace::force(self)
+    }
+}
+
+impl<T, I> Drop for LazyEmplace<T, I> {
+    fn drop(&mut self) {
+        match *self.state.get_mut() {
+            INCOMPLETE => unsafe { ManuallyDrop::drop(self.init.get_mut()) },
+            COMPLETE => unsafe { self.value.get_mut().assume_init_drop() },
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use core::cell::OnceCell;
+
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+use crate::init::{construct, try_construct, Init, TryInit};
+
+/// Extension methods for initializing a once cell with an initializer.
+///
+/// The standard cells expose no way to write into their storage, so the value
+/// is constructed immediately before being moved into the cell, in the same
+/// way as [`EmplaceEntry`](crate::EmplaceEntry). What these methods do provide
+/// is that an initializer, rather than a closure returning the value, can be
+/// given, and that it is only run, and its value only constructed, if the
+/// cell is empty. For a once cell whose value is constructed in place, use
+/// [`OnceEmplace`](super::OnceEmplace) instead.
+pub trait OnceExt<T> {
+    /// Returns the value of the cell, constructing it with `init` first if
+    /// the cell is empty.
+    ///
+    /// If the cell is already initialized, `init` is dropped without being
+    /// run.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::cell::OnceCell;
+    /// use inplace::cell::OnceExt;
+    /// use inplace::init;
+    ///
+    /// let cell: OnceCell<[u16; 256]> = OnceCell::new();
+    /// let table = cell.get_or_emplace(init::with(|| core::array::from_fn(|i| i as u16)));
+    /// assert_eq!(table[255], 255);
+    /// ```
+    fn get_or_emplace<I>(&self, init: I) -> &T
+    where
+        I: Init<T>;
+
+    /// Returns the value of the cell, attempting to construct it with `init`
+    /// first if the cell is empty.
+    ///
+    /// If the initializer fails, its error is returned and the cell is left
+    /// empty.
+    fn get_or_try_emplace<I, E>(&self, init: I) -> Result<&T, E>
+    where
+        I: TryInit<T, E>;
+}
+
+impl<T> OnceExt<T> for OnceCell<T> {
+    fn get_or_emplace<I>(&self, init: I) -> &T
+    where
+        I: Init<T>,
+    {
+        self.get_or_init(|| construct(init))
+    }
+
+    fn get_or_try_emplace<I, E>(&self, init: I) -> Result<&T, E>
+    where
+        I: TryInit<T, E>,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        let value = try_construct(init)?;
+        Ok(self.get_or_init(|| value))
+    }
+}
+
+/// If several threads race to initialize the cell with
+/// [`get_or_emplace`](OnceExt::get_or_emplace), only one initializer is run,
+/// and the others wait for its value. Since `OnceLock::get_or_try_init` is
+/// unstable, [`get_or_try_emplace`](OnceExt::get_or_try_emplace) instead runs
+/// its initializer before claiming the cell, so when threads race, several
+/// values may be constructed, of which all but the first stored are dropped.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::OnceLock;
+/// use inplace::cell::OnceExt;
+/// use inplace::init;
+///
+/// static TABLE: OnceLock<[u32; 1024]> = OnceLock::new();
+/// let table = TABLE.get_or_emplace(init::with(|| core::array::from_fn(|i| (i * i) as u32)));
+/// assert_eq!(table[1000], 1_000_000);
+/// ```
+#[cfg(feature = "std")]
+impl<T> OnceExt<T> for OnceLock<T> {
+    fn get_or_emplace<I>(&self, init: I) -> &T
+    where
+        I: Init<T>,
+    {
+        self.get_or_init(|| construct(init))
+    }
+
+    fn get_or_try_emplace<I, E>(&self, init: I) -> Result<&T, E>
+    where
+        I: TryInit<T, E>,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        let value = try_construct(init)?;
+        Ok(self.get_or_init(|| value))
+    }
+}
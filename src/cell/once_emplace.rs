@@ -0,0 +1,174 @@
+use core::cell::UnsafeCell;
+use core::convert::Infallible;
+use core::hint;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::init::{Init, TryInit};
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/// A cell which is written once, with its value constructed in place.
+///
+/// This is the in-place form of `OnceLock`. Where [`OnceExt`](super::OnceExt)
+/// has to construct the value before moving it into a standard cell, an
+/// `OnceEmplace` runs the initializer on its own storage, so a large value
+/// never passes through the stack. Unlike [`LazyEmplace`](super::LazyEmplace),
+/// the initializer is given on access rather than when the cell is created.
+///
+/// If several threads race to initialize the cell, only one initializer is
+/// run, and the others spin until it is complete. If the initializer panics
+/// or fails, the cell is left empty, and can be initialized again. An
+/// initializer which accesses the same cell never completes.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::cell::OnceEmplace;
+/// use inplace::init;
+///
+/// type Table = [u32; 1 << 16];
+/// static TABLE: OnceEmplace<Table> = OnceEmplace::new();
+///
+/// let table = TABLE.get_or_emplace(init::from_fn(|slot: &mut MaybeUninit<Table>| {
+///     // Zero the table in place, then fill it.
+///     let table = unsafe {
+///         slot.as_mut_ptr().write_bytes(0, 1);
+///         slot.assume_init_mut()
+///     };
+///     for (i, square) in table.iter_mut().enumerate() {
+///         *square = (i * i) as u32;
+///     }
+///     table
+/// }));
+/// assert_eq!(table[1000], 1_000_000);
+///
+/// // Once initialized, later initializers are dropped without being run.
+/// let again = TABLE.get_or_emplace(init::with(|| [0; 1 << 16]));
+/// assert_eq!(again[1000], 1_000_000);
+/// ```
+pub struct OnceEmplace<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send + Sync> Sync for OnceEmplace<T> {}
+
+/// Returns a cell to empty if its initializer panics or fails.
+struct Reset<'a>(&'a AtomicU8);
+
+impl Drop for Reset<'_> {
+    fn drop(&mut self) {
+        self.0.store(INCOMPLETE, Ordering::Release);
+    }
+}
+
+impl<T> OnceEmplace<T> {
+    /// Creates an empty cell.
+    pub const fn new() -> Self {
+        OnceEmplace {
+            state: AtomicU8::new(INCOMPLETE),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns a reference to the value, or `None` if the cell is empty or
+    /// being initialized.
+    pub fn get(&self) -> Option<&T> {
+        match self.state.load(Ordering::Acquire) {
+            COMPLETE => Some(unsafe { (*self.value.get()).assume_init_ref() }),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value, or `None` if the cell is
+    /// empty.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        match *self.state.get_mut() {
+            COMPLETE => Some(unsafe { self.value.get_mut().assume_init_mut() }),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the cell, constructing it in place with `init`
+    /// first if the cell is empty.
+    ///
+    /// If the cell is already initialized, `init` is dropped without being
+    /// run.
+    pub fn get_or_emplace<I>(&self, init: I) -> &T
+    where
+        I: Init<T>,
+    {
+        let result = self.get_or_run(|dest| {
+            unsafe { init.initialize(dest) };
+            Ok::<(), Infallible>(())
+        });
+        match result {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Returns the value of the cell, attempting to construct it in place
+    /// with `init` first if the cell is empty.
+    ///
+    /// If the initializer fails, its error is returned and the cell is left
+    /// empty.
+    pub fn get_or_try_emplace<I, E>(&self, init: I) -> Result<&T, E>
+    where
+        I: TryInit<T, E>,
+    {
+        self.get_or_run(|dest| unsafe { init.try_initialize(dest) })
+    }
+
+    /// Claims the cell if it is empty and runs `f` on its storage, or waits
+    /// for the value if another thread has claimed it.
+    fn get_or_run<E>(&self, f: impl FnOnce(*mut T) -> Result<(), E>) -> Result<&T, E> {
+        loop {
+            match self.state.compare_exchange_weak(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let reset = Reset(&self.state);
+                    f(unsafe { (*self.value.get()).as_mut_ptr() })?;
+                    core::mem::forget(reset);
+                    self.state.store(COMPLETE, Ordering::Release);
+                    return Ok(unsafe { (*self.value.get()).assume_init_ref() });
+                }
+                Err(COMPLETE) => return Ok(unsafe { (*self.value.get()).assume_init_ref() }),
+                Err(_) => hint::spin_loop(),
+            }
+        }
+    }
+
+    /// Takes the value out of the cell, leaving it empty.
+    pub fn take(&mut self) -> Option<T> {
+        match *self.state.get_mut() {
+            COMPLETE => {
+                *self.state.get_mut() = INCOMPLETE;
+                Some(unsafe { self.value.get_mut().assume_init_read() })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T> Default for OnceEmplace<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OnceEmplace<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.value.get_mut().assume_init_drop() }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use core::cell::UnsafeCell;
+use core::mem::{self, MaybeUninit};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::init::{Init, PinInit, TryInit};
+
+/// Static storage for a `T` which is initialized in place, once, at runtime.
+///
+/// This reserves space for the value in a `static`, so that a large buffer
+/// can be constructed directly in it, without passing through the stack,
+/// as on a microcontroller with a small stack. The value is never dropped.
+///
+/// Initializing gives a `&'static mut T`, so it can only be done once: later
+/// attempts panic, or with [`try_emplace`](StaticInPlace::try_emplace),
+/// return an error. If an initializer panics or fails, the storage remains
+/// uninitialized and can be initialized again.
+///
+/// # Example
+///
+/// ```
+/// use inplace::cell::StaticInPlace;
+/// use inplace::init;
+///
+/// static FRAME_BUFFER: StaticInPlace<[u32; 1 << 18]> = StaticInPlace::new();
+///
+/// let zeroed = unsafe { init::from_raw(|p: *mut [u32; 1 << 18]| p.write_bytes(0, 1)) };
+/// let frame: &'static mut [u32; 1 << 18] = FRAME_BUFFER.emplace(zeroed);
+/// frame[0] = 0xff00ff;
+/// assert!(FRAME_BUFFER.is_initialized());
+/// ```
+pub struct StaticInPlace<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    claimed: AtomicBool,
+}
+
+unsafe impl<T: Send> Sync for StaticInPlace<T> {}
+
+/// Releases a claim on the storage unless forgotten.
+struct Release<'a>(&'a AtomicBool);
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+// The storage can only be claimed once, so the `&'static mut T` given out
+// for it is unique.
+#[allow(clippy::mut_from_ref)]
+impl<T> StaticInPlace<T> {
+    /// Creates uninitialized storage.
+    pub const fn new() -> Self {
+        StaticInPlace {
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            claimed: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns true if the storage has been initialized, or is being
+    /// initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.claimed.load(Ordering::Acquire)
+    }
+
+    /// Claims the storage, returning a guard which releases it again unless
+    /// forgotten, or `None` if it has already been claimed.
+    fn claim(&self) -> Option<Release<'_>> {
+        self.claimed
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| Release(&self.claimed))
+    }
+
+    /// Constructs the value in the storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage has already been initialized.
+    pub fn emplace<I>(&'static self, init: I) -> &'static mut T
+    where
+        I: Init<T>,
+    {
+        let release = self.claim().expect("StaticInPlace already initialized");
+        unsafe {
+            let value = &mut *self.value.get();
+            init.initialize(value.as_mut_ptr());
+            mem::forget(release);
+            value.assume_init_mut()
+        }
+    }
+
+    /// Attempts to construct the value in the storage.
+    ///
+    /// Returns `Ok(None)` if the storage has already been initialized, in
+    /// which case the initializer is not run.
+    pub fn try_emplace<I, E>(&'static self, init: I) -> Result<Option<&'static mut T>, E>
+    where
+        I: TryInit<T, E>,
+    {
+        let Some(release) = self.claim() else {
+            return Ok(None);
+        };
+        unsafe {
+            let value = &mut *self.value.get();
+            init.try_initialize(value.as_mut_ptr())?;
+            mem::forget(release);
+            Ok(Some(value.assume_init_mut()))
+        }
+    }
+
+    /// Constructs a pinned value in the storage.
+    ///
+    /// Since the value is never moved or dropped, it can be pinned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage has already been initialized.
+    pub fn pin_emplace<I>(&'static self, init: I) -> Pin<&'static mut T>
+    where
+        I: PinInit<T>,
+    {
+        let release = self.claim().expect("StaticInPlace already initialized");
+        unsafe {
+            let value = &mut *self.value.get();
+            init.pin_initialize(value.as_mut_ptr());
+            mem::forget(release);
+            Pin::new_unchecked(value.assume_init_mut())
+        }
+    }
+}
+
+impl<T> Default for StaticInPlace<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
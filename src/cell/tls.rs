@@ -0,0 +1,196 @@
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+
+use std::thread::LocalKey;
+
+use crate::init::{Init, TryInit};
+
+const EMPTY: u8 = 0;
+const RUNNING: u8 = 1;
+const FULL: u8 = 2;
+
+/// Storage for a thread-local value which is constructed in place on first
+/// use.
+///
+/// A `thread_local!` with a `const` initializer reserves this storage in each
+/// thread without running anything, and the value is then constructed in it
+/// directly, rather than being built and moved in as a `thread_local!`
+/// initializer expression would be. The value is dropped when the thread
+/// exits.
+///
+/// The [`thread_local_emplace!`](crate::thread_local_emplace!) macro
+/// declares one with a fixed initializer. Used directly, it also allows
+/// fallible initialization:
+///
+/// ```
+/// use core::cell::RefCell;
+/// use inplace::cell::TlsSlot;
+/// use inplace::init;
+///
+/// thread_local! {
+///     static SCRATCH: TlsSlot<RefCell<[u8; 1 << 16]>> = const { TlsSlot::new() };
+/// }
+///
+/// let len = SCRATCH.with(|slot| {
+///     let buffer = slot.get_or_try_emplace(init::try_from_fn(|slot| {
+///         Ok::<_, ()>(slot.write(RefCell::new([0; 1 << 16])))
+///     }))?;
+///     Ok::<_, ()>(buffer.borrow().len())
+/// });
+/// assert_eq!(len, Ok(1 << 16));
+/// ```
+pub struct TlsSlot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    state: Cell<u8>,
+}
+
+/// Returns the slot to empty if initialization fails or panics.
+struct Reset<'a>(&'a Cell<u8>);
+
+impl Drop for Reset<'_> {
+    fn drop(&mut self) {
+        self.0.set(EMPTY);
+    }
+}
+
+impl<T> TlsSlot<T> {
+    /// Creates empty storage.
+    pub const fn new() -> Self {
+        TlsSlot {
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            state: Cell::new(EMPTY),
+        }
+    }
+
+    /// Returns the value, if it has been constructed.
+    pub fn get(&self) -> Option<&T> {
+        (self.state.get() == FULL).then(|| unsafe { (*self.value.get()).assume_init_ref() })
+    }
+
+    /// Claims the storage for initialization.
+    fn claim(&self) -> Reset<'_> {
+        assert!(
+            self.state.get() == EMPTY,
+            "TlsSlot accessed during its own initialization"
+        );
+        self.state.set(RUNNING);
+        Reset(&self.state)
+    }
+
+    /// Returns the value, constructing it with `init` first if there is none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within the initializer of the same slot.
+    pub fn get_or_emplace<I>(&self, init: I) -> &T
+    where
+        I: Init<T>,
+    {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        let reset = self.claim();
+        unsafe { init.initialize((*self.value.get()).as_mut_ptr()) };
+        core::mem::forget(reset);
+        self.state.set(FULL);
+        self.get().unwrap()
+    }
+
+    /// Returns the value, attempting to construct it with `init` first if
+    /// there is none.
+    ///
+    /// If the initializer fails, its error is returned and the slot is left
+    /// empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within the initializer of the same slot.
+    pub fn get_or_try_emplace<I, E>(&self, init: I) -> Result<&T, E>
+    where
+        I: TryInit<T, E>,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        let reset = self.claim();
+        unsafe { init.try_initialize((*self.value.get()).as_mut_ptr())? };
+        core::mem::forget(reset);
+        self.state.set(FULL);
+        Ok(self.get().unwrap())
+    }
+}
+
+impl<T> Default for TlsSlot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for TlsSlot<T> {
+    fn drop(&mut self) {
+        if self.state.get() == FULL {
+            unsafe { self.value.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+/// A thread-local value declared with
+/// [`thread_local_emplace!`](crate::thread_local_emplace!).
+pub struct TlsKey<T: 'static> {
+    #[doc(hidden)]
+    pub key: &'static LocalKey<TlsSlot<T>>,
+    #[doc(hidden)]
+    pub init: fn(&TlsSlot<T>) -> &T,
+}
+
+impl<T: 'static> TlsKey<T> {
+    /// Calls `f` with this thread's value, constructing it first if this is
+    /// its first use in the thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread's storage has already been destroyed, or if
+    /// called from within the value's own initializer.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.key.with(|slot| f((self.init)(slot)))
+    }
+}
+
+/// Declares thread-local values which are constructed in place on first use.
+///
+/// Each static is a [`TlsKey`], whose value is constructed by its initializer
+/// in the thread's storage the first time it is used in each thread.
+///
+/// # Example
+///
+/// ```
+/// use core::cell::RefCell;
+/// use inplace::init;
+///
+/// inplace::thread_local_emplace! {
+///     static HISTOGRAM: RefCell<[u32; 1 << 16]> = init::with(|| RefCell::new([0; 1 << 16]));
+/// }
+///
+/// HISTOGRAM.with(|h| h.borrow_mut()[42] += 1);
+/// assert_eq!(HISTOGRAM.with(|h| h.borrow()[42]), 1);
+/// ```
+#[macro_export]
+macro_rules! thread_local_emplace {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::cell::TlsKey<$ty> = {
+                ::std::thread_local! {
+                    static SLOT: $crate::cell::TlsSlot<$ty> = const { $crate::cell::TlsSlot::new() };
+                }
+                fn init(slot: &$crate::cell::TlsSlot<$ty>) -> &$ty {
+                    slot.get_or_emplace($init)
+                }
+                $crate::cell::TlsKey { key: &SLOT, init }
+            };
+        )*
+    };
+}
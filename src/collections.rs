@@ -0,0 +1,288 @@
+//! Emplacement into the `alloc::collections` types.
+//!
+//! These types do not expose their storage, so elements are constructed in
+//! the underlying `Vec` buffer, which the collections can be converted to and
+//! from without reallocating.
+
+use core::mem;
+
+#[cfg(not(inplace_no_global_oom_handling))]
+use alloc::collections::btree_map;
+use alloc::collections::{BinaryHeap, VecDeque};
+use alloc::vec::Vec;
+
+use crate::error::InplaceError;
+use crate::init::TryInit;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::{construct, Init};
+use crate::vec::VecExt;
+
+#[cfg(all(feature = "hashbrown", not(inplace_no_global_oom_handling)))]
+mod hash_map;
+#[cfg(all(feature = "slab", not(inplace_no_global_oom_handling)))]
+mod slab;
+
+#[cfg(all(feature = "slab", not(inplace_no_global_oom_handling)))]
+pub use self::slab::SlabExt;
+
+/// Extension methods for constructing `VecDeque` elements in place.
+///
+/// `VecDeque` does not expose the spare slots of its ring buffer, nor any way
+/// to extend its length without passing it a value, so elements cannot be
+/// constructed in the slot before the front or after the back where it
+/// wraps around. Instead, the elements are moved to the start of the buffer,
+/// which takes `O(n)` moves unless they are already there, and the new
+/// element is constructed in the first spare slot after them.
+///
+/// Deques which are only pushed to at the back and never popped from the
+/// front stay at the start of the buffer, so [`push_back_init`] is then
+/// amortized constant time. [`push_front_init`] leaves the elements wrapped
+/// around the end of the buffer, so repeated pushes at the front each take
+/// `O(n)` moves. Where that matters, prefer pushing at the back and reading
+/// the deque in reverse.
+///
+/// [`push_back_init`]: VecDequeExt::push_back_init
+/// [`push_front_init`]: VecDequeExt::push_front_init
+pub trait VecDequeExt<T> {
+    /// Prepends an element, constructing it in the deque's buffer.
+    ///
+    /// The element is constructed as by [`push_back_init`] and then moved to
+    /// the front, which moves only that element. See the
+    /// [trait documentation](VecDequeExt) for the cost of moving the other
+    /// elements first. If the initializer panics, the deque holds the same
+    /// elements as before.
+    ///
+    /// [`push_back_init`]: VecDequeExt::push_back_init
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::VecDeque;
+    /// use inplace::{init, VecDequeExt};
+    ///
+    /// let mut frames: VecDeque<[u8; 1500]> = VecDeque::new();
+    /// frames.push_back_init(init::with(|| [2; 1500]));
+    /// frames.push_front_init(init::with(|| [1; 1500]));
+    /// assert_eq!([frames[0][0], frames[1][0]], [1, 2]);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_front_init<I>(&mut self, init: I)
+    where
+        I: Init<T>;
+
+    /// Appends an element, constructing it in the deque's buffer.
+    ///
+    /// If the elements do not start at the beginning of the buffer, they are
+    /// first moved there, so this is only amortized constant time for deques
+    /// which are neither popped nor pushed at the front. If the initializer
+    /// panics, the deque holds the same elements as before.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_back_init<I>(&mut self, init: I)
+    where
+        I: Init<T>;
+
+    /// Attempts to prepend an element, constructing it in the deque's buffer.
+    ///
+    /// If capacity cannot be reserved, [`InplaceError::Alloc`] is returned
+    /// with the layout of the element, and the initializer is not run. If the
+    /// initializer fails, its error is returned. In either case the deque
+    /// holds the same elements as before.
+    fn try_push_front_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+
+    /// Attempts to append an element, constructing it in the deque's buffer.
+    ///
+    /// Errors are reported as for
+    /// [`try_push_front_init`](VecDequeExt::try_push_front_init).
+    fn try_push_back_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+}
+
+/// Extension methods for constructing `BinaryHeap` elements in place.
+pub trait BinaryHeapExt<T> {
+    /// Pushes an element, constructing it directly in the heap's buffer.
+    ///
+    /// The element is constructed in the first spare slot and then sifted up
+    /// to its position. Converting the buffer back into a heap checks the
+    /// heap property, so this costs `O(n)` comparisons, but only the elements
+    /// on the sift path are moved. If the initializer panics, the heap holds
+    /// the same elements as before.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::BinaryHeap;
+    /// use inplace::{init, BinaryHeapExt};
+    ///
+    /// let mut jobs: BinaryHeap<(u8, [u8; 256])> = BinaryHeap::new();
+    /// jobs.push_init(init::with(|| (1, [0; 256])));
+    /// jobs.push_init(init::with(|| (3, [0; 256])));
+    /// jobs.push_init(init::with(|| (2, [0; 256])));
+    /// assert_eq!(jobs.peek().unwrap().0, 3);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_init<I>(&mut self, init: I)
+    where
+        I: Init<T>;
+
+    /// Attempts to push an element, constructing it directly in the heap's
+    /// buffer.
+    ///
+    /// If capacity cannot be reserved, [`InplaceError::Alloc`] is returned
+    /// with the layout of the element, and the initializer is not run. If the
+    /// initializer fails, its error is returned. In either case the heap
+    /// holds the same elements as before.
+    fn try_push_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+}
+
+/// Extension methods for constructing map values on insertion.
+///
+/// This is implemented for the entries of [`BTreeMap`], and of `hashbrown`'s
+/// `HashMap` when the `hashbrown` feature is enabled.
+///
+/// The initializer is only run if the entry is vacant, after the lookup has
+/// decided where the value will go. Neither map gives access to the storage
+/// of a value before it is inserted: `hashbrown`'s entry and raw entry APIs
+/// only insert complete values, as does `BTreeMap`. So the value is not
+/// constructed in the bucket or node itself, but on the stack, immediately
+/// before being moved into the map, and large values are copied once.
+///
+/// [`BTreeMap`]: alloc::collections::BTreeMap
+#[cfg(not(inplace_no_global_oom_handling))]
+pub trait EmplaceEntry<'a, V> {
+    /// Ensures a value is in the entry, constructing it with `init` if the
+    /// entry is vacant, and returns a mutable reference to the value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use inplace::{init, EmplaceEntry};
+    ///
+    /// let mut pages: BTreeMap<u32, [u8; 4096]> = BTreeMap::new();
+    /// pages.entry(7).or_emplace_with(init::with(|| [0; 4096]))[0] = 1;
+    /// let page = pages.entry(7).or_emplace_with(init::with(|| [0; 4096]));
+    /// assert_eq!(page[0], 1);
+    /// ```
+    fn or_emplace_with<I>(self, init: I) -> &'a mut V
+    where
+        I: Init<V>;
+}
+
+/// Holds a collection's buffer as a `Vec`, converting it back when dropped,
+/// including during a panic.
+struct AsVec<'a, C: From<Vec<T>>, T> {
+    collection: &'a mut C,
+    vec: Vec<T>,
+}
+
+impl<'a, C, T> AsVec<'a, C, T>
+where
+    C: From<Vec<T>> + Default,
+    Vec<T>: From<C>,
+{
+    fn new(collection: &'a mut C) -> Self {
+        let vec = Vec::from(mem::take(collection));
+        AsVec { collection, vec }
+    }
+}
+
+impl<C: From<Vec<T>>, T> Drop for AsVec<'_, C, T> {
+    fn drop(&mut self) {
+        *self.collection = C::from(mem::take(&mut self.vec));
+    }
+}
+
+impl<T> VecDequeExt<T> for VecDeque<T> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_front_init<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        // The slot before the head cannot be addressed through the public
+        // API, so the element is built at the back and rotated round, which
+        // moves only that element within the buffer. This leaves the deque
+        // wrapped, so the next push realigns it.
+        self.push_back_init(init);
+        self.rotate_right(1);
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_back_init<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        // Converting to a `Vec` moves the elements to the start of the
+        // buffer if they wrap around or are offset, so that the back slot is
+        // the first spare slot of the `Vec`.
+        AsVec::new(self).vec.push_init(init);
+    }
+
+    fn try_push_front_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        self.try_push_back_init(init)?;
+        self.rotate_right(1);
+        Ok(())
+    }
+
+    fn try_push_back_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        AsVec::new(self).vec.try_push_init(init)
+    }
+}
+
+impl<T: Ord> BinaryHeapExt<T> for BinaryHeap<T> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_init<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        let mut heap = AsVec::new(self);
+        heap.vec.push_init(init);
+        sift_up(&mut heap.vec);
+    }
+
+    fn try_push_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let mut heap = AsVec::new(self);
+        heap.vec.try_push_init(init)?;
+        sift_up(&mut heap.vec);
+        Ok(())
+    }
+}
+
+/// Moves the last element of a max-heap up to its position.
+fn sift_up<T: Ord>(data: &mut [T]) {
+    let mut index = data.len() - 1;
+    while index > 0 {
+        let parent = (index - 1) / 2;
+        if data[index] <= data[parent] {
+            break;
+        }
+        data.swap(index, parent);
+        index = parent;
+    }
+}
+
+#[cfg(not(inplace_no_global_oom_handling))]
+impl<'a, K: Ord, V> EmplaceEntry<'a, V> for btree_map::Entry<'a, K, V> {
+    fn or_emplace_with<I>(self, init: I) -> &'a mut V
+    where
+        I: Init<V>,
+    {
+        match self {
+            btree_map::Entry::Occupied(entry) => entry.into_mut(),
+            btree_map::Entry::Vacant(entry) => entry.insert(construct(init)),
+        }
+    }
+}
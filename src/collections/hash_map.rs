@@ -0,0 +1,39 @@
+//! Emplacement into `hashbrown` maps.
+
+use core::hash::{BuildHasher, Hash};
+
+use hashbrown::hash_map::Entry;
+
+use crate::collections::EmplaceEntry;
+use crate::init::{construct, Init};
+
+/// The hash and probe computed for the lookup are reused for the insertion,
+/// but the value is constructed on the stack and moved into its bucket, as
+/// `hashbrown` has no API for initializing a bucket in place.
+///
+/// # Example
+///
+/// ```
+/// use hashbrown::HashMap;
+/// use inplace::{init, EmplaceEntry};
+///
+/// let mut pages: HashMap<u32, [u8; 4096]> = HashMap::new();
+/// pages.entry(7).or_emplace_with(init::with(|| [0; 4096]))[0] = 1;
+/// let page = pages.entry(7).or_emplace_with(init::with(|| [0; 4096]));
+/// assert_eq!(page[0], 1);
+/// ```
+impl<'a, K, V, S> EmplaceEntry<'a, V> for Entry<'a, K, V, S>
+where
+    K: Hash,
+    S: BuildHasher,
+{
+    fn or_emplace_with<I>(self, init: I) -> &'a mut V
+    where
+        I: Init<V>,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(construct(init)),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Emplacement into `slab` slabs.
+
+use slab::Slab;
+
+use crate::init::{construct, Init};
+
+/// Extension methods for constructing `Slab` entries in place.
+///
+/// The key is chosen before the initializer runs, so the value can be built
+/// knowing its own key. `Slab` does not expose the storage of a vacant
+/// entry, so, as for [`EmplaceEntry`](crate::EmplaceEntry), the value is
+/// constructed immediately before being written into the slot, and there is
+/// no fallible variant since the slab offers no fallible insertion.
+pub trait SlabExt<T> {
+    /// Inserts a value constructed by `init`, returning its key.
+    ///
+    /// The value is constructed on the stack and then moved into its slot, so
+    /// this does not avoid the copy of a large value. If the initializer
+    /// panics, the slab holds the same entries as before.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use slab::Slab;
+    /// use inplace::{init, SlabExt};
+    ///
+    /// let mut sessions: Slab<[u8; 4096]> = Slab::new();
+    /// let key = sessions.insert_init(init::with(|| [0; 4096]));
+    /// assert_eq!(sessions[key].len(), 4096);
+    /// ```
+    fn insert_init<I>(&mut self, init: I) -> usize
+    where
+        I: Init<T>;
+
+    /// Inserts a value constructed by the initializer `f` returns when
+    /// passed the value's key, returning the key.
+    ///
+    /// As for [`insert_init`](SlabExt::insert_init), the value is moved into
+    /// its slot once constructed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use slab::Slab;
+    /// use inplace::{init, SlabExt};
+    ///
+    /// struct Connection {
+    ///     id: usize,
+    ///     buffer: [u8; 8192],
+    /// }
+    ///
+    /// let mut connections: Slab<Connection> = Slab::new();
+    /// let key = connections.insert_init_with_key(|id| {
+    ///     init!(Connection { id, buffer <- init::with(|| [0; 8192]) })
+    /// });
+    /// assert_eq!(connections[key].id, key);
+    /// ```
+    fn insert_init_with_key<I, F>(&mut self, f: F) -> usize
+    where
+        I: Init<T>,
+        F: FnOnce(usize) -> I;
+}
+
+impl<T> SlabExt<T> for Slab<T> {
+    fn insert_init<I>(&mut self, init: I) -> usize
+    where
+        I: Init<T>,
+    {
+        self.insert_init_with_key(|_| init)
+    }
+
+    fn insert_init_with_key<I, F>(&mut self, f: F) -> usize
+    where
+        I: Init<T>,
+        F: FnOnce(usize) -> I,
+    {
+        let entry = self.vacant_entry();
+        let key = entry.key();
+        entry.insert(construct(f(key)));
+        key
+    }
+}
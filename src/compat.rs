@@ -0,0 +1,8 @@
+//! Interoperation with other crates for in-place initialization.
+//!
+//! Each submodule is enabled by the feature of the same name.
+
+#[cfg(feature = "moveit")]
+pub mod moveit;
+#[cfg(feature = "pin-init")]
+pub mod pin_init;
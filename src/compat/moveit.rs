@@ -0,0 +1,191 @@
+//! Conversions to and from the constructors of the `moveit` crate.
+//!
+//! `moveit` models in-place construction for C++ interoperation, with a
+//! [`New`] trait whose constructors always write to a pinned slot. A `New`
+//! therefore corresponds to this crate's [`PinInit`], and a `TryNew` to a
+//! [`TryInit`] run at a pinned destination. The wrappers here convert between
+//! them, so that either kind of constructor can be passed where the other is
+//! expected, such as to `moveit!` or [`BoxExt::pin_emplace`]. Values made by
+//! a [`TryInit`] are not pinned, so a `TryNew` can only be converted to one
+//! if its output is `Unpin`.
+//!
+//! [`BoxExt::pin_emplace`]: crate::BoxExt::pin_emplace
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(not(inplace_no_global_oom_handling))]
+//! # fn main() {
+//! use core::pin::Pin;
+//! use inplace::compat::moveit::{from_new, to_new};
+//! use inplace::{init, BoxExt};
+//! use moveit::{moveit, new};
+//!
+//! // An initializer from this crate, constructed in a `moveit!` slot.
+//! moveit! {
+//!     let page = to_new::<[u8; 4096], _>(init::with(|| [7u8; 4096]));
+//! }
+//! assert_eq!(page[0], 7);
+//!
+//! // A `moveit` constructor, run by this crate.
+//! let page: Pin<Box<[u8; 4096]>> = Box::pin_emplace(from_new(new::of([0u8; 4096])));
+//! assert_eq!(page[4095], 0);
+//! # }
+//! # #[cfg(inplace_no_global_oom_handling)]
+//! # fn main() {}
+//! ```
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+
+use moveit::{New, TryNew};
+
+use crate::init::{PinInit, TryInit};
+
+/// A pinned initializer wrapped as a `moveit` constructor.
+///
+/// Created by [`to_new`].
+pub struct ToNew<T, I> {
+    init: I,
+    _marker: PhantomData<fn(*mut T)>,
+}
+
+/// Wraps a pinned initializer as a [`New`].
+pub fn to_new<T, I>(init: I) -> ToNew<T, I>
+where
+    I: PinInit<T>,
+{
+    ToNew {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I> New for ToNew<T, I>
+where
+    I: PinInit<T>,
+{
+    type Output = T;
+
+    unsafe fn new(self, this: Pin<&mut MaybeUninit<T>>) {
+        let slot = Pin::into_inner_unchecked(this);
+        self.init.pin_initialize(slot.as_mut_ptr())
+    }
+}
+
+/// A fallible initializer wrapped as a fallible `moveit` constructor.
+///
+/// Created by [`to_try_new`].
+pub struct ToTryNew<T, E, I> {
+    init: I,
+    _marker: PhantomData<fn(*mut T) -> E>,
+}
+
+/// Wraps a fallible initializer as a [`TryNew`].
+pub fn to_try_new<T, E, I>(init: I) -> ToTryNew<T, E, I>
+where
+    I: TryInit<T, E>,
+{
+    ToTryNew {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, E, I> TryNew for ToTryNew<T, E, I>
+where
+    I: TryInit<T, E>,
+{
+    type Output = T;
+    type Error = E;
+
+    unsafe fn try_new(self, this: Pin<&mut MaybeUninit<T>>) -> Result<(), E> {
+        let slot = Pin::into_inner_unchecked(this);
+        self.init.try_initialize(slot.as_mut_ptr())
+    }
+}
+
+/// A `moveit` constructor wrapped as a pinned initializer.
+///
+/// Created by [`from_new`].
+pub struct FromNew<T, N> {
+    new: N,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Wraps a [`New`] as a pinned initializer.
+///
+/// Since a `New` may rely on its output never moving, it is only usable as a
+/// [`PinInit`].
+pub fn from_new<T, N>(new: N) -> FromNew<T, N>
+where
+    N: New<Output = T>,
+{
+    FromNew {
+        new,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, N> PinInit<T> for FromNew<T, N>
+where
+    N: New<Output = T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        let slot = Pin::new_unchecked(&mut *dest.cast::<MaybeUninit<T>>());
+        self.new.new(slot)
+    }
+}
+
+/// A fallible `moveit` constructor wrapped as a fallible initializer.
+///
+/// Created by [`from_try_new`].
+pub struct FromTryNew<T, N> {
+    new: N,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Wraps a [`TryNew`] as a fallible initializer.
+///
+/// A value constructed by a [`TryInit`] may be moved afterwards, while a
+/// `TryNew` may rely on its output never moving, so this is only available
+/// for outputs which are `Unpin`.
+pub fn from_try_new<T, N>(new: N) -> FromTryNew<T, N>
+where
+    N: TryNew<Output = T>,
+    T: Unpin,
+{
+    FromTryNew {
+        new,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, N> TryInit<T, N::Error> for FromTryNew<T, N>
+where
+    N: TryNew<Output = T>,
+    T: Unpin,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), N::Error> {
+        let slot = Pin::new(&mut *dest.cast::<MaybeUninit<T>>());
+        self.new.try_new(slot)
+    }
+}
@@ -0,0 +1,206 @@
+//! Conversions to and from the initializers of the `pin-init` crate.
+//!
+//! `pin-init` is the library used by the Linux kernel for pinned in-place
+//! initialization, published on crates.io as `pinned-init`. Its
+//! `PinInit<T, E>` and `Init<T, E>` traits correspond to this crate's
+//! [`PinInit`], [`Init`] and [`TryInit`], except that failure is part of
+//! every initializer, and no layout is exposed, since the value is always
+//! sized or placed by the caller.
+//!
+//! Both crates implement their traits for every value, so neither trait can
+//! be implemented for the other crate's initializers without overlap. The
+//! conversions are therefore made through the functions here, which wrap an
+//! initializer from one crate as an initializer of the other.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(not(inplace_no_global_oom_handling))]
+//! # fn main() {
+//! use core::convert::Infallible;
+//! use core::mem::MaybeUninit;
+//! use inplace::compat::pin_init::{from_init, to_init};
+//! use inplace::{init, BoxExt};
+//!
+//! // An initializer from this crate, run as `pin-init` code would run it.
+//! let page = to_init(init::with(|| [7u8; 4096]));
+//! let mut slot = MaybeUninit::<[u8; 4096]>::uninit();
+//! unsafe { pin_init::Init::__init(page, slot.as_mut_ptr()).unwrap() };
+//! assert_eq!(unsafe { slot.assume_init_ref() }[0], 7);
+//!
+//! // An initializer from `pin-init`, run by this crate.
+//! let zeroed = unsafe {
+//!     pin_init::init_from_closure(|slot: *mut [u8; 4096]| {
+//!         slot.write_bytes(0, 1);
+//!         Ok::<(), Infallible>(())
+//!     })
+//! };
+//! let page: Box<[u8; 4096]> = Box::emplace(from_init(zeroed));
+//! assert_eq!(page[4095], 0);
+//! # }
+//! # #[cfg(inplace_no_global_oom_handling)]
+//! # fn main() {}
+//! ```
+
+use core::alloc::Layout;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use crate::init::{Init, PinInit, TryInit};
+
+/// Wraps a pinned initializer as a `pin_init::PinInit`.
+pub fn to_pin_init<T, I>(init: I) -> impl pin_init::PinInit<T, Infallible>
+where
+    I: PinInit<T>,
+{
+    unsafe {
+        pin_init::pin_init_from_closure(move |slot: *mut T| {
+            init.pin_initialize(slot);
+            Ok(())
+        })
+    }
+}
+
+/// Wraps an initializer as a `pin_init::Init`.
+pub fn to_init<T, I>(init: I) -> impl pin_init::Init<T, Infallible>
+where
+    I: Init<T>,
+{
+    unsafe {
+        pin_init::init_from_closure(move |slot: *mut T| {
+            init.initialize(slot);
+            Ok(())
+        })
+    }
+}
+
+/// Wraps a fallible initializer as a `pin_init::Init`.
+pub fn to_try_init<T, E, I>(init: I) -> impl pin_init::Init<T, E>
+where
+    I: TryInit<T, E>,
+{
+    unsafe { pin_init::init_from_closure(move |slot: *mut T| init.try_initialize(slot)) }
+}
+
+/// A `pin_init::PinInit` wrapped as a [`PinInit`].
+///
+/// Created by [`from_pin_init`].
+pub struct FromPinInit<T, I> {
+    init: I,
+    _marker: PhantomData<fn(*mut T)>,
+}
+
+/// Wraps a `pin_init::PinInit` which cannot fail as a [`PinInit`].
+pub fn from_pin_init<T, I>(init: I) -> FromPinInit<T, I>
+where
+    I: pin_init::PinInit<T, Infallible>,
+{
+    FromPinInit {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I> PinInit<T> for FromPinInit<T, I>
+where
+    I: pin_init::PinInit<T, Infallible>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        match self.init.__pinned_init(dest) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+}
+
+/// A `pin_init::Init` wrapped as an [`Init`].
+///
+/// Created by [`from_init`].
+pub struct FromInit<T, I> {
+    init: I,
+    _marker: PhantomData<fn(*mut T)>,
+}
+
+/// Wraps a `pin_init::Init` which cannot fail as an [`Init`].
+pub fn from_init<T, I>(init: I) -> FromInit<T, I>
+where
+    I: pin_init::Init<T, Infallible>,
+{
+    FromInit {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I> PinInit<T> for FromInit<T, I>
+where
+    I: pin_init::Init<T, Infallible>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, I> Init<T> for FromInit<T, I>
+where
+    I: pin_init::Init<T, Infallible>,
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        match self.init.__init(dest) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+}
+
+/// A fallible `pin_init::Init` wrapped as a [`TryInit`].
+///
+/// Created by [`try_from_init`].
+pub struct TryFromInit<T, E, I> {
+    init: I,
+    _marker: PhantomData<fn(*mut T) -> E>,
+}
+
+/// Wraps a fallible `pin_init::Init` as a [`TryInit`].
+pub fn try_from_init<T, E, I>(init: I) -> TryFromInit<T, E, I>
+where
+    I: pin_init::Init<T, E>,
+{
+    TryFromInit {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, E, I> TryInit<T, E> for TryFromInit<T, E, I>
+where
+    I: pin_init::Init<T, E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E> {
+        self.init.__init(dest)
+    }
+}
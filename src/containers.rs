@@ -0,0 +1,4 @@
+//! Containers built on the emplacement APIs.
+
+pub mod dst_array;
+pub mod dst_vec;
@@ -0,0 +1,560 @@
+//! An array of separately allocated, possibly unsized values.
+//!
+//! This is the `DstArray` from the sketch, implemented with initializers in
+//! place of `?inplace T` parameters. Each element has its own allocation,
+//! sized from the initializer's layout, so elements of different sizes can be
+//! stored side by side and constructed without passing through the stack.
+
+use core::alloc::Layout;
+use core::convert::Infallible;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut, Index, IndexMut, RangeBounds};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+#[cfg(not(inplace_no_global_oom_handling))]
+use alloc::boxed::Box;
+use alloc::vec::{self, Vec};
+
+use crate::error::InplaceError;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::Init;
+use crate::init::TryInit;
+use crate::raw::{self, DeallocGuard};
+
+/// An array of separately allocated, possibly unsized values.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::containers::dst_array::DstArray;
+///
+/// let mut names: DstArray<str> = DstArray::new();
+/// names.push("ferris");
+/// names.push_box(String::from("corro").into_boxed_str());
+/// assert_eq!(names.get(1), Some("corro"));
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub struct DstArray<T: ?Sized> {
+    elements: Vec<Element<T>>,
+}
+
+/// An element removed from a [`DstArray`], still in its own allocation.
+///
+/// The element is dropped and its allocation freed when this is dropped.
+pub struct Element<T: ?Sized> {
+    ptr: NonNull<T>,
+    layout: Layout,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Element<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for Element<T> {}
+
+impl<T: ?Sized> Deref for Element<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for Element<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Element<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: ?Sized> Drop for Element<T> {
+    fn drop(&mut self) {
+        let _guard = DeallocGuard {
+            addr: self.ptr.cast(),
+            layout: self.layout,
+        };
+        unsafe { self.ptr.as_ptr().drop_in_place() }
+    }
+}
+
+impl<T: ?Sized> DstArray<T> {
+    /// Creates an empty array.
+    pub const fn new() -> Self {
+        DstArray {
+            elements: Vec::new(),
+        }
+    }
+
+    /// Returns the number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns true if the array contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Appends an element, constructing it in a new allocation.
+    ///
+    /// Since values are initializers for themselves, this accepts either a
+    /// value or an initializer, as the sketch's `?inplace T` parameter does.
+    /// If the initializer panics, the allocation is freed and the array is
+    /// unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::containers::dst_array::DstArray;
+    /// use inplace::init;
+    ///
+    /// let mut arr: DstArray<[u8; 1_000]> = DstArray::new();
+    /// arr.push([1; 1_000]);
+    /// arr.push(init::with(|| [2; 1_000]));
+    /// assert_eq!(arr.get(1).unwrap()[999], 2);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn push<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        let layout = init.layout();
+        let addr = raw::allocate(layout);
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            init.initialize(dest);
+            mem::forget(guard);
+            self.elements.push(Element {
+                ptr: NonNull::new_unchecked(dest),
+                layout,
+            });
+        }
+    }
+
+    /// Attempts to append an element, constructing it in a new allocation.
+    ///
+    /// If the element cannot be allocated, [`InplaceError::Alloc`] is
+    /// returned and the initializer is not run, as `append` returns `false`
+    /// in the sketch. If the initializer fails, its error is returned. In
+    /// either case the array is unchanged.
+    pub fn try_push<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        self.elements
+            .try_reserve(1)
+            .map_err(|_| InplaceError::Alloc(Layout::new::<Element<T>>()))?;
+        let layout = init.layout();
+        let addr = raw::try_allocate(layout).ok_or(InplaceError::Alloc(layout))?;
+        let dest = init.dest(addr.as_ptr());
+        let guard = DeallocGuard { addr, layout };
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            mem::forget(guard);
+            raw::push_reserved(
+                &mut self.elements,
+                Element {
+                    ptr: NonNull::new_unchecked(dest),
+                    layout,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Appends an element which has already been constructed in a `Box`,
+    /// taking over its allocation.
+    ///
+    /// This allows values which can only be constructed as a `Box`, such as
+    /// trait objects obtained by unsizing, to be stored without copying.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fmt::Display;
+    /// use inplace::containers::dst_array::DstArray;
+    ///
+    /// let mut items: DstArray<dyn Display> = DstArray::new();
+    /// items.push_box(Box::new(1.5));
+    /// items.push_box(Box::new("two"));
+    /// assert_eq!(items.get(0).unwrap().to_string(), "1.5");
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn push_box(&mut self, value: Box<T>) {
+        let layout = Layout::for_value(&*value);
+        let ptr = unsafe { NonNull::new_unchecked(Box::into_raw(value)) };
+        self.elements.push(Element { ptr, layout });
+    }
+
+    /// Returns a reference to the element at `index`, or `None` if out of
+    /// bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let element = self.elements.get(index)?;
+        Some(unsafe { element.ptr.as_ref() })
+    }
+
+    /// Returns a mutable reference to the element at `index`, or `None` if
+    /// out of bounds.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let element = self.elements.get_mut(index)?;
+        Some(unsafe { element.ptr.as_mut() })
+    }
+
+    /// Returns an iterator over references to the elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(not(inplace_no_global_oom_handling))]
+    /// # fn main() {
+    /// use std::fmt::Display;
+    /// use inplace::containers::dst_array::DstArray;
+    ///
+    /// let mut items: DstArray<dyn Display> = DstArray::new();
+    /// items.push_box(Box::new(1));
+    /// items.push_box(Box::new('b'));
+    /// let text: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+    /// assert_eq!(text, ["1", "b"]);
+    /// # }
+    /// # #[cfg(inplace_no_global_oom_handling)]
+    /// # fn main() {}
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.elements.iter(),
+        }
+    }
+
+    /// Returns an iterator over mutable references to the elements.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            inner: self.elements.iter_mut(),
+        }
+    }
+
+    /// Removes and returns the element at `index`, shifting the following
+    /// elements down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Element<T> {
+        let len = self.len();
+        assert!(
+            index < len,
+            "removal index (is {index}) should be < len (is {len})"
+        );
+        self.elements.remove(index)
+    }
+
+    /// Removes and returns the last element, or `None` if the array is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(not(inplace_no_global_oom_handling))]
+    /// # fn main() {
+    /// use inplace::containers::dst_array::DstArray;
+    ///
+    /// let mut arr: DstArray<[u8]> = DstArray::new();
+    /// arr.push(&[1, 2][..]);
+    /// arr.push(&[3, 4, 5][..]);
+    /// assert_eq!(&*arr.pop().unwrap(), &[3, 4, 5]);
+    /// assert_eq!(arr.len(), 1);
+    /// # }
+    /// # #[cfg(inplace_no_global_oom_handling)]
+    /// # fn main() {}
+    /// ```
+    pub fn pop(&mut self) -> Option<Element<T>> {
+        self.elements.pop()
+    }
+
+    /// Removes the elements in `range`, returning them as an iterator.
+    ///
+    /// Any elements not consumed from the iterator are dropped when it is
+    /// dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_, T>
+    where
+        R: RangeBounds<usize>,
+    {
+        Drain {
+            inner: self.elements.drain(range),
+        }
+    }
+
+    /// Drops the elements after the first `len`, freeing their allocations.
+    ///
+    /// Has no effect if the array is already shorter than `len`.
+    pub fn truncate(&mut self, len: usize) {
+        self.elements.truncate(len)
+    }
+
+    /// Drops all elements.
+    pub fn clear(&mut self) {
+        self.elements.clear()
+    }
+}
+
+impl DstArray<[u8]> {
+    /// Reserves an element of up to `max_len` bytes, to be written in place
+    /// before it is appended.
+    ///
+    /// This is for bytes whose length is only known once they have been
+    /// written, such as a datagram received from a socket with
+    /// [`recv_emplaced`](crate::io::recv_emplaced). The allocation is made
+    /// for `max_len` bytes, and shrunk to fit when the element is appended
+    /// with [`ReservedBytes::assume_init`]. Allocation failure is passed to
+    /// the global allocation error handler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_len` is larger than `isize::MAX`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::containers::dst_array::DstArray;
+    ///
+    /// let mut lines: DstArray<[u8]> = DstArray::new();
+    /// let mut reserved = lines.reserve_bytes(1024);
+    /// let line = b"GET / HTTP/1.1";
+    /// for (dest, &byte) in reserved.as_uninit_mut().iter_mut().zip(line) {
+    ///     dest.write(byte);
+    /// }
+    /// unsafe { reserved.assume_init(line.len()) };
+    /// assert_eq!(&lines[0], line);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn reserve_bytes(&mut self, max_len: usize) -> ReservedBytes<'_> {
+        self.elements.reserve(1);
+        let layout = Layout::array::<u8>(max_len).expect("slice length overflows layout");
+        ReservedBytes {
+            addr: raw::allocate(layout),
+            layout,
+            array: self,
+        }
+    }
+
+    /// Attempts to reserve an element of up to `max_len` bytes, to be
+    /// written in place before it is appended.
+    ///
+    /// As [`reserve_bytes`](DstArray::reserve_bytes), but allocation failure
+    /// is returned as [`InplaceError::Alloc`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_len` is larger than `isize::MAX`.
+    pub fn try_reserve_bytes(
+        &mut self,
+        max_len: usize,
+    ) -> Result<ReservedBytes<'_>, InplaceError<Infallible>> {
+        self.elements
+            .try_reserve(1)
+            .map_err(|_| InplaceError::Alloc(Layout::new::<Element<[u8]>>()))?;
+        let layout = Layout::array::<u8>(max_len).expect("slice length overflows layout");
+        let addr = raw::try_allocate(layout).ok_or(InplaceError::Alloc(layout))?;
+        Ok(ReservedBytes {
+            addr,
+            layout,
+            array: self,
+        })
+    }
+}
+
+/// An element of a [`DstArray<[u8]>`] reserved to be written in place.
+///
+/// Created by [`DstArray::reserve_bytes`] and
+/// [`DstArray::try_reserve_bytes`]. Dropping it frees the allocation without
+/// appending anything.
+pub struct ReservedBytes<'a> {
+    array: &'a mut DstArray<[u8]>,
+    addr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl<'a> ReservedBytes<'a> {
+    /// Returns the length of the reservation, the most bytes the element
+    /// can hold.
+    pub fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Returns the reserved bytes, which are uninitialized.
+    pub fn as_uninit_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        unsafe { slice::from_raw_parts_mut(self.addr.as_ptr().cast(), self.layout.size()) }
+    }
+
+    /// Appends the first `len` reserved bytes as an element, shrinking the
+    /// allocation to fit them, and returns the element.
+    ///
+    /// # Safety
+    ///
+    /// `len` must be no larger than the capacity, and the first `len` bytes
+    /// must have been initialized.
+    pub unsafe fn assume_init(self, len: usize) -> &'a mut [u8] {
+        let this = mem::ManuallyDrop::new(self);
+        let (addr, layout) = raw::shrink(this.addr, this.layout, len);
+        let dest = ptr::slice_from_raw_parts_mut(addr.as_ptr(), len);
+        let elements = &mut ptr::read(&this.array).elements;
+        raw::push_reserved(
+            elements,
+            Element {
+                ptr: NonNull::new_unchecked(dest),
+                layout,
+            },
+        );
+        &mut *dest
+    }
+}
+
+impl fmt::Debug for ReservedBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReservedBytes")
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ReservedBytes<'_> {
+    fn drop(&mut self) {
+        unsafe { raw::deallocate(self.addr, self.layout) }
+    }
+}
+
+impl<T: ?Sized> Default for DstArray<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for DstArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: ?Sized> Index<usize> for DstArray<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.elements[index]
+    }
+}
+
+impl<T: ?Sized> IndexMut<usize> for DstArray<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.elements[index]
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a DstArray<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a mut DstArray<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+/// An iterator over references to the elements of a [`DstArray`].
+///
+/// Created by [`DstArray::iter`].
+pub struct Iter<'a, T: ?Sized> {
+    inner: slice::Iter<'a, Element<T>>,
+}
+
+impl<'a, T: ?Sized> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|element| &**element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back().map(|element| &**element)
+    }
+}
+
+impl<T: ?Sized> ExactSizeIterator for Iter<'_, T> {}
+
+/// An iterator over mutable references to the elements of a [`DstArray`].
+///
+/// Created by [`DstArray::iter_mut`].
+pub struct IterMut<'a, T: ?Sized> {
+    inner: slice::IterMut<'a, Element<T>>,
+}
+
+impl<'a, T: ?Sized> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.inner.next().map(|element| &mut **element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        self.inner.next_back().map(|element| &mut **element)
+    }
+}
+
+impl<T: ?Sized> ExactSizeIterator for IterMut<'_, T> {}
+
+/// An iterator removing elements from a [`DstArray`].
+///
+/// Created by [`DstArray::drain`].
+pub struct Drain<'a, T: ?Sized> {
+    inner: vec::Drain<'a, Element<T>>,
+}
+
+impl<T: ?Sized> Iterator for Drain<'_, T> {
+    type Item = Element<T>;
+
+    fn next(&mut self) -> Option<Element<T>> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T: ?Sized> DoubleEndedIterator for Drain<'_, T> {
+    fn next_back(&mut self) -> Option<Element<T>> {
+        self.inner.next_back()
+    }
+}
+
+impl<T: ?Sized> ExactSizeIterator for Drain<'_, T> {}
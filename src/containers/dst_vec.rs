@@ -0,0 +1,374 @@
+//! A packed array of possibly unsized values in a single buffer.
+//!
+//! [`DstVec`] stores its elements one after another in one growable buffer,
+//! each at an offset aligned for its own layout. Compared to the
+//! pointer-per-element [`DstArray`], this uses one allocation instead of one
+//! per element and keeps the elements close together in memory, but the
+//! elements move when the buffer grows, so references to them are not
+//! stable and they cannot be pinned. Without the `nightly` feature, the
+//! elements can only be sized types, slices and `str`, as described by
+//! [`Element`].
+//!
+//! [`DstArray`]: super::dst_array::DstArray
+
+use core::alloc::{Layout, LayoutError};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Index, IndexMut};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use alloc::vec::Vec;
+
+use crate::error::InplaceError;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::Init;
+use crate::init::TryInit;
+use crate::raw;
+
+/// A packed array of possibly unsized values in a single buffer.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::containers::dst_vec::DstVec;
+///
+/// let mut words: DstVec<str> = DstVec::new();
+/// words.push("packed");
+/// words.push("together");
+/// assert_eq!(&words[1], "together");
+/// assert_eq!(words.iter().map(str::len).sum::<usize>(), 14);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub struct DstVec<T: ?Sized + Element> {
+    buf: NonNull<u8>,
+    layout: Layout,
+    used: usize,
+    entries: Vec<Entry<T>>,
+    _marker: PhantomData<T>,
+}
+
+/// The position of an element in the buffer, and the metadata needed to
+/// rebuild a pointer to it.
+struct Entry<T: ?Sized + Element> {
+    offset: usize,
+    metadata: T::Metadata,
+}
+
+/// The types which can be elements of a [`DstVec`].
+///
+/// The elements move when the buffer grows, so pointers to them are rebuilt
+/// from the buffer and their pointer metadata. Stable Rust can only do this
+/// for sized types, slices and `str`. With the `nightly` feature, this is
+/// implemented for every type, including trait objects.
+///
+/// # Safety
+///
+/// [`from_parts`](Element::from_parts) must return a pointer with the address
+/// and provenance of `addr`, and the metadata of the pointer passed to
+/// [`metadata`](Element::metadata).
+pub unsafe trait Element {
+    /// The pointer metadata of the type.
+    type Metadata: Copy;
+
+    /// Returns the metadata of `ptr`.
+    fn metadata(ptr: *mut Self) -> Self::Metadata;
+
+    /// Returns a pointer to `addr` with the given metadata.
+    fn from_parts(addr: *mut u8, metadata: Self::Metadata) -> *mut Self;
+}
+
+#[cfg(not(feature = "nightly"))]
+unsafe impl<T> Element for T {
+    type Metadata = ();
+
+    fn metadata(_: *mut T) {}
+
+    fn from_parts(addr: *mut u8, _: ()) -> *mut T {
+        addr.cast()
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+unsafe impl<T> Element for [T] {
+    type Metadata = usize;
+
+    fn metadata(ptr: *mut [T]) -> usize {
+        ptr.len()
+    }
+
+    fn from_parts(addr: *mut u8, len: usize) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), len)
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+unsafe impl Element for str {
+    type Metadata = usize;
+
+    fn metadata(ptr: *mut str) -> usize {
+        (ptr as *mut [u8]).len()
+    }
+
+    fn from_parts(addr: *mut u8, len: usize) -> *mut str {
+        ptr::slice_from_raw_parts_mut(addr, len) as *mut str
+    }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<T: ?Sized> Element for T {
+    type Metadata = <T as core::ptr::Pointee>::Metadata;
+
+    fn metadata(ptr: *mut T) -> Self::Metadata {
+        core::ptr::metadata(ptr)
+    }
+
+    fn from_parts(addr: *mut u8, metadata: Self::Metadata) -> *mut T {
+        core::ptr::from_raw_parts_mut(addr, metadata)
+    }
+}
+
+unsafe impl<T: ?Sized + Element + Send> Send for DstVec<T> {}
+unsafe impl<T: ?Sized + Element + Sync> Sync for DstVec<T> {}
+
+impl<T: ?Sized + Element> DstVec<T> {
+    /// Creates an empty array, without allocating.
+    pub fn new() -> Self {
+        let layout = Layout::new::<()>();
+        DstVec {
+            buf: NonNull::dangling(),
+            layout,
+            used: 0,
+            entries: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the array contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of bytes of the buffer in use, including padding
+    /// between elements.
+    pub fn used_bytes(&self) -> usize {
+        self.used
+    }
+
+    /// Appends an element, constructing it at the end of the buffer.
+    ///
+    /// The element is placed at the next offset aligned for its layout. If
+    /// the buffer is too small, or not aligned enough, it is reallocated and
+    /// the existing elements are moved. If the initializer panics, the array
+    /// is unchanged.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn push<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        let layout = init.layout();
+        let offset = self.used.next_multiple_of(layout.align());
+        let end = offset
+            .checked_add(layout.size())
+            .expect("DstVec capacity overflow");
+        let grow = self
+            .grow_layout(end, layout.align())
+            .expect("DstVec capacity overflow");
+        if let Some(buf_layout) = grow {
+            let buf = raw::allocate(buf_layout);
+            self.replace_buf(buf, buf_layout);
+        }
+        self.entries.reserve(1);
+        unsafe {
+            let dest = init.dest(self.buf.as_ptr().add(offset));
+            let metadata = T::metadata(dest);
+            init.initialize(dest);
+            self.entries.push(Entry { offset, metadata });
+        }
+        self.used = end;
+    }
+
+    /// Attempts to append an element, constructing it at the end of the
+    /// buffer.
+    ///
+    /// If the buffer cannot be grown, [`InplaceError::Alloc`] is returned
+    /// with the layout of the element, and the initializer is not run. If the
+    /// initializer fails, its error is returned. In either case the array is
+    /// unchanged.
+    pub fn try_push<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let layout = init.layout();
+        let offset = self.used.next_multiple_of(layout.align());
+        let end = offset
+            .checked_add(layout.size())
+            .ok_or(InplaceError::Alloc(layout))?;
+        let grow = self
+            .grow_layout(end, layout.align())
+            .map_err(|_| InplaceError::Alloc(layout))?;
+        if let Some(buf_layout) = grow {
+            let buf = raw::try_allocate(buf_layout).ok_or(InplaceError::Alloc(layout))?;
+            self.replace_buf(buf, buf_layout);
+        }
+        self.entries
+            .try_reserve(1)
+            .map_err(|_| InplaceError::Alloc(layout))?;
+        unsafe {
+            let dest = init.dest(self.buf.as_ptr().add(offset));
+            let metadata = T::metadata(dest);
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            raw::push_reserved(&mut self.entries, Entry { offset, metadata });
+        }
+        self.used = end;
+        Ok(())
+    }
+
+    /// Returns the layout the buffer must be grown to, if it is too small to
+    /// hold `end` bytes at alignment `align`.
+    fn grow_layout(&self, end: usize, align: usize) -> Result<Option<Layout>, LayoutError> {
+        if end <= self.layout.size() && align <= self.layout.align() {
+            return Ok(None);
+        }
+        let size = end.max(self.layout.size().saturating_mul(2));
+        let align = align.max(self.layout.align());
+        Layout::from_size_align(size, align).map(Some)
+    }
+
+    /// Moves the elements into a new buffer, and frees the old one.
+    fn replace_buf(&mut self, buf: NonNull<u8>, layout: Layout) {
+        unsafe {
+            ptr::copy_nonoverlapping(self.buf.as_ptr(), buf.as_ptr(), self.used);
+            raw::deallocate(self.buf, self.layout);
+        }
+        self.buf = buf;
+        self.layout = layout;
+    }
+
+    fn element(&self, entry: &Entry<T>) -> *mut T {
+        T::from_parts(
+            unsafe { self.buf.as_ptr().add(entry.offset) },
+            entry.metadata,
+        )
+    }
+
+    /// Returns a reference to the element at `index`, or `None` if out of
+    /// bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let entry = self.entries.get(index)?;
+        Some(unsafe { &*self.element(entry) })
+    }
+
+    /// Returns a mutable reference to the element at `index`, or `None` if
+    /// out of bounds.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let entry = self.entries.get(index)?;
+        Some(unsafe { &mut *self.element(entry) })
+    }
+
+    /// Returns an iterator over references to the elements.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            vec: self,
+            entries: self.entries.iter(),
+        }
+    }
+
+    /// Drops the elements after the first `len`, releasing their space in the
+    /// buffer.
+    ///
+    /// Has no effect if the array is already shorter than `len`.
+    pub fn truncate(&mut self, len: usize) {
+        while self.entries.len() > len {
+            let entry = self.entries.pop().unwrap();
+            self.used = entry.offset;
+            unsafe { self.element(&entry).drop_in_place() }
+        }
+    }
+
+    /// Drops all elements.
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+}
+
+impl<T: ?Sized + Element> Drop for DstVec<T> {
+    fn drop(&mut self) {
+        self.clear();
+        unsafe { raw::deallocate(self.buf, self.layout) }
+    }
+}
+
+impl<T: ?Sized + Element> Default for DstVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized + Element + fmt::Debug> fmt::Debug for DstVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: ?Sized + Element> Index<usize> for DstVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        unsafe { &*self.element(&self.entries[index]) }
+    }
+}
+
+impl<T: ?Sized + Element> IndexMut<usize> for DstVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        unsafe { &mut *self.element(&self.entries[index]) }
+    }
+}
+
+impl<'a, T: ?Sized + Element> IntoIterator for &'a DstVec<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// An iterator over references to the elements of a [`DstVec`].
+///
+/// Created by [`DstVec::iter`].
+pub struct Iter<'a, T: ?Sized + Element> {
+    vec: &'a DstVec<T>,
+    entries: slice::Iter<'a, Entry<T>>,
+}
+
+impl<'a, T: ?Sized + Element> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let entry = self.entries.next()?;
+        Some(unsafe { &*self.vec.element(entry) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized + Element> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let entry = self.entries.next_back()?;
+        Some(unsafe { &*self.vec.element(entry) })
+    }
+}
+
+impl<T: ?Sized + Element> ExactSizeIterator for Iter<'_, T> {}
@@ -0,0 +1,470 @@
+//! Construction of slice-tailed dynamically sized types.
+//!
+//! A struct whose last field is a slice, such as `struct Packet { meta: M,
+//! data: [T] }`, can only be created in Rust by unsizing a struct with an array tail,
+//! which fixes the length at compile time. With initializers the length can
+//! be chosen at runtime: the combined layout is computed up front, the
+//! header and the tail are initialized in place, and the result is addressed
+//! through a fat pointer carrying the tail length.
+//!
+//! [`HeaderSlice`] covers the common case of one header and one tail. Other
+//! unsized types can describe their own layout by implementing
+//! [`LayoutProvider`], and are then constructed with [`from_params`]. With
+//! the `nightly` feature, any unsized type can be constructed from its
+//! pointer metadata with `from_metadata`, without describing its layout.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, addr_of_mut};
+
+use crate::__private::DropGuard;
+use crate::init::{Init, PinInit, Prefix};
+
+/// A sized header followed by a slice tail.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{dst, BoxExt};
+/// use inplace::dst::HeaderSlice;
+///
+/// let packet: Box<HeaderSlice<u16, u8>> = Box::emplace(dst::with_tail(7, &[1, 2, 3][..]));
+/// assert_eq!(packet.header, 7);
+/// assert_eq!(&packet.tail, &[1, 2, 3]);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[repr(C)]
+pub struct HeaderSlice<H, T> {
+    /// The sized header.
+    pub header: H,
+    /// The slice tail.
+    pub tail: [T],
+}
+
+/// An initializer for a [`HeaderSlice`].
+///
+/// Created by [`with_tail`] and [`with_tail_iter`].
+pub struct WithTail<H, T, IH, IT> {
+    header: IH,
+    tail: IT,
+    len: usize,
+    _marker: PhantomData<fn(H, T)>,
+}
+
+/// Creates an initializer for a [`HeaderSlice`] from initializers for its
+/// header and its tail.
+///
+/// The length of the tail is taken from the tail initializer. If the tail
+/// initializer panics, the header is dropped.
+pub fn with_tail<H, T, IH, IT>(header: IH, tail: IT) -> WithTail<H, T, IH, IT>
+where
+    IH: PinInit<H>,
+    IT: PinInit<[T]>,
+{
+    let len = tail.dest(ptr::null_mut()).len();
+    WithTail {
+        header,
+        tail,
+        len,
+        _marker: PhantomData,
+    }
+}
+
+/// Creates an initializer for a [`HeaderSlice`] whose tail is filled from an
+/// exact-size iterator.
+///
+/// The length of the tail is the length reported by the iterator. The
+/// iterator yielding a different number of items is a bug, and causes a
+/// panic when the initializer is run, after dropping what was written.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{dst, BoxExt};
+/// use inplace::dst::HeaderSlice;
+///
+/// let squares: Box<HeaderSlice<&str, u32>> =
+///     Box::emplace(dst::with_tail_iter("squares", (1..5).map(|n| n * n)));
+/// assert_eq!(&squares.tail, &[1, 4, 9, 16]);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn with_tail_iter<H, T, IH, It>(
+    header: IH,
+    iter: It,
+) -> WithTail<H, T, IH, TailIter<T, It::IntoIter>>
+where
+    IH: PinInit<H>,
+    It: IntoIterator<Item = T>,
+    It::IntoIter: ExactSizeIterator,
+{
+    let iter = iter.into_iter();
+    with_tail(
+        header,
+        TailIter {
+            len: iter.len(),
+            iter,
+            _marker: PhantomData,
+        },
+    )
+}
+
+unsafe impl<H, T, IH, IT> PinInit<HeaderSlice<H, T>> for WithTail<H, T, IH, IT>
+where
+    IH: PinInit<H>,
+    IT: PinInit<[T]>,
+{
+    fn layout(&self) -> Layout {
+        HeaderSlice::<H, T>::layout(&self.len)
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut HeaderSlice<H, T> {
+        HeaderSlice::<H, T>::dest(addr, &self.len)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut HeaderSlice<H, T>) {
+        let header = addr_of_mut!((*dest).header);
+        self.header.pin_initialize(header);
+        let guard = DropGuard::new(header);
+        self.tail.pin_initialize(addr_of_mut!((*dest).tail));
+        mem::forget(guard);
+    }
+}
+
+unsafe impl<H, T, IH, IT> Init<HeaderSlice<H, T>> for WithTail<H, T, IH, IT>
+where
+    IH: Init<H>,
+    IT: Init<[T]>,
+{
+    unsafe fn initialize(self, dest: *mut HeaderSlice<H, T>) {
+        let header = addr_of_mut!((*dest).header);
+        self.header.initialize(header);
+        let guard = DropGuard::new(header);
+        self.tail.initialize(addr_of_mut!((*dest).tail));
+        mem::forget(guard);
+    }
+}
+
+/// An initializer for a slice filled from an exact-size iterator.
+///
+/// Created by [`with_tail_iter`].
+pub struct TailIter<T, I> {
+    iter: I,
+    len: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+unsafe impl<T, I> PinInit<[T]> for TailIter<T, I>
+where
+    I: Iterator<Item = T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("tail length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut [T]) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, I> Init<[T]> for TailIter<T, I>
+where
+    I: Iterator<Item = T>,
+{
+    unsafe fn initialize(mut self, dest: *mut [T]) {
+        let mut guard = Prefix {
+            start: dest.cast::<T>(),
+            len: 0,
+        };
+        while guard.len < self.len {
+            let item = self
+                .iter
+                .next()
+                .expect("iterator yielded fewer items than its reported length");
+            guard.start.add(guard.len).write(item);
+            guard.len += 1;
+        }
+        assert!(
+            self.iter.next().is_none(),
+            "iterator yielded more items than its reported length"
+        );
+        mem::forget(guard);
+    }
+}
+
+/// A type whose layout and pointer metadata can be computed from a set of
+/// parameters, such as the length of a slice tail.
+///
+/// Implementing this for a custom unsized type allows it to be constructed
+/// with [`from_params`], and so to be hosted by any of the crate's
+/// containers.
+///
+/// # Safety
+///
+/// `layout` must return the layout of the value described by `params`, and
+/// `dest` must return a pointer to `addr` with the metadata for that value.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use core::alloc::Layout;
+/// use core::ptr::{self, addr_of_mut};
+/// use inplace::{dst, BoxExt};
+/// use inplace::dst::LayoutProvider;
+///
+/// #[repr(C)]
+/// struct Packet {
+///     kind: u8,
+///     checksum: u32,
+///     payload: [u8],
+/// }
+///
+/// unsafe impl LayoutProvider for Packet {
+///     type Params = usize;
+///
+///     fn layout(len: &usize) -> Layout {
+///         let prefix = Layout::new::<u8>().extend(Layout::new::<u32>()).unwrap().0;
+///         dst::tail_layout::<u8>(prefix, *len)
+///     }
+///
+///     fn dest(addr: *mut u8, len: &usize) -> *mut Packet {
+///         ptr::slice_from_raw_parts_mut(addr, *len) as *mut Packet
+///     }
+/// }
+///
+/// let packet: Box<Packet> = Box::emplace(unsafe {
+///     dst::from_params(3, |dest: *mut Packet| {
+///         addr_of_mut!((*dest).kind).write(1);
+///         addr_of_mut!((*dest).checksum).write(0xbeef);
+///         addr_of_mut!((*dest).payload).cast::<u8>().write_bytes(7, 3);
+///     })
+/// });
+/// assert_eq!(packet.checksum, 0xbeef);
+/// assert_eq!(&packet.payload, &[7, 7, 7]);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub unsafe trait LayoutProvider {
+    /// The parameters which determine the layout of a value.
+    type Params;
+
+    /// Returns the layout of a value with the given parameters.
+    fn layout(params: &Self::Params) -> Layout;
+
+    /// Returns a pointer to a value with the given parameters at `addr`.
+    fn dest(addr: *mut u8, params: &Self::Params) -> *mut Self;
+}
+
+unsafe impl<T> LayoutProvider for T {
+    type Params = ();
+
+    fn layout(_: &()) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(addr: *mut u8, _: &()) -> *mut T {
+        addr.cast()
+    }
+}
+
+unsafe impl<T> LayoutProvider for [T] {
+    type Params = usize;
+
+    fn layout(len: &usize) -> Layout {
+        Layout::array::<T>(*len).expect("slice length overflows layout")
+    }
+
+    fn dest(addr: *mut u8, len: &usize) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), *len)
+    }
+}
+
+unsafe impl LayoutProvider for str {
+    type Params = usize;
+
+    fn layout(len: &usize) -> Layout {
+        Layout::array::<u8>(*len).expect("string length overflows layout")
+    }
+
+    fn dest(addr: *mut u8, len: &usize) -> *mut str {
+        ptr::slice_from_raw_parts_mut(addr, *len) as *mut str
+    }
+}
+
+unsafe impl<H, T> LayoutProvider for HeaderSlice<H, T> {
+    type Params = usize;
+
+    fn layout(len: &usize) -> Layout {
+        tail_layout::<T>(Layout::new::<H>(), *len)
+    }
+
+    fn dest(addr: *mut u8, len: &usize) -> *mut HeaderSlice<H, T> {
+        ptr::slice_from_raw_parts_mut(addr.cast::<T>(), *len) as *mut HeaderSlice<H, T>
+    }
+}
+
+/// Returns the layout of a `#[repr(C)]` struct whose sized fields have the
+/// combined layout `prefix`, followed by a tail of `len` elements of `T`.
+pub fn tail_layout<T>(prefix: Layout, len: usize) -> Layout {
+    let tail = Layout::array::<T>(len).expect("tail length overflows layout");
+    prefix
+        .extend(tail)
+        .expect("tail length overflows layout")
+        .0
+        .pad_to_align()
+}
+
+/// An initializer for a [`LayoutProvider`] type.
+///
+/// Created by [`from_params`].
+pub struct FromParams<T: ?Sized + LayoutProvider, F> {
+    params: T::Params,
+    f: F,
+}
+
+/// Creates an initializer for a value of a [`LayoutProvider`] type, described
+/// by `params`, from a closure which initializes it through a raw pointer.
+///
+/// # Safety
+///
+/// As for [`init::from_raw`], `f` must fully initialize the value at the
+/// pointer it is given, which has the metadata described by `params`.
+///
+/// [`init::from_raw`]: crate::init::from_raw
+pub unsafe fn from_params<T, F>(params: T::Params, f: F) -> FromParams<T, F>
+where
+    T: ?Sized + LayoutProvider,
+    F: FnOnce(*mut T),
+{
+    FromParams { params, f }
+}
+
+unsafe impl<T, F> PinInit<T> for FromParams<T, F>
+where
+    T: ?Sized + LayoutProvider,
+    F: FnOnce(*mut T),
+{
+    fn layout(&self) -> Layout {
+        T::layout(&self.params)
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        T::dest(addr, &self.params)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        (self.f)(dest)
+    }
+}
+
+unsafe impl<T, F> Init<T> for FromParams<T, F>
+where
+    T: ?Sized + LayoutProvider,
+    F: FnOnce(*mut T),
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        (self.f)(dest)
+    }
+}
+
+/// An initializer for an unsized value described by its pointer metadata.
+///
+/// Created by [`from_metadata`].
+#[cfg(feature = "nightly")]
+pub struct FromMetadata<T: ?Sized, F> {
+    metadata: <T as core::ptr::Pointee>::Metadata,
+    f: F,
+}
+
+/// Creates an initializer for a value of any unsized type, described by its
+/// pointer metadata, from a closure which initializes it through a raw
+/// pointer.
+///
+/// The layout of the value is computed by the compiler from the metadata,
+/// so no [`LayoutProvider`] implementation is needed.
+///
+/// # Safety
+///
+/// `metadata` must be valid for `T`, describing a value whose size fits in
+/// `isize`, and `f` must fully initialize the value at the pointer it is
+/// given.
+///
+/// # Example
+///
+/// ```
+/// #![feature(ptr_metadata)]
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{dst, BoxExt};
+///
+/// let text: Box<str> = Box::emplace(unsafe {
+///     dst::from_metadata(3, |dest: *mut str| {
+///         dest.cast::<u8>().copy_from_nonoverlapping(b"abc".as_ptr(), 3)
+///     })
+/// });
+/// assert_eq!(&*text, "abc");
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "nightly")]
+pub unsafe fn from_metadata<T, F>(
+    metadata: <T as core::ptr::Pointee>::Metadata,
+    f: F,
+) -> FromMetadata<T, F>
+where
+    T: ?Sized,
+    F: FnOnce(*mut T),
+{
+    FromMetadata { metadata, f }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<T, F> PinInit<T> for FromMetadata<T, F>
+where
+    T: ?Sized,
+    F: FnOnce(*mut T),
+{
+    fn layout(&self) -> Layout {
+        unsafe {
+            Layout::for_value_raw(ptr::from_raw_parts_mut::<T>(
+                ptr::null_mut::<u8>(),
+                self.metadata,
+            ))
+        }
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        ptr::from_raw_parts_mut(addr, self.metadata)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        (self.f)(dest)
+    }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<T, F> Init<T> for FromMetadata<T, F>
+where
+    T: ?Sized,
+    F: FnOnce(*mut T),
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        (self.f)(dest)
+    }
+}
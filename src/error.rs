@@ -0,0 +1,67 @@
+//! Errors reported when emplacing a value.
+
+use core::alloc::Layout;
+use core::error::Error;
+use core::fmt;
+
+/// The ways in which emplacing a value can fail.
+///
+/// Allocation failure and initialization failure are kept distinct, since a
+/// caller may want to retry the first but not the second. When the
+/// initializer is infallible, `E` is [`Infallible`](core::convert::Infallible)
+/// and only allocation failure remains.
+///
+/// The allocating APIs in the crate have `try_` variants which report failure
+/// with this type. Those of `Rc` and `Arc` can only report allocation failure
+/// with the `nightly` feature, and the map entries of `EmplaceEntry` and the
+/// slabs of `SlabExt` have none, since those containers offer no fallible
+/// insertion.
+///
+/// Building with `--cfg inplace_no_global_oom_handling` removes every API
+/// which would abort on allocation failure, for targets whose `alloc` is
+/// built without a global OOM handler. What remains reports allocation
+/// failure with this type, so the APIs above which have no `try_` variant are
+/// removed entirely.
+///
+/// # Example
+///
+/// ```
+/// use core::alloc::Layout;
+/// use core::convert::Infallible;
+/// use inplace::InplaceError;
+///
+/// let error: InplaceError<Infallible> = InplaceError::Alloc(Layout::new::<[u64; 512]>());
+/// assert_eq!(error.to_string(), "failed to allocate 4096 bytes with alignment 8");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InplaceError<E> {
+    /// Space for the value could not be allocated. The initializer was dropped
+    /// without being run.
+    Alloc(Layout),
+    /// The initializer returned an error. Any space allocated for the value
+    /// has been released.
+    Init(E),
+}
+
+impl<E: fmt::Display> fmt::Display for InplaceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InplaceError::Alloc(layout) => write!(
+                f,
+                "failed to allocate {} bytes with alignment {}",
+                layout.size(),
+                layout.align()
+            ),
+            InplaceError::Init(error) => write!(f, "initializer failed: {error}"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for InplaceError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InplaceError::Alloc(_) => None,
+            InplaceError::Init(error) => Some(error),
+        }
+    }
+}
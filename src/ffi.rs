@@ -0,0 +1,136 @@
+//! Construction of NUL-terminated strings for C APIs.
+//!
+//! A [`CString`] is usually built by copying the bytes into a `Vec`, scanning
+//! them for interior NULs, and then pushing the terminator, which can
+//! reallocate. Here the bytes are scanned first, and then copied once, with
+//! the terminator, into a destination of exactly the right size: either a
+//! new allocation or a buffer provided by the caller.
+
+use core::alloc::Layout;
+use core::ffi::CStr;
+use core::mem::MaybeUninit;
+use core::ptr;
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use alloc::{boxed::Box, ffi::CString};
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use crate::boxed::BoxExt;
+use crate::init::{Init, PinInit};
+
+/// The error returned when bytes for a C string contain a NUL byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InteriorNul {
+    position: usize,
+}
+
+impl InteriorNul {
+    /// Returns the position of the first NUL byte.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// An initializer for the bytes of a C string, including its terminator.
+///
+/// Created by [`with_nul`].
+#[derive(Clone, Copy, Debug)]
+pub struct WithNul<'a> {
+    bytes: &'a [u8],
+}
+
+/// Checks that `bytes` contains no NUL byte, and creates an initializer which
+/// copies it into place followed by a NUL terminator.
+///
+/// # Example
+///
+/// ```
+/// use inplace::ffi;
+///
+/// assert!(ffi::with_nul(b"hello").is_ok());
+/// assert_eq!(ffi::with_nul(b"he\0llo").unwrap_err().position(), 2);
+/// ```
+pub fn with_nul(bytes: &[u8]) -> Result<WithNul<'_>, InteriorNul> {
+    match bytes.iter().position(|&byte| byte == 0) {
+        Some(position) => Err(InteriorNul { position }),
+        None => Ok(WithNul { bytes }),
+    }
+}
+
+impl WithNul<'_> {
+    /// Returns the length of the C string, including its terminator.
+    pub fn len_with_nul(&self) -> usize {
+        self.bytes.len() + 1
+    }
+}
+
+unsafe impl PinInit<[u8]> for WithNul<'_> {
+    fn layout(&self) -> Layout {
+        Layout::array::<u8>(self.len_with_nul()).expect("string length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [u8] {
+        ptr::slice_from_raw_parts_mut(addr, self.len_with_nul())
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut [u8]) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl Init<[u8]> for WithNul<'_> {
+    unsafe fn initialize(self, dest: *mut [u8]) {
+        let dest = dest.cast::<u8>();
+        ptr::copy_nonoverlapping(self.bytes.as_ptr(), dest, self.bytes.len());
+        dest.add(self.bytes.len()).write(0);
+    }
+}
+
+/// Writes a C string into the start of a caller-provided buffer.
+///
+/// Returns `None`, leaving the buffer untouched, if it is too small to hold
+/// the string and its terminator.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::ffi;
+///
+/// let mut buf = [MaybeUninit::uninit(); 16];
+/// let name = ffi::emplace_in(&mut buf, ffi::with_nul(b"eth0").unwrap()).unwrap();
+/// assert_eq!(name.to_bytes_with_nul(), b"eth0\0");
+/// assert!(ffi::emplace_in(&mut buf[..4], ffi::with_nul(b"eth0").unwrap()).is_none());
+/// ```
+pub fn emplace_in<'b>(buf: &'b mut [MaybeUninit<u8>], init: WithNul<'_>) -> Option<&'b CStr> {
+    let len = init.len_with_nul();
+    if buf.len() < len {
+        return None;
+    }
+    unsafe {
+        let dest = init.dest(buf.as_mut_ptr().cast());
+        init.initialize(dest);
+        Some(CStr::from_bytes_with_nul_unchecked(&*dest))
+    }
+}
+
+/// Allocates a [`CString`] of exactly the right size and writes the string
+/// into it.
+///
+/// The allocation is made once and handed to the `CString` without being
+/// copied or resized. Use [`CString::into_boxed_c_str`] to obtain a
+/// `Box<CStr>`.
+///
+/// # Example
+///
+/// ```
+/// use inplace::ffi;
+///
+/// let path = ffi::c_string(ffi::with_nul(b"/dev/null").unwrap());
+/// assert_eq!(path.as_bytes_with_nul(), b"/dev/null\0");
+/// ```
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+pub fn c_string(init: WithNul<'_>) -> CString {
+    let bytes: Box<[u8]> = Box::emplace(init);
+    unsafe { CString::from_vec_with_nul_unchecked(bytes.into_vec()) }
+}
@@ -0,0 +1,1198 @@
+//! Initializers: the library counterpart of `inplace T`.
+//!
+//! An initializer is a potential value. It can be queried for the layout of
+//! the value it will produce, and then asked to construct that value in place
+//! at a destination chosen later, typically by a container that has just
+//! allocated space for it.
+//!
+//! The same approach works for unsized values. The initializer knows the size
+//! of the value it will produce, so a consumer can allocate space for it, ask
+//! the initializer for the correctly typed destination pointer, and then
+//! initialize:
+//!
+//! ```
+//! use std::alloc::{alloc, dealloc};
+//! use inplace::{Init, PinInit};
+//!
+//! let source: &[u16] = &[1, 2, 3];
+//! let layout = PinInit::<[u16]>::layout(&source);
+//! unsafe {
+//!     let addr = alloc(layout);
+//!     assert!(!addr.is_null());
+//!     let dest = PinInit::<[u16]>::dest(&source, addr);
+//!     Init::<[u16]>::initialize(source, dest);
+//!     assert_eq!(&*dest, &[1, 2, 3]);
+//!     dealloc(addr, layout);
+//! }
+//! ```
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+
+mod chunked;
+pub mod combinators;
+mod fill;
+mod slice;
+mod transpose;
+
+pub use self::chunked::{chunked, Chunked};
+pub use self::combinators::{tuple, zip};
+pub use self::fill::{filled, zeroed, AnyBitPattern, Filled, Zeroable, Zeroed};
+pub use self::slice::{try_init_slice, ElementError, TryInitSlice};
+pub use self::transpose::{transpose_option, transpose_result, TransposeOption, TransposeResult};
+pub use crate::iter::from_iter;
+
+/// An initializer for a value of type `T` which may not be moved once built.
+///
+/// This is the weaker of the two initializer traits. The value it constructs
+/// is pinned from the moment it exists: the destination passed to
+/// [`pin_initialize`](PinInit::pin_initialize) must remain valid until the
+/// value is dropped. This makes it possible to construct `!Unpin` values, such
+/// as the self-referential `ListHead` from the sketch, directly at their final
+/// address. Pinned initializers for structs are most easily created with the
+/// [`pin_init!`](crate::pin_init!) macro.
+///
+/// Every [`Init`] is also a `PinInit`, since a value which may be moved can
+/// certainly be left where it is.
+///
+/// # Safety
+///
+/// Implementations must fully initialize the destination passed to
+/// [`pin_initialize`](PinInit::pin_initialize) unless they panic, in which
+/// case they must leave it uninitialized. The value written must have the
+/// layout returned by [`layout`](PinInit::layout).
+pub unsafe trait PinInit<T: ?Sized>: Sized {
+    /// Returns the layout of the value this initializer will construct.
+    ///
+    /// This corresponds to `Layout::for_inplace_value` in the sketch. For
+    /// unsized `T`, this is the layout of the complete value, such as a slice
+    /// of a particular length, or the concrete type behind a trait object.
+    fn layout(&self) -> Layout;
+
+    /// Returns a pointer to the value as it will be when constructed at
+    /// `addr`.
+    ///
+    /// For sized `T` this is just `addr` cast to `*mut T`. For unsized `T`, it
+    /// attaches the metadata the value will have, such as a slice length or a
+    /// vtable. Together with [`layout`](PinInit::layout), this allows a
+    /// consumer to allocate untyped memory and then obtain the destination
+    /// pointer to initialize.
+    fn dest(&self, addr: *mut u8) -> *mut T;
+
+    /// Constructs the value in place at `dest`, consuming the initializer.
+    ///
+    /// # Safety
+    ///
+    /// `dest` must be valid for writes of [`layout`](PinInit::layout) and
+    /// suitably aligned for it. Any value already at `dest` is overwritten
+    /// without being dropped. If this returns, the value at `dest` must not be
+    /// moved, and its memory must not be reused, until it has been dropped.
+    unsafe fn pin_initialize(self, dest: *mut T);
+}
+
+/// An initializer for a value of type `T`.
+///
+/// This is the library form of `inplace T`. The layout of the potential value
+/// is available up front through the [`PinInit`] supertrait, so that a
+/// consumer can find a suitable location for it, after which
+/// [`initialize`](Init::initialize) constructs the value there.
+///
+/// Any `T` is an initializer for itself, which makes `I: Init<T>` usable as an
+/// approximation of `?inplace T` in function signatures. Closures are wrapped
+/// with [`with`], since a blanket impl for them would overlap with the one for
+/// plain values. Because every initializer is also trivially an initializer
+/// of itself, calling these methods directly needs the target type spelled
+/// out, as in `PinInit::<T>::layout(&init)`.
+///
+/// The sketch requires that initializers never unwind. That cannot be enforced
+/// here, so an initializer which panics must instead leave the destination
+/// uninitialized, having dropped anything it had already written.
+///
+/// # Safety
+///
+/// Implementations must fully initialize the destination passed to
+/// [`initialize`](Init::initialize) unless they panic, and the value written
+/// must have the layout returned by [`layout`](PinInit::layout). The value
+/// must not depend on remaining at that address.
+///
+/// # Example
+///
+/// ```
+/// use core::alloc::Layout;
+/// use core::mem::MaybeUninit;
+/// use inplace::init::{self, Init, PinInit};
+///
+/// let init = init::with(|| [7u8; 64]);
+/// assert_eq!(PinInit::<[u8; 64]>::layout(&init), Layout::new::<[u8; 64]>());
+///
+/// let mut slot = MaybeUninit::<[u8; 64]>::uninit();
+/// unsafe { Init::<[u8; 64]>::initialize(init, slot.as_mut_ptr()) };
+/// assert_eq!(unsafe { slot.assume_init() }, [7; 64]);
+/// ```
+pub unsafe trait Init<T: ?Sized>: PinInit<T> {
+    /// Constructs the value in place at `dest`, consuming the initializer.
+    ///
+    /// # Safety
+    ///
+    /// `dest` must be valid for writes of [`layout`](PinInit::layout) and
+    /// suitably aligned for it. Any value already at `dest` is overwritten
+    /// without being dropped.
+    unsafe fn initialize(self, dest: *mut T);
+}
+
+/// A fallible initializer for a value of type `T`.
+///
+/// The sketch deliberately leaves fallible initialization out of the language
+/// proposal, but hypothesises a trait like this one. Where possible, errors
+/// should still be handled before an initializer is created, so that only the
+/// final construction is deferred; this trait covers the cases where that is
+/// not practical.
+///
+/// An [`Init<T>`] can be used where a `TryInit<T, E>` is expected by wrapping
+/// it with [`fallible`], and a `TryInit` can be made infallible by supplying a
+/// fallback with [`unwrap_or_else`]. A blanket impl in the first direction is
+/// not possible, since downstream crates could then create overlapping impls
+/// for the fallible initializer types defined here.
+///
+/// # Safety
+///
+/// Implementations must fully initialize the destination passed to
+/// [`try_initialize`](TryInit::try_initialize) if they return `Ok`, and leave
+/// it uninitialized if they return `Err` or panic. The value written must have
+/// the layout returned by [`layout`](TryInit::layout).
+pub unsafe trait TryInit<T: ?Sized, E>: Sized {
+    /// Returns the layout of the value this initializer will construct.
+    fn layout(&self) -> Layout;
+
+    /// Returns a pointer to the value as it will be when constructed at
+    /// `addr`, as for [`PinInit::dest`].
+    fn dest(&self, addr: *mut u8) -> *mut T;
+
+    /// Attempts to construct the value in place at `dest`, consuming the
+    /// initializer.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Init::initialize`]. If an error is returned, `dest` is left
+    /// uninitialized.
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E>;
+}
+
+// A value that already exists is simply moved into place.
+unsafe impl<T> PinInit<T> for T {
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T> Init<T> for T {
+    unsafe fn initialize(self, dest: *mut T) {
+        dest.write(self)
+    }
+}
+
+/// An initializer which calls a closure to produce the value.
+///
+/// Created by [`with`].
+pub struct With<T, F> {
+    f: F,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer which calls `f` to produce the value.
+///
+/// This is the closure analogy from the sketch: the value is not created until
+/// its destination is known. The closure's result is still returned through
+/// the stack, so this only defers construction rather than guaranteeing that
+/// no copy is made.
+pub fn with<T, F>(f: F) -> With<T, F>
+where
+    F: FnOnce() -> T,
+{
+    With {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+impl<T, F: Clone> Clone for With<T, F> {
+    fn clone(&self) -> Self {
+        With {
+            f: self.f.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+unsafe impl<T, F> PinInit<T> for With<T, F>
+where
+    F: FnOnce() -> T,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, F> Init<T> for With<T, F>
+where
+    F: FnOnce() -> T,
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        dest.write((self.f)())
+    }
+}
+
+/// An initializer defined by a closure which writes to a raw destination.
+///
+/// Created by [`from_raw`].
+pub struct FromRaw<T, F> {
+    f: F,
+    _marker: PhantomData<fn(*mut T)>,
+}
+
+/// Creates an initializer from a closure which writes the value to a pointer.
+///
+/// This is the most general way to construct an initializer, and the building
+/// block used by the macros in this crate. The closure can write the value
+/// piecewise, for instance one field at a time, so no complete `T` ever needs
+/// to exist anywhere except its destination.
+///
+/// # Safety
+///
+/// When called with a pointer valid for writes of `T`, `f` must fully
+/// initialize the pointee before returning. If `f` panics, it must leave the
+/// pointee uninitialized, dropping any parts it had already written.
+pub unsafe fn from_raw<T, F>(f: F) -> FromRaw<T, F>
+where
+    F: FnOnce(*mut T),
+{
+    FromRaw {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, F> PinInit<T> for FromRaw<T, F>
+where
+    F: FnOnce(*mut T),
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, F> Init<T> for FromRaw<T, F>
+where
+    F: FnOnce(*mut T),
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        (self.f)(dest)
+    }
+}
+
+/// A pinned initializer defined by a closure which writes to a raw
+/// destination.
+///
+/// Created by [`pin_from_raw`].
+pub struct PinFromRaw<T, F> {
+    f: F,
+    _marker: PhantomData<fn(*mut T)>,
+}
+
+/// Creates a pinned initializer from a closure which writes the value to a
+/// pointer.
+///
+/// This is the pinned form of [`from_raw`]. The closure may rely on the value
+/// never being moved once it returns, for instance by storing the destination
+/// address inside the value.
+///
+/// # Safety
+///
+/// As for [`from_raw`].
+pub unsafe fn pin_from_raw<T, F>(f: F) -> PinFromRaw<T, F>
+where
+    F: FnOnce(*mut T),
+{
+    PinFromRaw {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, F> PinInit<T> for PinFromRaw<T, F>
+where
+    F: FnOnce(*mut T),
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        (self.f)(dest)
+    }
+}
+
+/// A pinned initializer which is told its final address before it is built.
+///
+/// Created by [`with_inplace`].
+pub struct WithInplace<T, F> {
+    f: F,
+    _marker: PhantomData<fn(NonNull<T>)>,
+}
+
+/// Creates a pinned initializer from a closure which receives the final
+/// address of the value, and returns an initializer for it.
+///
+/// This is the library form of `&inplace` from the sketch. The closure is run
+/// once the destination is known, and can store its address in the value,
+/// such as in the `prev` and `next` pointers of an empty list head. The
+/// returned initializer is then run at that address. Since the value refers
+/// to its own location, the result is only a pinned initializer.
+///
+/// The address must not be dereferenced by the closure, as nothing has been
+/// written there yet.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use core::ptr::NonNull;
+/// use inplace::{init, BoxExt};
+///
+/// struct ListHead {
+///     prev: NonNull<ListHead>,
+///     next: NonNull<ListHead>,
+///     _pin: PhantomPinned,
+/// }
+///
+/// let head: Pin<Box<ListHead>> = Box::pin_emplace(init::with_inplace(|this| ListHead {
+///     prev: this,
+///     next: this,
+///     _pin: PhantomPinned,
+/// }));
+/// assert_eq!(head.next.as_ptr() as *const ListHead, &*head as *const ListHead);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn with_inplace<T, I, F>(f: F) -> WithInplace<T, F>
+where
+    F: FnOnce(NonNull<T>) -> I,
+    I: PinInit<T>,
+{
+    WithInplace {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I, F> PinInit<T> for WithInplace<T, F>
+where
+    F: FnOnce(NonNull<T>) -> I,
+    I: PinInit<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        let init = (self.f)(NonNull::new_unchecked(dest));
+        init.pin_initialize(dest)
+    }
+}
+
+/// An initializer defined by a closure which fills an uninitialized slot.
+///
+/// Created by [`from_fn`].
+pub struct FromFn<T, F> {
+    f: F,
+    _marker: PhantomData<fn(&mut MaybeUninit<T>)>,
+}
+
+/// Creates an initializer from a closure which fills an uninitialized slot.
+///
+/// This is a safe alternative to [`from_raw`]. The closure receives the
+/// destination as a `&mut MaybeUninit<T>`, and proves that it has initialized
+/// it by returning the `&mut T` obtained from it, for instance by
+/// [`MaybeUninit::write`]. Returning a reference to anything other than the
+/// slot is caught at runtime and results in a panic.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::init::{self, Init};
+///
+/// let init = init::from_fn(|slot: &mut MaybeUninit<(u32, [u8; 16])>| {
+///     slot.write((1, [2; 16]))
+/// });
+///
+/// let mut slot = MaybeUninit::uninit();
+/// unsafe { Init::<(u32, [u8; 16])>::initialize(init, slot.as_mut_ptr()) };
+/// assert_eq!(unsafe { slot.assume_init() }, (1, [2; 16]));
+/// ```
+pub const fn from_fn<T, F>(f: F) -> FromFn<T, F>
+where
+    F: for<'a> FnOnce(&'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    FromFn {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, F> PinInit<T> for FromFn<T, F>
+where
+    F: for<'a> FnOnce(&'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, F> Init<T> for FromFn<T, F>
+where
+    F: for<'a> FnOnce(&'a mut MaybeUninit<T>) -> &'a mut T,
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        let slot = &mut *dest.cast::<MaybeUninit<T>>();
+        let value: *mut T = (self.f)(slot);
+        assert!(
+            ptr::eq(value, dest),
+            "from_fn closure returned a reference to something other than its slot"
+        );
+    }
+}
+
+/// A fallible initializer defined by a closure which writes to a raw
+/// destination.
+///
+/// Created by [`try_from_raw`].
+pub struct TryFromRaw<T, E, F> {
+    f: F,
+    _marker: PhantomData<fn(*mut T) -> E>,
+}
+
+/// Creates a fallible initializer from a closure which writes the value to a
+/// pointer.
+///
+/// # Safety
+///
+/// When called with a pointer valid for writes of `T`, `f` must fully
+/// initialize the pointee if it returns `Ok`. If it returns `Err` or panics,
+/// it must leave the pointee uninitialized, dropping any parts it had already
+/// written.
+pub unsafe fn try_from_raw<T, E, F>(f: F) -> TryFromRaw<T, E, F>
+where
+    F: FnOnce(*mut T) -> Result<(), E>,
+{
+    TryFromRaw {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, E, F> TryInit<T, E> for TryFromRaw<T, E, F>
+where
+    F: FnOnce(*mut T) -> Result<(), E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E> {
+        (self.f)(dest)
+    }
+}
+
+/// A fallible initializer defined by a closure which fills an uninitialized
+/// slot.
+///
+/// Created by [`try_from_fn`].
+pub struct TryFromFn<T, E, F> {
+    f: F,
+    _marker: PhantomData<fn(&mut MaybeUninit<T>) -> E>,
+}
+
+/// Creates a fallible initializer from a closure which fills an uninitialized
+/// slot.
+///
+/// This is the fallible form of [`from_fn`]: on success the closure returns
+/// the `&mut T` obtained from its slot, and on failure it returns an error
+/// having left the slot uninitialized.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::init::{self, TryInit};
+///
+/// let init = init::try_from_fn(|slot: &mut MaybeUninit<u32>| {
+///     let value = "42".parse()?;
+///     Ok::<_, core::num::ParseIntError>(slot.write(value))
+/// });
+///
+/// let mut slot = MaybeUninit::uninit();
+/// unsafe { init.try_initialize(slot.as_mut_ptr()) }.unwrap();
+/// assert_eq!(unsafe { slot.assume_init() }, 42);
+/// ```
+pub fn try_from_fn<T, E, F>(f: F) -> TryFromFn<T, E, F>
+where
+    F: for<'a> FnOnce(&'a mut MaybeUninit<T>) -> Result<&'a mut T, E>,
+{
+    TryFromFn {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, E, F> TryInit<T, E> for TryFromFn<T, E, F>
+where
+    F: for<'a> FnOnce(&'a mut MaybeUninit<T>) -> Result<&'a mut T, E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E> {
+        let slot = &mut *dest.cast::<MaybeUninit<T>>();
+        let value: *mut T = (self.f)(slot)?;
+        assert!(
+            ptr::eq(value, dest),
+            "try_from_fn closure returned a reference to something other than its slot"
+        );
+        Ok(())
+    }
+}
+
+/// A fallible initializer which always succeeds.
+///
+/// Created by [`fallible`].
+pub struct Fallible<T: ?Sized, E, I> {
+    init: I,
+    _marker: PhantomData<fn(*mut T) -> E>,
+}
+
+/// Converts an infallible initializer into a fallible one which never fails.
+pub fn fallible<T: ?Sized, E, I>(init: I) -> Fallible<T, E, I>
+where
+    I: Init<T>,
+{
+    Fallible {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T: ?Sized, E, I> TryInit<T, E> for Fallible<T, E, I>
+where
+    I: Init<T>,
+{
+    fn layout(&self) -> Layout {
+        self.init.layout()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        self.init.dest(addr)
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E> {
+        self.init.initialize(dest);
+        Ok(())
+    }
+}
+
+/// An infallible initializer which falls back to a closure on error.
+///
+/// Created by [`unwrap_or_else`].
+pub struct UnwrapOrElse<T, E, I, F> {
+    init: I,
+    f: F,
+    _marker: PhantomData<fn(E) -> T>,
+}
+
+/// Converts a fallible initializer into an infallible one.
+///
+/// If `init` fails, its error is passed to `f`, and the value returned by `f`
+/// is written to the destination instead. This is the "error handling within
+/// the deferred initializer which still produces a valid value" allowed by the
+/// sketch.
+pub fn unwrap_or_else<T, E, I, F>(init: I, f: F) -> UnwrapOrElse<T, E, I, F>
+where
+    I: TryInit<T, E>,
+    F: FnOnce(E) -> T,
+{
+    UnwrapOrElse {
+        init,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, E, I, F> PinInit<T> for UnwrapOrElse<T, E, I, F>
+where
+    I: TryInit<T, E>,
+    F: FnOnce(E) -> T,
+{
+    fn layout(&self) -> Layout {
+        self.init.layout()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        self.init.dest(addr)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, E, I, F> Init<T> for UnwrapOrElse<T, E, I, F>
+where
+    I: TryInit<T, E>,
+    F: FnOnce(E) -> T,
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        if let Err(error) = self.init.try_initialize(dest) {
+            dest.write((self.f)(error))
+        }
+    }
+}
+
+/// An initializer for an unsized value, produced by unsizing the value of an
+/// initializer for a sized type.
+///
+/// Created by [`unsize`], usually through the [`unsize!`](crate::unsize!)
+/// macro.
+pub struct Unsize<T, U: ?Sized, I> {
+    init: I,
+    coerce: fn(*mut T) -> *mut U,
+}
+
+/// Creates an initializer for the unsized type `U` from an initializer for
+/// `T`, using `coerce` to convert pointers to the destination.
+///
+/// The value is constructed with the layout of `T`, and the pointer returned
+/// by `coerce` carries the metadata, such as a vtable, needed to use it as a
+/// `U`. This allows, for instance, a `Box<dyn Trait>` to be emplaced without
+/// naming the concrete type.
+///
+/// # Safety
+///
+/// `coerce` must perform an unsizing coercion of its argument. The
+/// [`unsize!`](crate::unsize!) macro provides a safe interface which ensures
+/// this.
+pub unsafe fn unsize<T, U: ?Sized, I>(init: I, coerce: fn(*mut T) -> *mut U) -> Unsize<T, U, I>
+where
+    I: PinInit<T>,
+{
+    Unsize { init, coerce }
+}
+
+/// Creates an initializer for an unsized type from an initializer for a
+/// sized type which coerces to it.
+///
+/// `unsize!(init => U)` wraps `init` with [`init::unsize`], using an
+/// unsizing coercion to `U`. Conversions other than coercions are rejected
+/// at compile time.
+///
+/// The sized type is inferred from the initializer. The adapters in this
+/// module are also initializers of their own type, so when one is passed
+/// directly the sized type must be given, as `unsize!(init => U, from T)`.
+/// Initializers returned as `impl Init<T>` need no annotation.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::fmt::Display;
+/// use inplace::{init, unsize, BoxExt, Init};
+///
+/// fn answer() -> impl Init<u64> {
+///     init::with(|| 42)
+/// }
+///
+/// let items: [Box<dyn Display>; 2] = [
+///     Box::emplace(unsize!(answer() => dyn Display)),
+///     Box::emplace(unsize!(init::with(|| '!') => dyn Display, from char)),
+/// ];
+/// assert_eq!(items[0].to_string() + &items[1].to_string(), "42!");
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+///
+/// [`init::unsize`]: crate::init::unsize
+#[macro_export]
+macro_rules! unsize {
+    // The initializer is evaluated outside the `unsafe` block, so that it
+    // cannot use unsafe operations without its own.
+    ($init:expr => $ty:ty, from $src:ty) => {{
+        let init = $init;
+        unsafe { $crate::init::unsize(init, |ptr: *mut $src| -> *mut $ty { ptr }) }
+    }};
+    ($init:expr => $ty:ty) => {{
+        let init = $init;
+        unsafe { $crate::init::unsize(init, |ptr| -> *mut $ty { ptr }) }
+    }};
+}
+
+unsafe impl<T, U: ?Sized, I> PinInit<U> for Unsize<T, U, I>
+where
+    I: PinInit<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut U {
+        (self.coerce)(addr.cast())
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut U) {
+        self.init.pin_initialize(dest.cast())
+    }
+}
+
+unsafe impl<T, U: ?Sized, I> Init<U> for Unsize<T, U, I>
+where
+    I: Init<T>,
+{
+    unsafe fn initialize(self, dest: *mut U) {
+        self.init.initialize(dest.cast())
+    }
+}
+
+/// Creates an initializer for a slice from an initializer for an array.
+///
+/// This lets producers of fixed-size arrays feed consumers of slices, such
+/// as `Box<[T]>` or a `DstArray<[T]>`, with the array still constructed in
+/// place.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::containers::dst_array::DstArray;
+/// use inplace::init;
+///
+/// let mut rows: DstArray<[u32]> = DstArray::new();
+/// rows.push(init::unsize_array(init::with(|| [1; 4])));
+/// rows.push(&[2, 3][..]);
+/// assert_eq!(rows[0].len(), 4);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn unsize_array<T, I, const N: usize>(init: I) -> Unsize<[T; N], [T], I>
+where
+    I: PinInit<[T; N]>,
+{
+    unsafe { unsize(init, |ptr| ptr) }
+}
+
+/// An initializer for an array which constructs each element in place.
+///
+/// Created by [`array_from_fn`].
+pub struct ArrayFromFn<T, F, const N: usize> {
+    f: F,
+    _marker: PhantomData<fn() -> [T; N]>,
+}
+
+/// Creates an initializer for an array of `N` elements, where element `i` is
+/// constructed in place by the initializer `f(i)`.
+///
+/// This is the in-place counterpart of [`core::array::from_fn`]: each element
+/// is constructed directly in the destination array, rather than the array
+/// being assembled on the stack. If an element's initializer panics, or with
+/// [`TryInit`] fails, the elements already constructed are dropped in place.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, BoxExt};
+///
+/// let planes: Box<[[u16; 4096]; 3]> =
+///     Box::emplace(init::array_from_fn(|i| init::with(move || [i as u16; 4096])));
+/// assert_eq!(planes[2][4095], 2);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn array_from_fn<T, I, F, const N: usize>(f: F) -> ArrayFromFn<T, F, N>
+where
+    F: FnMut(usize) -> I,
+{
+    ArrayFromFn {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I, F, const N: usize> PinInit<[T; N]> for ArrayFromFn<T, F, N>
+where
+    F: FnMut(usize) -> I,
+    I: PinInit<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<[T; N]>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T; N] {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(mut self, dest: *mut [T; N]) {
+        let mut guard = Prefix {
+            start: dest.cast::<T>(),
+            len: 0,
+        };
+        while guard.len < N {
+            (self.f)(guard.len).pin_initialize(guard.start.add(guard.len));
+            guard.len += 1;
+        }
+        mem::forget(guard);
+    }
+}
+
+unsafe impl<T, I, F, const N: usize> Init<[T; N]> for ArrayFromFn<T, F, N>
+where
+    F: FnMut(usize) -> I,
+    I: Init<T>,
+{
+    unsafe fn initialize(mut self, dest: *mut [T; N]) {
+        let mut guard = Prefix {
+            start: dest.cast::<T>(),
+            len: 0,
+        };
+        while guard.len < N {
+            (self.f)(guard.len).initialize(guard.start.add(guard.len));
+            guard.len += 1;
+        }
+        mem::forget(guard);
+    }
+}
+
+unsafe impl<T, E, I, F, const N: usize> TryInit<[T; N], E> for ArrayFromFn<T, F, N>
+where
+    F: FnMut(usize) -> I,
+    I: TryInit<T, E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<[T; N]>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T; N] {
+        addr.cast()
+    }
+
+    unsafe fn try_initialize(mut self, dest: *mut [T; N]) -> Result<(), E> {
+        let mut guard = Prefix {
+            start: dest.cast::<T>(),
+            len: 0,
+        };
+        while guard.len < N {
+            (self.f)(guard.len).try_initialize(guard.start.add(guard.len))?;
+            guard.len += 1;
+        }
+        mem::forget(guard);
+        Ok(())
+    }
+}
+
+/// An initializer which can construct any number of values, each in its own
+/// destination.
+///
+/// This is implemented for every initializer which is `Init` and `Clone`,
+/// including plain `Clone` values, by constructing each value from a clone.
+///
+/// # Safety
+///
+/// Implementations must fully initialize the destination passed to
+/// [`initialize_one`](RepeatInit::initialize_one) unless they panic, in which
+/// case they must leave it uninitialized.
+pub unsafe trait RepeatInit<T> {
+    /// Constructs one value in place at `dest`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Init::initialize`].
+    unsafe fn initialize_one(&mut self, dest: *mut T);
+}
+
+unsafe impl<T, I> RepeatInit<T> for I
+where
+    I: Init<T> + Clone,
+{
+    unsafe fn initialize_one(&mut self, dest: *mut T) {
+        self.clone().initialize(dest)
+    }
+}
+
+/// An initializer for a slice which constructs every element from the same
+/// template.
+///
+/// Created by [`repeat`].
+pub struct Repeat<T, I> {
+    init: I,
+    len: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer for a slice of `len` elements, each constructed in
+/// place by `init`.
+///
+/// This is the library form of an `inplace [value; len]` expression whose
+/// length is only known at run time. If constructing an element panics, the
+/// elements already constructed are dropped in place.
+///
+/// Elements which need no dropping, such as `Copy` values, are written
+/// without tracking how many have been constructed, since there is nothing
+/// to clean up if one panics.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::containers::dst_array::DstArray;
+/// use inplace::{init, BoxExt};
+///
+/// let mut arr: DstArray<[u32]> = DstArray::new();
+/// arr.push(init::repeat(1u32, 1_000_000));
+/// assert_eq!(arr[0].len(), 1_000_000);
+///
+/// let rows = Box::<[[u8; 4096]]>::emplace(init::repeat(init::with(|| [7; 4096]), 16));
+/// assert_eq!(rows[15][4095], 7);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn repeat<T, I>(init: I, len: usize) -> Repeat<T, I>
+where
+    I: RepeatInit<T>,
+{
+    Repeat {
+        init,
+        len,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I> PinInit<[T]> for Repeat<T, I>
+where
+    I: RepeatInit<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut [T]) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, I> Init<[T]> for Repeat<T, I>
+where
+    I: RepeatInit<T>,
+{
+    unsafe fn initialize(mut self, dest: *mut [T]) {
+        let start = dest.cast::<T>();
+        if !mem::needs_drop::<T>() {
+            for i in 0..self.len {
+                self.init.initialize_one(start.add(i));
+            }
+            return;
+        }
+        let mut guard = Prefix { start, len: 0 };
+        while guard.len < self.len {
+            self.init.initialize_one(guard.start.add(guard.len));
+            guard.len += 1;
+        }
+        mem::forget(guard);
+    }
+}
+
+/// Constructs a value at an untyped address, checking it against the
+/// initializer's layout.
+///
+/// This is for allocator and arena authors, who have an address and a size
+/// from their own bookkeeping. The address is checked against the alignment
+/// of the layout, and `size` against its size, before anything is written.
+/// Returns a pointer to the constructed value.
+///
+/// # Panics
+///
+/// Panics if `addr` is not suitably aligned, or if `size` is smaller than
+/// the size of the layout. The initializer is not run.
+///
+/// # Safety
+///
+/// `addr` must be valid for writes of `size` bytes, and its memory must not
+/// be in use by any other value.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use core::ptr::NonNull;
+/// use inplace::init;
+///
+/// let mut region = [MaybeUninit::<u64>::uninit(); 8];
+/// let addr = NonNull::new(region.as_mut_ptr().cast::<u8>()).unwrap();
+/// let value = unsafe { init::emplace_at::<[u32; 4], _>(addr, 64, [1, 2, 3, 4]) };
+/// assert_eq!(unsafe { value.as_ref() }, &[1, 2, 3, 4]);
+/// ```
+pub unsafe fn emplace_at<T: ?Sized, I>(addr: NonNull<u8>, size: usize, init: I) -> NonNull<T>
+where
+    I: Init<T>,
+{
+    let layout = init.layout();
+    assert!(
+        (addr.as_ptr() as usize).is_multiple_of(layout.align()),
+        "address is not aligned for the initializer's layout"
+    );
+    assert!(
+        size >= layout.size(),
+        "region of {size} bytes is too small for the initializer's layout of {} bytes",
+        layout.size()
+    );
+    let dest = init.dest(addr.as_ptr());
+    init.initialize(dest);
+    NonNull::new_unchecked(dest)
+}
+
+/// Constructs a value on the stack, for containers which only accept
+/// complete values.
+pub(crate) fn construct<T, I: Init<T>>(init: I) -> T {
+    let mut value = MaybeUninit::<T>::uninit();
+    unsafe {
+        init.initialize(value.as_mut_ptr());
+        value.assume_init()
+    }
+}
+
+/// Attempts to construct a value on the stack, for containers which only
+/// accept complete values.
+pub(crate) fn try_construct<T, E, I: TryInit<T, E>>(init: I) -> Result<T, E> {
+    let mut value = MaybeUninit::<T>::uninit();
+    unsafe {
+        init.try_initialize(value.as_mut_ptr())?;
+        Ok(value.assume_init())
+    }
+}
+
+/// Drops the initialized prefix of a slice if initialization panics.
+pub(crate) struct Prefix<T> {
+    pub(crate) start: *mut T,
+    pub(crate) len: usize,
+}
+
+impl<T> Drop for Prefix<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::slice_from_raw_parts_mut(self.start, self.len).drop_in_place() }
+    }
+}
+
+// Slices of `Copy` elements, and string slices, are copied into place. This
+// allows unsized values to be emplaced from borrowed data.
+unsafe impl<T: Copy> PinInit<[T]> for &[T] {
+    fn layout(&self) -> Layout {
+        Layout::for_value(*self)
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len())
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut [T]) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T: Copy> Init<[T]> for &[T] {
+    unsafe fn initialize(self, dest: *mut [T]) {
+        ptr::copy_nonoverlapping(self.as_ptr(), dest.cast(), self.len())
+    }
+}
+
+unsafe impl PinInit<str> for &str {
+    fn layout(&self) -> Layout {
+        Layout::for_value(*self)
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut str {
+        ptr::slice_from_raw_parts_mut(addr, self.len()) as *mut str
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut str) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl Init<str> for &str {
+    unsafe fn initialize(self, dest: *mut str) {
+        ptr::copy_nonoverlapping(self.as_ptr(), dest.cast(), self.len())
+    }
+}
@@ -0,0 +1,161 @@
+//! Initializers which report progress while filling a slice.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+use crate::init::{Init, PinInit, Prefix, TryInit};
+
+/// An initializer for a slice which is constructed in chunks, reporting
+/// progress after each one.
+///
+/// Created by [`chunked`].
+pub struct Chunked<T, F, P> {
+    len: usize,
+    chunk_len: usize,
+    f: F,
+    progress: P,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer for a slice of `len` elements, where element `i` is
+/// constructed in place by the initializer `f(i)`, calling `progress` after
+/// each chunk of `chunk_len` elements.
+///
+/// `progress` is passed the number of elements constructed so far and the
+/// total, and the last call, after a chunk which may be shorter, reports the
+/// slice complete. A fill which takes seconds can then update a progress bar,
+/// log, or yield to other work as it goes.
+///
+/// If `progress` returns `()`, this is an [`Init`]. If it returns a `Result`,
+/// this is a [`TryInit`], and an error cancels the fill: the elements already
+/// constructed are dropped and the error is returned.
+///
+/// # Panics
+///
+/// Panics if `chunk_len` is zero.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, BoxExt, InplaceError};
+///
+/// let mut reports = Vec::new();
+/// let buf: Box<[[u8; 4096]]> = Box::emplace(init::chunked(
+///     1024,
+///     256,
+///     |_| init::zeroed(),
+///     |done, total| reports.push(done * 100 / total),
+/// ));
+/// assert_eq!(buf.len(), 1024);
+/// assert_eq!(reports, [25, 50, 75, 100]);
+///
+/// let cancelled = Box::<[[u8; 4096]]>::try_emplace(init::chunked(
+///     1024,
+///     256,
+///     |_| init::zeroed(),
+///     |done, _| if done < 512 { Ok(()) } else { Err("cancelled") },
+/// ));
+/// assert_eq!(cancelled.unwrap_err(), InplaceError::Init("cancelled"));
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn chunked<T, I, F, P>(len: usize, chunk_len: usize, f: F, progress: P) -> Chunked<T, F, P>
+where
+    F: FnMut(usize) -> I,
+    I: Init<T>,
+{
+    assert!(chunk_len != 0, "chunk length must be nonzero");
+    Chunked {
+        len,
+        chunk_len,
+        f,
+        progress,
+        _marker: PhantomData,
+    }
+}
+
+impl<T, F, P> Chunked<T, F, P> {
+    /// Constructs every element, calling `report` after each chunk and
+    /// stopping at its first error.
+    unsafe fn fill<I, E>(
+        mut self,
+        dest: *mut [T],
+        mut report: impl FnMut(&mut P, usize, usize) -> Result<(), E>,
+    ) -> Result<(), E>
+    where
+        F: FnMut(usize) -> I,
+        I: Init<T>,
+    {
+        let mut guard = Prefix {
+            start: dest.cast::<T>(),
+            len: 0,
+        };
+        while guard.len < self.len {
+            let end = self.len.min(guard.len.saturating_add(self.chunk_len));
+            while guard.len < end {
+                (self.f)(guard.len).initialize(guard.start.add(guard.len));
+                guard.len += 1;
+            }
+            report(&mut self.progress, guard.len, self.len)?;
+        }
+        mem::forget(guard);
+        Ok(())
+    }
+}
+
+unsafe impl<T, I, F, P> PinInit<[T]> for Chunked<T, F, P>
+where
+    F: FnMut(usize) -> I,
+    I: Init<T>,
+    P: FnMut(usize, usize),
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut [T]) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, I, F, P> Init<[T]> for Chunked<T, F, P>
+where
+    F: FnMut(usize) -> I,
+    I: Init<T>,
+    P: FnMut(usize, usize),
+{
+    unsafe fn initialize(self, dest: *mut [T]) {
+        let _ = self.fill::<I, ()>(dest, |progress, done, total| {
+            progress(done, total);
+            Ok(())
+        });
+    }
+}
+
+unsafe impl<T, E, I, F, P> TryInit<[T], E> for Chunked<T, F, P>
+where
+    F: FnMut(usize) -> I,
+    I: Init<T>,
+    P: FnMut(usize, usize) -> Result<(), E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn try_initialize(self, dest: *mut [T]) -> Result<(), E> {
+        self.fill(dest, |progress, done, total| progress(done, total))
+    }
+}
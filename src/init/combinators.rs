@@ -0,0 +1,474 @@
+//! Adapters for building initializers out of other initializers.
+//!
+//! Apart from [`map`], which must pass the value through the stack, these
+//! adapters operate on the value at its destination, so they can be used to
+//! compose large values without intermediate copies.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::pin::Pin;
+use core::ptr;
+
+use crate::__private::DropGuard;
+use crate::init::{Init, PinInit, TryInit};
+
+/// An initializer which converts the value produced by another.
+///
+/// Created by [`map`].
+pub struct Map<T, U, I, F> {
+    init: I,
+    f: F,
+    _marker: PhantomData<fn(T) -> U>,
+}
+
+/// Creates an initializer which runs `init`, then converts its value with `f`.
+///
+/// The intermediate `T` is constructed on the stack and moved into `f`, so
+/// this is only suitable when `T` is small. To modify a value in place, use
+/// [`chain`] instead.
+pub fn map<T, U, I, F>(init: I, f: F) -> Map<T, U, I, F>
+where
+    I: Init<T>,
+    F: FnOnce(T) -> U,
+{
+    Map {
+        init,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, U, I, F> PinInit<U> for Map<T, U, I, F>
+where
+    I: Init<T>,
+    F: FnOnce(T) -> U,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<U>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut U {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut U) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, U, I, F> Init<U> for Map<T, U, I, F>
+where
+    I: Init<T>,
+    F: FnOnce(T) -> U,
+{
+    unsafe fn initialize(self, dest: *mut U) {
+        let mut value = MaybeUninit::<T>::uninit();
+        self.init.initialize(value.as_mut_ptr());
+        dest.write((self.f)(value.assume_init()))
+    }
+}
+
+/// An initializer which modifies the value produced by another in place.
+///
+/// Created by [`chain`].
+pub struct Chain<T: ?Sized, I, F> {
+    init: I,
+    f: F,
+    _marker: PhantomData<fn(&mut T)>,
+}
+
+/// Creates an initializer which runs `init`, then calls `f` on the value at
+/// its destination.
+///
+/// If `f` panics, the value is dropped in place.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::init::{self, combinators, Init};
+///
+/// let init = combinators::chain(init::with(|| [0u8; 1024]), |buf: &mut [u8; 1024]| {
+///     buf[..5].copy_from_slice(b"hello");
+/// });
+///
+/// let mut slot = MaybeUninit::<[u8; 1024]>::uninit();
+/// unsafe { Init::<[u8; 1024]>::initialize(init, slot.as_mut_ptr()) };
+/// assert_eq!(&unsafe { slot.assume_init() }[..5], b"hello");
+/// ```
+pub fn chain<T: ?Sized, I, F>(init: I, f: F) -> Chain<T, I, F>
+where
+    I: Init<T>,
+    F: FnOnce(&mut T),
+{
+    Chain {
+        init,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T: ?Sized, I, F> PinInit<T> for Chain<T, I, F>
+where
+    I: Init<T>,
+    F: FnOnce(&mut T),
+{
+    fn layout(&self) -> Layout {
+        self.init.layout()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        self.init.dest(addr)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T: ?Sized, I, F> Init<T> for Chain<T, I, F>
+where
+    I: Init<T>,
+    F: FnOnce(&mut T),
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        self.init.initialize(dest);
+        let guard = DropGuard::new(dest);
+        (self.f)(&mut *dest);
+        mem::forget(guard);
+    }
+}
+
+/// A pinned initializer which modifies the value produced by another in place.
+///
+/// Created by [`pin_chain`].
+pub struct PinChain<T: ?Sized, I, F> {
+    init: I,
+    f: F,
+    _marker: PhantomData<fn(Pin<&mut T>)>,
+}
+
+/// Creates a pinned initializer which runs `init`, then calls `f` on the
+/// pinned value at its destination.
+///
+/// If `f` panics, the value is dropped in place.
+pub fn pin_chain<T: ?Sized, I, F>(init: I, f: F) -> PinChain<T, I, F>
+where
+    I: PinInit<T>,
+    F: FnOnce(Pin<&mut T>),
+{
+    PinChain {
+        init,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T: ?Sized, I, F> PinInit<T> for PinChain<T, I, F>
+where
+    I: PinInit<T>,
+    F: FnOnce(Pin<&mut T>),
+{
+    fn layout(&self) -> Layout {
+        self.init.layout()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        self.init.dest(addr)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.init.pin_initialize(dest);
+        let guard = DropGuard::new(dest);
+        (self.f)(Pin::new_unchecked(&mut *dest));
+        mem::forget(guard);
+    }
+}
+
+/// A fallible initializer which runs a fallible step on the value produced by
+/// another.
+///
+/// Created by [`and_then`].
+pub struct AndThen<T: ?Sized, E, I, F> {
+    init: I,
+    f: F,
+    _marker: PhantomData<fn(&mut T) -> E>,
+}
+
+/// Creates a fallible initializer which runs `init`, then calls `f` on the
+/// value at its destination.
+///
+/// If `f` returns an error or panics, the value is dropped in place, leaving
+/// the destination uninitialized.
+pub fn and_then<T: ?Sized, E, I, F>(init: I, f: F) -> AndThen<T, E, I, F>
+where
+    I: TryInit<T, E>,
+    F: FnOnce(&mut T) -> Result<(), E>,
+{
+    AndThen {
+        init,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T: ?Sized, E, I, F> TryInit<T, E> for AndThen<T, E, I, F>
+where
+    I: TryInit<T, E>,
+    F: FnOnce(&mut T) -> Result<(), E>,
+{
+    fn layout(&self) -> Layout {
+        self.init.layout()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        self.init.dest(addr)
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E> {
+        self.init.try_initialize(dest)?;
+        let guard = DropGuard::new(dest);
+        (self.f)(&mut *dest)?;
+        mem::forget(guard);
+        Ok(())
+    }
+}
+
+/// A fallible initializer which converts the error returned by another.
+///
+/// Created by [`map_err`].
+pub struct MapErr<T: ?Sized, E, I, F> {
+    init: I,
+    f: F,
+    _marker: PhantomData<fn(*mut T) -> E>,
+}
+
+/// Creates a fallible initializer which runs `init`, converting any error it
+/// returns with `f`.
+pub fn map_err<T: ?Sized, E, E2, I, F>(init: I, f: F) -> MapErr<T, E, I, F>
+where
+    I: TryInit<T, E>,
+    F: FnOnce(E) -> E2,
+{
+    MapErr {
+        init,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T: ?Sized, E, E2, I, F> TryInit<T, E2> for MapErr<T, E, I, F>
+where
+    I: TryInit<T, E>,
+    F: FnOnce(E) -> E2,
+{
+    fn layout(&self) -> Layout {
+        self.init.layout()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        self.init.dest(addr)
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E2> {
+        self.init.try_initialize(dest).map_err(self.f)
+    }
+}
+
+/// An initializer for a pair, built from initializers for each element.
+///
+/// Created by [`zip`].
+pub struct Zip<A, B, IA, IB> {
+    a: IA,
+    b: IB,
+    _marker: PhantomData<fn(*mut (A, B))>,
+}
+
+/// Creates an initializer for a pair which constructs each element in place
+/// at its offset within the destination.
+///
+/// If the second initializer panics, the first element is dropped in place.
+/// The result is a pinned initializer if either input is.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::init::{self, combinators, Init};
+///
+/// let init = combinators::zip(1u32, init::with(|| [2u8; 4096]));
+///
+/// let mut slot = MaybeUninit::<(u32, [u8; 4096])>::uninit();
+/// unsafe { Init::<(u32, [u8; 4096])>::initialize(init, slot.as_mut_ptr()) };
+/// let (a, b) = unsafe { slot.assume_init() };
+/// assert_eq!((a, b[4095]), (1, 2));
+/// ```
+pub fn zip<A, B, IA, IB>(a: IA, b: IB) -> Zip<A, B, IA, IB>
+where
+    IA: PinInit<A>,
+    IB: PinInit<B>,
+{
+    Zip {
+        a,
+        b,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<A, B, IA, IB> PinInit<(A, B)> for Zip<A, B, IA, IB>
+where
+    IA: PinInit<A>,
+    IB: PinInit<B>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<(A, B)>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut (A, B) {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut (A, B)) {
+        let a = ptr::addr_of_mut!((*dest).0);
+        self.a.pin_initialize(a);
+        let guard = DropGuard::new(a);
+        self.b.pin_initialize(ptr::addr_of_mut!((*dest).1));
+        mem::forget(guard);
+    }
+}
+
+unsafe impl<A, B, IA, IB> Init<(A, B)> for Zip<A, B, IA, IB>
+where
+    IA: Init<A>,
+    IB: Init<B>,
+{
+    unsafe fn initialize(self, dest: *mut (A, B)) {
+        let a = ptr::addr_of_mut!((*dest).0);
+        self.a.initialize(a);
+        let guard = DropGuard::new(a);
+        self.b.initialize(ptr::addr_of_mut!((*dest).1));
+        mem::forget(guard);
+    }
+}
+
+/// An initializer for a tuple, built from a tuple of initializers for its
+/// elements.
+///
+/// Created by [`tuple`].
+pub struct Tuple<T, I> {
+    inits: I,
+    _marker: PhantomData<fn(*mut T)>,
+}
+
+/// Creates an initializer for a tuple of up to eight elements from a tuple
+/// of initializers, constructing each element in place at its offset within
+/// the destination.
+///
+/// This is the library form of the sketch's rule that a struct is
+/// initialized in place when each of its fields is. A tuple of initializers
+/// cannot itself be an initializer for the tuple of their values, since
+/// every tuple is already an initializer for itself, so it is wrapped by
+/// this function instead. [`zip`] is the same for pairs.
+///
+/// The elements are constructed in order. If one panics or fails, those
+/// already constructed are dropped in place. The result is a pinned or
+/// fallible initializer if every input is.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, BoxExt};
+///
+/// let record: Box<(u32, [u8; 4096], String)> = Box::emplace(init::tuple((
+///     7,
+///     init::zeroed(),
+///     init::with(|| String::from("name")),
+/// )));
+/// assert_eq!((record.0, record.1[4095], record.2.as_str()), (7, 0, "name"));
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn tuple<T, I>(inits: I) -> Tuple<T, I> {
+    Tuple {
+        inits,
+        _marker: PhantomData,
+    }
+}
+
+macro_rules! tuple_init {
+    ($($T:ident $I:ident $index:tt),+) => {
+        unsafe impl<$($T, $I),+> PinInit<($($T,)+)> for Tuple<($($T,)+), ($($I,)+)>
+        where
+            $($I: PinInit<$T>),+
+        {
+            fn layout(&self) -> Layout {
+                Layout::new::<($($T,)+)>()
+            }
+
+            fn dest(&self, addr: *mut u8) -> *mut ($($T,)+) {
+                addr.cast()
+            }
+
+            unsafe fn pin_initialize(self, dest: *mut ($($T,)+)) {
+                // If an element panics, the guards of those before it are
+                // dropped, in reverse order, with the rest of the tuple.
+                let guards = ($({
+                    let element = ptr::addr_of_mut!((*dest).$index);
+                    self.inits.$index.pin_initialize(element);
+                    DropGuard::new(element)
+                },)+);
+                mem::forget(guards);
+            }
+        }
+
+        unsafe impl<$($T, $I),+> Init<($($T,)+)> for Tuple<($($T,)+), ($($I,)+)>
+        where
+            $($I: Init<$T>),+
+        {
+            unsafe fn initialize(self, dest: *mut ($($T,)+)) {
+                let guards = ($({
+                    let element = ptr::addr_of_mut!((*dest).$index);
+                    self.inits.$index.initialize(element);
+                    DropGuard::new(element)
+                },)+);
+                mem::forget(guards);
+            }
+        }
+
+        unsafe impl<E, $($T, $I),+> TryInit<($($T,)+), E> for Tuple<($($T,)+), ($($I,)+)>
+        where
+            $($I: TryInit<$T, E>),+
+        {
+            fn layout(&self) -> Layout {
+                Layout::new::<($($T,)+)>()
+            }
+
+            fn dest(&self, addr: *mut u8) -> *mut ($($T,)+) {
+                addr.cast()
+            }
+
+            unsafe fn try_initialize(self, dest: *mut ($($T,)+)) -> Result<(), E> {
+                let guards = ($({
+                    let element = ptr::addr_of_mut!((*dest).$index);
+                    self.inits.$index.try_initialize(element)?;
+                    DropGuard::new(element)
+                },)+);
+                mem::forget(guards);
+                Ok(())
+            }
+        }
+    };
+}
+
+tuple_init!(A IA 0);
+tuple_init!(A IA 0, B IB 1);
+tuple_init!(A IA 0, B IB 1, C IC 2);
+tuple_init!(A IA 0, B IB 1, C IC 2, D ID 3);
+tuple_init!(A IA 0, B IB 1, C IC 2, D ID 3, E2 IE 4);
+tuple_init!(A IA 0, B IB 1, C IC 2, D ID 3, E2 IE 4, F IF 5);
+tuple_init!(A IA 0, B IB 1, C IC 2, D ID 3, E2 IE 4, F IF 5, G IG 6);
+tuple_init!(A IA 0, B IB 1, C IC 2, D ID 3, E2 IE 4, F IF 5, G IG 6, H IH 7);
@@ -0,0 +1,222 @@
+//! Initializers which fill the destination with a byte.
+
+use core::alloc::Layout;
+use core::cell::{Cell, UnsafeCell};
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::num::{self, Wrapping};
+use core::ptr::NonNull;
+use core::sync::atomic;
+
+use crate::init::{Init, PinInit};
+
+/// Types for which a value of all zero bytes is valid.
+///
+/// # Safety
+///
+/// Every field of the type must be valid when all of its bytes are zero.
+/// Padding bytes may be zeroed too.
+pub unsafe trait Zeroable {}
+
+/// Types for which every byte pattern is a valid value.
+///
+/// # Safety
+///
+/// Every field of the type must be valid whatever the values of its bytes,
+/// so it may not, for instance, contain a `bool`, a reference or an enum.
+pub unsafe trait AnyBitPattern: Zeroable {}
+
+macro_rules! impl_fill {
+    ($trait:ident: $($(#[$attr:meta])* $ty:ty),* $(,)?) => {
+        $($(#[$attr])* unsafe impl $trait for $ty {})*
+    };
+}
+
+impl_fill!(Zeroable: (), bool, char);
+impl_fill!(Zeroable: u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+impl_fill!(AnyBitPattern: (), u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+impl_fill!(
+    Zeroable:
+    Option<num::NonZeroU8>,
+    Option<num::NonZeroU16>,
+    Option<num::NonZeroU32>,
+    Option<num::NonZeroU64>,
+    Option<num::NonZeroU128>,
+    Option<num::NonZeroUsize>,
+    Option<num::NonZeroI8>,
+    Option<num::NonZeroI16>,
+    Option<num::NonZeroI32>,
+    Option<num::NonZeroI64>,
+    Option<num::NonZeroI128>,
+    Option<num::NonZeroIsize>,
+);
+impl_fill!(
+    Zeroable:
+    #[cfg(target_has_atomic = "8")] atomic::AtomicBool,
+    #[cfg(target_has_atomic = "8")] atomic::AtomicU8,
+    #[cfg(target_has_atomic = "16")] atomic::AtomicU16,
+    #[cfg(target_has_atomic = "32")] atomic::AtomicU32,
+    #[cfg(target_has_atomic = "64")] atomic::AtomicU64,
+    #[cfg(target_has_atomic = "ptr")] atomic::AtomicUsize,
+    #[cfg(target_has_atomic = "8")] atomic::AtomicI8,
+    #[cfg(target_has_atomic = "16")] atomic::AtomicI16,
+    #[cfg(target_has_atomic = "32")] atomic::AtomicI32,
+    #[cfg(target_has_atomic = "64")] atomic::AtomicI64,
+    #[cfg(target_has_atomic = "ptr")] atomic::AtomicIsize,
+);
+impl_fill!(
+    AnyBitPattern:
+    #[cfg(target_has_atomic = "8")] atomic::AtomicU8,
+    #[cfg(target_has_atomic = "16")] atomic::AtomicU16,
+    #[cfg(target_has_atomic = "32")] atomic::AtomicU32,
+    #[cfg(target_has_atomic = "64")] atomic::AtomicU64,
+    #[cfg(target_has_atomic = "ptr")] atomic::AtomicUsize,
+    #[cfg(target_has_atomic = "8")] atomic::AtomicI8,
+    #[cfg(target_has_atomic = "16")] atomic::AtomicI16,
+    #[cfg(target_has_atomic = "32")] atomic::AtomicI32,
+    #[cfg(target_has_atomic = "64")] atomic::AtomicI64,
+    #[cfg(target_has_atomic = "ptr")] atomic::AtomicIsize,
+);
+
+unsafe impl<T> Zeroable for *const T {}
+unsafe impl<T> Zeroable for *mut T {}
+unsafe impl<T> Zeroable for Option<NonNull<T>> {}
+unsafe impl<T> Zeroable for Option<&T> {}
+unsafe impl<T> Zeroable for Option<&mut T> {}
+#[cfg(target_has_atomic = "ptr")]
+unsafe impl<T> Zeroable for atomic::AtomicPtr<T> {}
+unsafe impl<T: ?Sized> Zeroable for PhantomData<T> {}
+unsafe impl<T: ?Sized> AnyBitPattern for PhantomData<T> {}
+unsafe impl<T> Zeroable for MaybeUninit<T> {}
+unsafe impl<T> AnyBitPattern for MaybeUninit<T> {}
+unsafe impl<T: Zeroable, const N: usize> Zeroable for [T; N] {}
+unsafe impl<T: AnyBitPattern, const N: usize> AnyBitPattern for [T; N] {}
+unsafe impl<T: Zeroable> Zeroable for Wrapping<T> {}
+unsafe impl<T: AnyBitPattern> AnyBitPattern for Wrapping<T> {}
+unsafe impl<T: Zeroable> Zeroable for ManuallyDrop<T> {}
+unsafe impl<T: AnyBitPattern> AnyBitPattern for ManuallyDrop<T> {}
+unsafe impl<T: Zeroable> Zeroable for Cell<T> {}
+unsafe impl<T: AnyBitPattern> AnyBitPattern for Cell<T> {}
+unsafe impl<T: Zeroable> Zeroable for UnsafeCell<T> {}
+unsafe impl<T: AnyBitPattern> AnyBitPattern for UnsafeCell<T> {}
+
+/// An initializer which sets every byte of the value to zero.
+///
+/// Created by [`zeroed`].
+pub struct Zeroed<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer which sets every byte of the value to zero.
+///
+/// The value is written with a single `memset`, however large it is, rather
+/// than element by element.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, BoxExt};
+///
+/// let counts: Box<[u64; 1 << 20]> = Box::emplace(init::zeroed());
+/// assert!(counts.iter().all(|&n| n == 0));
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub const fn zeroed<T: Zeroable>() -> Zeroed<T> {
+    Zeroed {
+        _marker: PhantomData,
+    }
+}
+
+impl<T> Clone for Zeroed<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Zeroed<T> {}
+
+unsafe impl<T: Zeroable> PinInit<T> for Zeroed<T> {
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T: Zeroable> Init<T> for Zeroed<T> {
+    unsafe fn initialize(self, dest: *mut T) {
+        dest.write_bytes(0, 1)
+    }
+}
+
+/// An initializer which sets every byte of the value to the same byte.
+///
+/// Created by [`filled`].
+pub struct Filled<T> {
+    byte: u8,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer which sets every byte of the value to `byte`.
+///
+/// As with [`zeroed`], the value is written with a single `memset`. Each
+/// element of an integer array holds `byte` in every one of its bytes.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, BoxExt};
+///
+/// let mask: Box<[u32; 1 << 20]> = Box::emplace(init::filled(0xff));
+/// assert!(mask.iter().all(|&word| word == u32::MAX));
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub const fn filled<T: AnyBitPattern>(byte: u8) -> Filled<T> {
+    Filled {
+        byte,
+        _marker: PhantomData,
+    }
+}
+
+impl<T> Clone for Filled<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Filled<T> {}
+
+unsafe impl<T: AnyBitPattern> PinInit<T> for Filled<T> {
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T: AnyBitPattern> Init<T> for Filled<T> {
+    unsafe fn initialize(self, dest: *mut T) {
+        dest.cast::<u8>()
+            .write_bytes(self.byte, mem::size_of::<T>())
+    }
+}
@@ -0,0 +1,127 @@
+//! Slices whose elements are constructed by fallible initializers.
+
+use core::alloc::Layout;
+use core::error::Error;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+use crate::init::{Prefix, TryInit};
+
+/// The error returned when the initializer of one element of a slice fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ElementError<E> {
+    error: E,
+    completed: usize,
+}
+
+impl<E> ElementError<E> {
+    /// Returns the error of the element's initializer.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the error of the element's initializer, discarding the count.
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    /// Returns the number of elements which were constructed before the
+    /// failure, which is also the index of the element which failed.
+    ///
+    /// Those elements have been dropped by the time the error is returned.
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ElementError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "element {} failed: {}", self.completed, self.error)
+    }
+}
+
+impl<E: Error + 'static> Error for ElementError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An initializer for a slice whose elements are constructed by fallible
+/// initializers.
+///
+/// Created by [`try_init_slice`].
+pub struct TryInitSlice<T, F> {
+    len: usize,
+    f: F,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer for a slice of `len` elements, where element `i` is
+/// constructed in place by the fallible initializer `f(i)`.
+///
+/// Construction stops at the first element whose initializer fails. The
+/// elements before it are dropped in place, and the error is returned as an
+/// [`ElementError`] which also records how many were completed, so the caller
+/// can tell where the input went wrong.
+///
+/// # Example
+///
+/// ```
+/// use inplace::{init, BoxExt, InplaceError};
+///
+/// let fields = ["4", "8", "15", "sixteen", "23"];
+/// let parse = |i: usize| fields[i].parse::<u32>();
+///
+/// let parsed: Box<[u32]> = Box::try_emplace(init::try_init_slice(3, parse)).unwrap();
+/// assert_eq!(*parsed, [4, 8, 15]);
+///
+/// let parsed = Box::<[u32]>::try_emplace(init::try_init_slice(fields.len(), parse));
+/// let Err(InplaceError::Init(error)) = parsed else { panic!() };
+/// assert_eq!(error.completed(), 3);
+/// ```
+pub fn try_init_slice<T, E, I, F>(len: usize, f: F) -> TryInitSlice<T, F>
+where
+    F: FnMut(usize) -> I,
+    I: TryInit<T, E>,
+{
+    TryInitSlice {
+        len,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, E, I, F> TryInit<[T], ElementError<E>> for TryInitSlice<T, F>
+where
+    F: FnMut(usize) -> I,
+    I: TryInit<T, E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn try_initialize(mut self, dest: *mut [T]) -> Result<(), ElementError<E>> {
+        let mut guard = Prefix {
+            start: dest.cast::<T>(),
+            len: 0,
+        };
+        while guard.len < self.len {
+            let init = (self.f)(guard.len);
+            if let Err(error) = init.try_initialize(guard.start.add(guard.len)) {
+                return Err(ElementError {
+                    error,
+                    completed: guard.len,
+                });
+            }
+            guard.len += 1;
+        }
+        mem::forget(guard);
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Conversions between initializers and `Option` or `Result`.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::init::{construct, Init, PinInit, TryInit};
+
+/// Returns true if a `U` wrapping a `T` is represented by the `T` itself,
+/// with the other variant stored in a niche of `T`.
+///
+/// A wrapper of the same size as `T` must store its `T` verbatim at offset
+/// zero, and can then only tell its variants apart by an invalid value of
+/// `T`, so writing any valid `T` at the start of it selects the variant
+/// holding the `T`. This holds as long as the other variant has no data.
+fn niche<T, U>() -> bool {
+    mem::size_of::<U>() == mem::size_of::<T>()
+}
+
+/// An initializer for an `Option` which is `None`, or constructs the value
+/// of `Some`.
+///
+/// Created by [`transpose_option`].
+pub struct TransposeOption<T, I> {
+    init: Option<I>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Converts an optional initializer into an initializer for an `Option`.
+///
+/// `None` is written as it is. For `Some`, the value is constructed directly
+/// in the destination when `Option<T>` stores its discriminant in a niche of
+/// `T`, as it does for references, boxes, `NonZero` integers, and structs or
+/// arrays containing them. The discriminant is then implied by the value.
+/// Otherwise the discriminant is stored separately, at a position which the
+/// language leaves unspecified, so the value is constructed on the stack and
+/// moved into the `Option`.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, BoxExt};
+///
+/// // The reference gives `Option` a niche, so the array is built in place.
+/// let entry = Some(init::tuple(("name", init::filled(1))));
+/// let slot: Box<Option<(&str, [u8; 4096])>> = Box::emplace(init::transpose_option(entry));
+/// assert_eq!(slot.unwrap().1[4095], 1);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn transpose_option<T, I>(init: Option<I>) -> TransposeOption<T, I>
+where
+    I: Init<T>,
+{
+    TransposeOption {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I> PinInit<Option<T>> for TransposeOption<T, I>
+where
+    I: Init<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<Option<T>>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut Option<T> {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut Option<T>) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, I> Init<Option<T>> for TransposeOption<T, I>
+where
+    I: Init<T>,
+{
+    unsafe fn initialize(self, dest: *mut Option<T>) {
+        match self.init {
+            None => dest.write(None),
+            Some(init) if niche::<T, Option<T>>() => init.initialize(dest.cast()),
+            Some(init) => dest.write(Some(construct(init))),
+        }
+    }
+}
+
+/// An initializer for a `Result` which is an error, or constructs the value
+/// of `Ok`.
+///
+/// Created by [`transpose_result`].
+pub struct TransposeResult<T, E, I> {
+    init: Result<I, E>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Converts a result holding an initializer into an initializer for a
+/// `Result`.
+///
+/// `Err` is written as it is. For `Ok`, the value is constructed directly in
+/// the destination when the error type has no data and `Result<T, E>` stores
+/// its discriminant in a niche of `T`, as for [`transpose_option`], and
+/// otherwise on the stack.
+///
+/// To construct the value in place whatever its type, and pass the error to
+/// the caller instead of storing it, use the `Result` itself as a
+/// [`TryInit`].
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, BoxExt, InplaceError};
+///
+/// fn frame(len: usize) -> Result<init::Zeroed<[u8; 4096]>, &'static str> {
+///     if len <= 4096 { Ok(init::zeroed()) } else { Err("frame too long") }
+/// }
+///
+/// let stored: Box<Result<[u8; 4096], &str>> = Box::emplace(init::transpose_result(frame(9000)));
+/// assert_eq!(*stored, Err("frame too long"));
+///
+/// let returned = Box::<[u8; 4096]>::try_emplace(frame(9000));
+/// assert_eq!(returned.unwrap_err(), InplaceError::Init("frame too long"));
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn transpose_result<T, E, I>(init: Result<I, E>) -> TransposeResult<T, E, I>
+where
+    I: Init<T>,
+{
+    TransposeResult {
+        init,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, E, I> PinInit<Result<T, E>> for TransposeResult<T, E, I>
+where
+    I: Init<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<Result<T, E>>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut Result<T, E> {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut Result<T, E>) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, E, I> Init<Result<T, E>> for TransposeResult<T, E, I>
+where
+    I: Init<T>,
+{
+    unsafe fn initialize(self, dest: *mut Result<T, E>) {
+        match self.init {
+            Err(error) => dest.write(Err(error)),
+            Ok(init) if mem::size_of::<E>() == 0 && niche::<T, Result<T, E>>() => {
+                init.initialize(dest.cast())
+            }
+            Ok(init) => dest.write(Ok(construct(init))),
+        }
+    }
+}
+
+// A result holding an initializer fails with its error before anything is
+// written, or constructs the value in place. This lets an error found while
+// preparing an initializer be reported by `try_emplace` with the rest.
+unsafe impl<T, E, I> TryInit<T, E> for Result<I, E>
+where
+    I: Init<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::new::<T>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn try_initialize(self, dest: *mut T) -> Result<(), E> {
+        self?.initialize(dest);
+        Ok(())
+    }
+}
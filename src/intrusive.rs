@@ -0,0 +1,9 @@
+//! Intrusive data structures built from pinned initializers.
+//!
+//! The nodes of an intrusive structure embed the links which connect them,
+//! and those links point at each other, so nodes must never move while they
+//! are linked. Pinned initializers allow such nodes to be constructed directly
+//! at their final address.
+
+pub mod list;
+pub mod wait_list;
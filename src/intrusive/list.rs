@@ -0,0 +1,317 @@
+//! A pinned intrusive doubly-linked list.
+//!
+//! This is a working form of the self-referential `ListHead` from the sketch.
+//! The list is circular, in the style of the Linux kernel's `list_head`: the
+//! [`ListHead`] contains a sentinel [`Links`] whose `prev` and `next` point at
+//! itself when the list is empty, and each node embeds a `Links` of its own.
+//! Both kinds of `Links` are built with pinned initializers, since they point
+//! at their own final address.
+//!
+//! A list borrows its nodes for its lifetime `'a`, so nodes cannot be dropped
+//! while the list could still reach them. When the list is dropped, any nodes
+//! still in it are unlinked.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(not(inplace_no_global_oom_handling))]
+//! # fn main() {
+//! use core::mem::offset_of;
+//! use core::pin::Pin;
+//! use inplace::intrusive::list::{Linked, Links, ListHead};
+//! use inplace::{pin_init, BoxExt, PinInit};
+//!
+//! #[derive(PinInit)]
+//! struct Task {
+//!     id: u32,
+//!     #[pin]
+//!     links: Links,
+//! }
+//!
+//! unsafe impl Linked for Task {
+//!     const LINKS_OFFSET: usize = offset_of!(Task, links);
+//! }
+//!
+//! fn task(id: u32) -> impl PinInit<Task> {
+//!     pin_init!(Task {
+//!         id,
+//!         links <- Links::new(),
+//!     })
+//! }
+//!
+//! let tasks: Vec<Pin<Box<Task>>> = (0..3).map(|id| Box::pin_emplace(task(id))).collect();
+//! let addrs: Vec<*const Task> = tasks.iter().map(|task| &**task as *const Task).collect();
+//!
+//! let list: Pin<Box<ListHead<Task>>> = Box::pin_emplace(ListHead::new());
+//! for task in &tasks {
+//!     list.as_ref().push_back(task.as_ref());
+//! }
+//! assert_eq!(list.iter().map(|task| task.id).collect::<Vec<_>>(), [0, 1, 2]);
+//!
+//! // The list refers to the nodes where they were constructed.
+//! let linked: Vec<*const Task> = list.iter().map(|task| &*task as *const Task).collect();
+//! assert_eq!(linked, addrs);
+//!
+//! assert!(list.as_ref().remove(tasks[1].as_ref()));
+//! assert!(!tasks[1].links.is_linked());
+//! assert_eq!(list.iter().map(|task| task.id).collect::<Vec<_>>(), [0, 2]);
+//! # }
+//! # #[cfg(inplace_no_global_oom_handling)]
+//! # fn main() {}
+//! ```
+
+use core::cell::Cell;
+use core::marker::{PhantomData, PhantomPinned};
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+
+use crate::init::{self, PinInit};
+use crate::pin::{OnlyCallFromDrop, PinnedDrop};
+
+/// The links embedded in each node of a list, and in the list's head.
+///
+/// An unlinked `Links` points at itself, so it must be constructed in place
+/// with [`Links::new`].
+pub struct Links {
+    prev: Cell<*const Links>,
+    next: Cell<*const Links>,
+    /// The sentinel of the list this node is in, or null if unlinked.
+    list: Cell<*const Links>,
+    _pin: PhantomPinned,
+}
+
+impl Links {
+    /// Creates an initializer for unlinked links, which point at their own
+    /// final address.
+    pub fn new() -> impl PinInit<Links> {
+        init::with_inplace(|this: NonNull<Links>| Links {
+            prev: Cell::new(this.as_ptr()),
+            next: Cell::new(this.as_ptr()),
+            list: Cell::new(ptr::null()),
+            _pin: PhantomPinned,
+        })
+    }
+
+    /// Returns true if the node containing these links is in a list.
+    pub fn is_linked(&self) -> bool {
+        !self.list.get().is_null()
+    }
+
+    /// Links `self` between two adjacent links.
+    unsafe fn insert(&self, prev: *const Links, next: *const Links, list: *const Links) {
+        self.prev.set(prev);
+        self.next.set(next);
+        self.list.set(list);
+        (*prev).next.set(self);
+        (*next).prev.set(self);
+    }
+
+    /// Unlinks `self`, leaving it pointing at itself.
+    unsafe fn unlink(&self) {
+        let prev = self.prev.get();
+        let next = self.next.get();
+        (*prev).next.set(next);
+        (*next).prev.set(prev);
+        self.prev.set(self);
+        self.next.set(self);
+        self.list.set(ptr::null());
+    }
+}
+
+/// Types which can be nodes of a list, by embedding [`Links`].
+///
+/// # Safety
+///
+/// `LINKS_OFFSET` must be the offset of a field of type `Links` within
+/// `Self`, such as given by [`offset_of!`](core::mem::offset_of). That field
+/// must be structurally pinned, and must not be used by any other list type.
+pub unsafe trait Linked {
+    /// The offset of the embedded `Links` within `Self`.
+    const LINKS_OFFSET: usize;
+}
+
+fn links<T: Linked>(node: &T) -> &Links {
+    unsafe { &*(node as *const T).byte_add(T::LINKS_OFFSET).cast() }
+}
+
+/// Returns the node containing the given links.
+///
+/// # Safety
+///
+/// `links` must be the links embedded in a live `T`.
+unsafe fn node<'a, T: Linked>(links: *const Links) -> Pin<&'a T> {
+    Pin::new_unchecked(&*links.byte_sub(T::LINKS_OFFSET).cast())
+}
+
+/// The head of an intrusive doubly-linked list of `T`s.
+///
+/// The head must be pinned before nodes can be added, so it is constructed
+/// with a pinned initializer from [`ListHead::new`].
+#[crate::pin_data]
+#[pinned_drop]
+pub struct ListHead<'a, T: Linked> {
+    #[pin]
+    head: Links,
+    // Invariant in `'a`, so that a list of longer-lived nodes cannot be
+    // treated as one that accepts shorter-lived nodes.
+    _marker: PhantomData<Cell<&'a T>>,
+}
+
+impl<'a, T: Linked> ListHead<'a, T> {
+    /// Creates an initializer for an empty list.
+    pub fn new() -> impl PinInit<Self> {
+        crate::pin_init!(ListHead {
+            head <- Links::new(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns true if the list contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        ptr::eq(self.head.next.get(), &self.head)
+    }
+
+    /// Returns the number of nodes in the list.
+    ///
+    /// This walks the list, so takes time proportional to its length.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns true if the node is in this list.
+    pub fn contains(&self, node: &T) -> bool {
+        ptr::eq(links(node).list.get(), &self.head)
+    }
+
+    /// Adds a node at the front of the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is already in a list.
+    pub fn push_front(self: Pin<&Self>, node: Pin<&'a T>) {
+        let head = &self.head;
+        let links = links(&*node);
+        assert!(!links.is_linked(), "node is already in a list");
+        unsafe { links.insert(head, head.next.get(), head) }
+    }
+
+    /// Adds a node at the back of the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is already in a list.
+    pub fn push_back(self: Pin<&Self>, node: Pin<&'a T>) {
+        let head = &self.head;
+        let links = links(&*node);
+        assert!(!links.is_linked(), "node is already in a list");
+        unsafe { links.insert(head.prev.get(), head, head) }
+    }
+
+    /// Returns the node at the front of the list.
+    pub fn front(&self) -> Option<Pin<&'a T>> {
+        self.iter().next()
+    }
+
+    /// Returns the node at the back of the list.
+    pub fn back(&self) -> Option<Pin<&'a T>> {
+        self.iter().next_back()
+    }
+
+    /// Removes and returns the node at the front of the list.
+    pub fn pop_front(self: Pin<&Self>) -> Option<Pin<&'a T>> {
+        let node = self.front()?;
+        unsafe { links(&*node).unlink() };
+        Some(node)
+    }
+
+    /// Removes and returns the node at the back of the list.
+    pub fn pop_back(self: Pin<&Self>) -> Option<Pin<&'a T>> {
+        let node = self.back()?;
+        unsafe { links(&*node).unlink() };
+        Some(node)
+    }
+
+    /// Removes a node from the list.
+    ///
+    /// Returns false, leaving the node unchanged, if it is not in this list.
+    pub fn remove(self: Pin<&Self>, node: Pin<&T>) -> bool {
+        if !self.contains(&node) {
+            return false;
+        }
+        unsafe { links(&*node).unlink() };
+        true
+    }
+
+    /// Returns an iterator over the nodes, from front to back.
+    pub fn iter(&self) -> Iter<'_, 'a, T> {
+        Iter {
+            front: self.head.next.get(),
+            back: self.head.prev.get(),
+            head: &self.head,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Linked> PinnedDrop for ListHead<'_, T> {
+    fn drop(self: Pin<&mut Self>, _: OnlyCallFromDrop) {
+        while !self.is_empty() {
+            unsafe { (*self.head.next.get()).unlink() }
+        }
+    }
+}
+
+impl<'l, 'a, T: Linked> IntoIterator for &'l ListHead<'a, T> {
+    type Item = Pin<&'a T>;
+    type IntoIter = Iter<'l, 'a, T>;
+
+    fn into_iter(self) -> Iter<'l, 'a, T> {
+        self.iter()
+    }
+}
+
+/// An iterator over the nodes of a [`ListHead`].
+///
+/// Created by [`ListHead::iter`].
+pub struct Iter<'l, 'a, T: Linked> {
+    front: *const Links,
+    back: *const Links,
+    head: &'l Links,
+    _marker: PhantomData<&'l ListHead<'a, T>>,
+}
+
+impl<'a, T: Linked> Iterator for Iter<'_, 'a, T> {
+    type Item = Pin<&'a T>;
+
+    fn next(&mut self) -> Option<Pin<&'a T>> {
+        let links = self.front;
+        // Stop if the list has changed so that the next node was removed.
+        if ptr::eq(links, self.head) || !ptr::eq(unsafe { (*links).list.get() }, self.head) {
+            return None;
+        }
+        if ptr::eq(links, self.back) {
+            self.front = self.head;
+            self.back = self.head;
+        } else {
+            self.front = unsafe { (*links).next.get() };
+        }
+        Some(unsafe { node(links) })
+    }
+}
+
+impl<'a, T: Linked> DoubleEndedIterator for Iter<'_, 'a, T> {
+    fn next_back(&mut self) -> Option<Pin<&'a T>> {
+        let links = self.back;
+        // Stop if the list has changed so that the next node was removed.
+        if ptr::eq(links, self.head) || !ptr::eq(unsafe { (*links).list.get() }, self.head) {
+            return None;
+        }
+        if ptr::eq(links, self.front) {
+            self.front = self.head;
+            self.back = self.head;
+        } else {
+            self.back = unsafe { (*links).prev.get() };
+        }
+        Some(unsafe { node(links) })
+    }
+}
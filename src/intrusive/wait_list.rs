@@ -0,0 +1,193 @@
+//! A pinned list of tasks waiting for an event.
+//!
+//! A [`Waiter`] is embedded in the structure of whoever is waiting, such as a
+//! future, and is registered with a [`WaitList`] along with the waker to call.
+//! Neither allocates: the waiter is linked into the list where it was
+//! constructed, which is only sound because it is built in place and pinned.
+//! This is the pattern used for wait queues in kernels and for the
+//! registration of wakers in async runtimes.
+//!
+//! As with [`ListHead`], the list borrows its waiters, so they must outlive
+//! it. The list uses `Cell`s, so is not `Sync`. Across threads, it would be
+//! protected by a lock, as kernel wait queues are.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(not(inplace_no_global_oom_handling))]
+//! # fn main() {
+//! use core::future::Future;
+//! use core::pin::Pin;
+//! use core::task::{Context, Poll, Waker};
+//! use inplace::intrusive::wait_list::{WaitList, Waiter};
+//! use inplace::BoxExt;
+//!
+//! /// A future which completes once its waiter is woken.
+//! struct Wait<'l, 'a> {
+//!     list: Pin<&'l WaitList<'a>>,
+//!     waiter: Pin<&'a Waiter>,
+//! }
+//!
+//! impl Future for Wait<'_, '_> {
+//!     type Output = ();
+//!
+//!     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+//!         if self.waiter.is_woken() {
+//!             return Poll::Ready(());
+//!         }
+//!         self.list.register(self.waiter, cx.waker());
+//!         Poll::Pending
+//!     }
+//! }
+//!
+//! let waiter: Pin<Box<Waiter>> = Box::pin_emplace(Waiter::new());
+//! let list: Pin<Box<WaitList>> = Box::pin_emplace(WaitList::new());
+//! let mut wait = Wait {
+//!     list: list.as_ref(),
+//!     waiter: waiter.as_ref(),
+//! };
+//!
+//! let mut cx = Context::from_waker(Waker::noop());
+//! assert!(Pin::new(&mut wait).poll(&mut cx).is_pending());
+//! assert_eq!(list.len(), 1);
+//!
+//! assert_eq!(list.as_ref().wake_all(), 1);
+//! assert!(Pin::new(&mut wait).poll(&mut cx).is_ready());
+//! # }
+//! # #[cfg(inplace_no_global_oom_handling)]
+//! # fn main() {}
+//! ```
+
+use core::cell::Cell;
+use core::mem::offset_of;
+use core::pin::Pin;
+use core::task::Waker;
+
+use crate::init::PinInit;
+use crate::intrusive::list::{Linked, Links, ListHead};
+
+/// A registration for a task waiting on a [`WaitList`].
+#[crate::pin_data]
+pub struct Waiter {
+    #[pin]
+    links: Links,
+    waker: Cell<Option<Waker>>,
+    woken: Cell<bool>,
+}
+
+unsafe impl Linked for Waiter {
+    const LINKS_OFFSET: usize = offset_of!(Waiter, links);
+}
+
+impl Waiter {
+    /// Creates an initializer for an unregistered waiter.
+    pub fn new() -> impl PinInit<Waiter> {
+        crate::pin_init!(Waiter {
+            links <- Links::new(),
+            waker: Cell::new(None),
+            woken: Cell::new(false),
+        })
+    }
+
+    /// Returns true if the waiter is registered and has not yet been woken.
+    pub fn is_registered(&self) -> bool {
+        self.links.is_linked()
+    }
+
+    /// Returns true if the waiter has been woken since it was last
+    /// registered.
+    pub fn is_woken(&self) -> bool {
+        self.woken.get()
+    }
+
+    fn wake(&self) {
+        self.woken.set(true);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A list of waiters, woken in the order they registered.
+#[crate::pin_data]
+pub struct WaitList<'a> {
+    #[pin]
+    waiters: ListHead<'a, Waiter>,
+}
+
+impl<'a> WaitList<'a> {
+    /// Creates an initializer for an empty wait list.
+    pub fn new() -> impl PinInit<Self> {
+        crate::pin_init!(WaitList {
+            waiters <- ListHead::new(),
+        })
+    }
+
+    fn waiters(self: Pin<&Self>) -> Pin<&ListHead<'a, Waiter>> {
+        unsafe { self.map_unchecked(|list| &list.waiters) }
+    }
+
+    /// Returns true if no waiters are registered.
+    pub fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+
+    /// Returns the number of registered waiters.
+    pub fn len(&self) -> usize {
+        self.waiters.len()
+    }
+
+    /// Registers a waiter to be woken with `waker`.
+    ///
+    /// If the waiter is already registered with this list, only its waker is
+    /// updated, and it keeps its place. Otherwise it is added at the back, and
+    /// no longer counts as woken.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the waiter is registered with a different list, in which
+    /// case the waiter is left unchanged.
+    pub fn register(self: Pin<&Self>, waiter: Pin<&'a Waiter>, waker: &Waker) {
+        if !self.waiters.contains(&waiter) {
+            // Link first, so that a waiter registered with another list
+            // panics before its waker is touched.
+            self.waiters().push_back(waiter);
+            waiter.woken.set(false);
+        }
+        match waiter.waker.take() {
+            Some(old) if old.will_wake(waker) => waiter.waker.set(Some(old)),
+            _ => waiter.waker.set(Some(waker.clone())),
+        }
+    }
+
+    /// Removes a waiter without waking it, returning false if it was not
+    /// registered with this list.
+    pub fn unregister(self: Pin<&Self>, waiter: Pin<&Waiter>) -> bool {
+        let removed = self.waiters().remove(waiter);
+        if removed {
+            waiter.waker.set(None);
+        }
+        removed
+    }
+
+    /// Wakes the waiter which registered first, returning false if there
+    /// were none.
+    pub fn wake_one(self: Pin<&Self>) -> bool {
+        match self.waiters().pop_front() {
+            Some(waiter) => {
+                waiter.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wakes all registered waiters, returning how many were woken.
+    pub fn wake_all(self: Pin<&Self>) -> usize {
+        let mut count = 0;
+        while self.wake_one() {
+            count += 1;
+        }
+        count
+    }
+}
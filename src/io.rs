@@ -0,0 +1,230 @@
+//! Construction of byte buffers by reading them from `std::io`.
+//!
+//! Reading a payload of known length into a `Box<[u8]>` normally means
+//! zero-filling a buffer, or growing a `Vec`, only for the reader to
+//! overwrite it. [`read_exact`] is instead an initializer which reads into
+//! the destination, so the buffer is allocated once, at its final size.
+//!
+//! [`Read::read`] may read from the buffer it is given, so on stable the
+//! destination is zeroed before it is read into. With the `nightly` feature it
+//! is instead passed to [`Read::read_buf`] as a
+//! [`BorrowedCursor`](core::io::BorrowedCursor), which tracks which bytes
+//! have been written, so that readers able to fill uninitialized memory do
+//! so without it being zeroed.
+//!
+//! [`recv_emplaced`] receives from a socket straight into storage reserved in
+//! a container, whose length is only settled by the receive. Sockets are
+//! accepted if they implement [`RecvUninit`]. Those of `std` do, zeroing the
+//! storage before receiving into it, and with the `socket2` feature so does
+//! `socket2::Socket`, which receives into uninitialized memory natively.
+//!
+//! With the `tokio` feature, [`read_exact_emplaced_async`] and
+//! [`read_tail_emplaced_async`] read into place from a `tokio` reader, for
+//! services receiving large payloads over the network.
+
+use core::alloc::Layout;
+#[cfg(feature = "nightly")]
+use core::io::BorrowedBuf;
+#[cfg(feature = "nightly")]
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::slice;
+
+#[cfg(not(inplace_no_global_oom_handling))]
+use alloc::alloc::handle_alloc_error;
+#[cfg(not(inplace_no_global_oom_handling))]
+use alloc::boxed::Box;
+use std::io::{self, Read};
+
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::boxed::BoxExt;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::error::InplaceError;
+use crate::init::TryInit;
+
+#[cfg(all(feature = "tokio", not(inplace_no_global_oom_handling)))]
+mod async_read;
+mod recv;
+
+#[cfg(all(feature = "tokio", not(inplace_no_global_oom_handling)))]
+pub use self::async_read::{read_exact_emplaced_async, read_tail_emplaced_async, ReadEmplaced};
+pub use self::recv::{recv_emplaced, Received, RecvTarget, RecvUninit};
+
+/// An initializer for a byte slice which is read from a reader.
+///
+/// Created by [`read_exact`].
+pub struct ReadExact<R> {
+    reader: R,
+    len: usize,
+}
+
+/// Creates an initializer for a byte slice of `len` bytes read from
+/// `reader`.
+///
+/// As with [`Read::read_exact`], reads are repeated until the slice is full,
+/// interrupted reads are retried, and a reader which ends early fails with
+/// [`io::ErrorKind::UnexpectedEof`]. Any other error of the reader is
+/// returned as it is. Bytes already read are lost in either case.
+///
+/// Without the `nightly` feature, the slice is zeroed before it is read
+/// into, as a reader may read from the buffer it is given.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::sync::Arc;
+/// use inplace::{io, ArcExt, InplaceError};
+///
+/// let mut stream: &[u8] = b"\x00\x05hello";
+/// let header: Arc<[u8]> = Arc::try_emplace(io::read_exact(&mut stream, 2)).unwrap();
+/// let len = u16::from_be_bytes([header[0], header[1]]) as usize;
+/// let body: Arc<[u8]> = Arc::try_emplace(io::read_exact(&mut stream, len)).unwrap();
+/// assert_eq!(&*body, b"hello");
+///
+/// let Err(InplaceError::Init(error)) = Arc::<[u8]>::try_emplace(io::read_exact(stream, 1)) else {
+///     panic!()
+/// };
+/// assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn read_exact<R: Read>(reader: R, len: usize) -> ReadExact<R> {
+    ReadExact { reader, len }
+}
+
+unsafe impl<R: Read> TryInit<[u8], io::Error> for ReadExact<R> {
+    fn layout(&self) -> Layout {
+        Layout::array::<u8>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [u8] {
+        ptr::slice_from_raw_parts_mut(addr, self.len)
+    }
+
+    #[cfg(feature = "nightly")]
+    unsafe fn try_initialize(self, dest: *mut [u8]) -> Result<(), io::Error> {
+        read_buf_exact(self.reader, self.len).try_initialize(dest)
+    }
+
+    #[cfg(not(feature = "nightly"))]
+    unsafe fn try_initialize(mut self, dest: *mut [u8]) -> Result<(), io::Error> {
+        let start = dest.cast::<u8>();
+        start.write_bytes(0, self.len);
+        self.reader
+            .read_exact(slice::from_raw_parts_mut(start, self.len))
+    }
+}
+
+/// An initializer for a byte slice which is read from any reader.
+///
+/// Created by [`read_buf_exact`].
+#[cfg(feature = "nightly")]
+pub struct ReadBufExact<R> {
+    reader: R,
+    len: usize,
+}
+
+/// Creates an initializer for a byte slice of `len` bytes read from `reader`
+/// with [`Read::read_buf_exact`].
+///
+/// The destination is passed to the reader as a cursor, and readers which
+/// can only fill an initialized buffer zero the part they need first. This is
+/// how [`read_exact`] reads with the `nightly` feature. Errors are as for
+/// [`read_exact`], and a reader which reports success without filling the
+/// cursor also fails with [`io::ErrorKind::UnexpectedEof`].
+///
+/// # Example
+///
+/// ```
+/// #![feature(core_io_borrowed_buf, read_buf)]
+/// use std::io::{BorrowedCursor, Read};
+/// use inplace::{io, BoxExt, InplaceError};
+///
+/// // Reads from the buffer it is given, which is zeroed first.
+/// struct Scrambler(u8);
+///
+/// impl Read for Scrambler {
+///     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+///         buf.iter_mut().for_each(|byte| *byte ^= self.0);
+///         Ok(buf.len())
+///     }
+/// }
+///
+/// let mask: Box<[u8]> = Box::try_emplace(io::read_buf_exact(Scrambler(0x5a), 4096)).unwrap();
+/// assert!(mask.iter().all(|&byte| byte == 0x5a));
+///
+/// // Claims to have filled the cursor without writing to it.
+/// struct Liar;
+///
+/// impl Read for Liar {
+///     fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+///         Ok(0)
+///     }
+///
+///     fn read_buf_exact(&mut self, _: BorrowedCursor<'_>) -> std::io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let Err(InplaceError::Init(error)) = Box::<[u8]>::try_emplace(io::read_buf_exact(Liar, 4096))
+/// else {
+///     panic!()
+/// };
+/// assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+/// ```
+#[cfg(feature = "nightly")]
+pub fn read_buf_exact<R: Read>(reader: R, len: usize) -> ReadBufExact<R> {
+    ReadBufExact { reader, len }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<R: Read> TryInit<[u8], io::Error> for ReadBufExact<R> {
+    fn layout(&self) -> Layout {
+        Layout::array::<u8>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [u8] {
+        ptr::slice_from_raw_parts_mut(addr, self.len)
+    }
+
+    unsafe fn try_initialize(mut self, dest: *mut [u8]) -> Result<(), io::Error> {
+        let unfilled = slice::from_raw_parts_mut(dest.cast::<MaybeUninit<u8>>(), self.len);
+        let mut buf = BorrowedBuf::from(unfilled);
+        self.reader.read_buf_exact(buf.unfilled())?;
+        // `read_buf_exact` can be overridden, so only the cursor is trusted.
+        if buf.len() != self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reads exactly `len` bytes from `reader` into a new `Box<[u8]>`.
+///
+/// The box is allocated once and read into directly, zeroed first only
+/// without the `nightly` feature. Errors are as for [`read_exact`], and
+/// allocation failure is passed to the global allocation error handler.
+///
+/// # Example
+///
+/// ```
+/// use inplace::io;
+///
+/// let mut file: &[u8] = b"GIF89a...";
+/// let magic = io::read_exact_emplaced(&mut file, 6)?;
+/// assert_eq!(&*magic, b"GIF89a");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(not(inplace_no_global_oom_handling))]
+pub fn read_exact_emplaced<R: Read>(reader: R, len: usize) -> io::Result<Box<[u8]>> {
+    Box::try_emplace(read_exact(reader, len)).map_err(|error| match error {
+        InplaceError::Alloc(layout) => handle_alloc_error(layout),
+        InplaceError::Init(error) => error,
+    })
+}
@@ -0,0 +1,238 @@
+//! Reading into place from `tokio` readers.
+
+use core::alloc::Layout;
+use core::future::Future;
+use core::mem;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+use core::ptr::{self, addr_of_mut, NonNull};
+use core::slice;
+use core::task::{ready, Context, Poll};
+
+use alloc::boxed::Box;
+use std::io::{self, ErrorKind};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::dst::{HeaderSlice, LayoutProvider};
+use crate::init::Init;
+use crate::raw::{self, DeallocGuard};
+
+/// The number of bytes read between yields by default.
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// A value on the heap whose byte tail is still being read.
+struct Partial<T: ?Sized> {
+    addr: NonNull<u8>,
+    layout: Layout,
+    dest: *mut T,
+    tail: *mut u8,
+    len: usize,
+    /// Drops the parts of the value before the tail.
+    drop_head: unsafe fn(*mut T),
+}
+
+/// A future which reads bytes into a value constructed in place on the heap.
+///
+/// Created by [`read_exact_emplaced_async`] and [`read_tail_emplaced_async`].
+/// Dropping the future before it completes drops the parts of the value
+/// already constructed and frees the allocation.
+pub struct ReadEmplaced<R, T: ?Sized> {
+    reader: R,
+    partial: Option<Partial<T>>,
+    filled: usize,
+    chunk_len: usize,
+    since_yield: usize,
+}
+
+unsafe impl<R: Send, T: ?Sized + Send> Send for ReadEmplaced<R, T> {}
+unsafe impl<R: Sync, T: ?Sized + Sync> Sync for ReadEmplaced<R, T> {}
+
+/// Allocates space for a `Box<[u8]>` of `len` bytes, and returns a future
+/// which fills it by reading from `reader`.
+///
+/// The bytes are read straight into the uninitialized allocation, with no
+/// zero-fill or intermediate buffer. As with `read_exact`, reading continues
+/// until the slice is full, a reader which ends early fails with
+/// [`ErrorKind::UnexpectedEof`], and other errors are returned as they are.
+/// The bytes are read in chunks, 64 KiB by default, and the future yields to
+/// the executor between them, so that a fast reader cannot starve other
+/// tasks.
+///
+/// # Example
+///
+/// ```
+/// use inplace::io;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut socket: &[u8] = b"\x00\x05hello";
+/// let header = io::read_exact_emplaced_async(&mut socket, 2).await?;
+/// let len = u16::from_be_bytes([header[0], header[1]]) as usize;
+/// let body: Box<[u8]> = io::read_exact_emplaced_async(&mut socket, len).await?;
+/// assert_eq!(&*body, b"hello");
+/// # Ok::<(), std::io::Error>(())
+/// # }).unwrap();
+/// ```
+pub fn read_exact_emplaced_async<R>(reader: R, len: usize) -> ReadEmplaced<R, [u8]>
+where
+    R: AsyncRead + Unpin,
+{
+    let layout = <[u8]>::layout(&len);
+    let addr = raw::allocate(layout);
+    ReadEmplaced::new(
+        reader,
+        Partial {
+            addr,
+            layout,
+            dest: <[u8]>::dest(addr.as_ptr(), &len),
+            tail: addr.as_ptr(),
+            len,
+            drop_head: |_| {},
+        },
+    )
+}
+
+/// Allocates space for a [`HeaderSlice`] with a tail of `len` bytes,
+/// constructs its header with `header`, and returns a future which fills the
+/// tail by reading from `reader`.
+///
+/// Reading is as for [`read_exact_emplaced_async`]. If reading fails, the
+/// header is dropped.
+///
+/// # Example
+///
+/// ```
+/// use inplace::dst::HeaderSlice;
+/// use inplace::{init, io};
+///
+/// struct Meta {
+///     stream: u32,
+///     checksums: [u32; 64],
+/// }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let socket: &[u8] = &[0xab; 300];
+/// let meta = init!(Meta { stream: 9, checksums <- init::zeroed() });
+/// let frame: Box<HeaderSlice<Meta, u8>> =
+///     io::read_tail_emplaced_async(socket, meta, 300).await?;
+/// assert_eq!((frame.header.stream, frame.tail.len()), (9, 300));
+/// # Ok::<(), std::io::Error>(())
+/// # }).unwrap();
+/// ```
+pub fn read_tail_emplaced_async<R, H, I>(
+    reader: R,
+    header: I,
+    len: usize,
+) -> ReadEmplaced<R, HeaderSlice<H, u8>>
+where
+    R: AsyncRead + Unpin,
+    I: Init<H>,
+{
+    unsafe fn drop_header<H>(dest: *mut HeaderSlice<H, u8>) {
+        ptr::drop_in_place(addr_of_mut!((*dest).header))
+    }
+
+    let layout = HeaderSlice::<H, u8>::layout(&len);
+    let addr = raw::allocate(layout);
+    let dest = HeaderSlice::<H, u8>::dest(addr.as_ptr(), &len);
+    let guard = DeallocGuard { addr, layout };
+    let tail = unsafe {
+        header.initialize(addr_of_mut!((*dest).header));
+        addr_of_mut!((*dest).tail).cast::<u8>()
+    };
+    mem::forget(guard);
+    ReadEmplaced::new(
+        reader,
+        Partial {
+            addr,
+            layout,
+            dest,
+            tail,
+            len,
+            drop_head: drop_header::<H>,
+        },
+    )
+}
+
+impl<R, T: ?Sized> ReadEmplaced<R, T> {
+    fn new(reader: R, partial: Partial<T>) -> Self {
+        ReadEmplaced {
+            reader,
+            partial: Some(partial),
+            filled: 0,
+            chunk_len: CHUNK_LEN,
+            since_yield: 0,
+        }
+    }
+
+    /// Sets the most bytes read between yields to the executor.
+    ///
+    /// A reader which is always ready, such as a file in the page cache,
+    /// would otherwise fill a buffer of gigabytes in a single poll.
+    pub fn with_chunk_len(mut self, chunk_len: usize) -> Self {
+        self.chunk_len = chunk_len.max(1);
+        self
+    }
+}
+
+impl<R, T> Future for ReadEmplaced<R, T>
+where
+    R: AsyncRead + Unpin,
+    T: ?Sized,
+{
+    type Output = io::Result<Box<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let partial = this
+            .partial
+            .as_ref()
+            .expect("`ReadEmplaced` polled after completion");
+        while this.filled < partial.len {
+            if this.since_yield >= this.chunk_len {
+                this.since_yield = 0;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = (partial.len - this.filled).min(this.chunk_len - this.since_yield);
+            let unfilled = unsafe {
+                slice::from_raw_parts_mut(
+                    partial.tail.add(this.filled).cast::<MaybeUninit<u8>>(),
+                    len,
+                )
+            };
+            let mut buf = ReadBuf::uninit(unfilled);
+            let ptr = buf.filled().as_ptr();
+            ready!(Pin::new(&mut this.reader).poll_read(cx, &mut buf))?;
+            // A reader may replace the buffer, in which case its bytes were
+            // not read into our slot.
+            assert_eq!(
+                ptr,
+                buf.filled().as_ptr(),
+                "reader replaced the read buffer"
+            );
+            let read = buf.filled().len();
+            if read == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                )));
+            }
+            this.filled += read;
+            this.since_yield += read;
+        }
+        let dest = partial.dest;
+        this.partial = None;
+        Poll::Ready(Ok(unsafe { Box::from_raw(dest) }))
+    }
+}
+
+impl<R, T: ?Sized> Drop for ReadEmplaced<R, T> {
+    fn drop(&mut self) {
+        if let Some(partial) = self.partial.take() {
+            unsafe {
+                (partial.drop_head)(partial.dest);
+                raw::deallocate(partial.addr, partial.layout);
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Receiving from sockets into reserved slots of containers.
+
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::ptr::addr_of_mut;
+use core::slice;
+
+use std::io::{self, Read};
+use std::net::{TcpStream, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::{UnixDatagram, UnixStream};
+
+use crate::containers::dst_array::ReservedBytes;
+use crate::init::{self, TryInit};
+#[cfg(target_has_atomic = "ptr")]
+use crate::queue::{mpmc, spsc};
+
+/// Sockets which can receive into uninitialized memory.
+///
+/// # Safety
+///
+/// [`recv_uninit`](RecvUninit::recv_uninit) must only write to the buffer it
+/// is passed, never read from it, and on success must return a count no
+/// larger than the buffer, having initialized that many bytes at its start.
+pub unsafe trait RecvUninit {
+    /// Receives a datagram, or the next bytes of a stream, into `buf`,
+    /// returning the number of bytes received.
+    fn recv_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize>;
+}
+
+/// Zeroes uninitialized bytes, for a socket of `std`, which takes a byte
+/// slice that it is free to read from.
+fn zeroed(buf: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    buf.fill(MaybeUninit::new(0));
+    unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), buf.len()) }
+}
+
+unsafe impl RecvUninit for UdpSocket {
+    fn recv_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        self.recv(zeroed(buf))
+    }
+}
+
+unsafe impl RecvUninit for TcpStream {
+    fn recv_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        (&*self).read(zeroed(buf))
+    }
+}
+
+#[cfg(unix)]
+unsafe impl RecvUninit for UnixDatagram {
+    fn recv_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        self.recv(zeroed(buf))
+    }
+}
+
+#[cfg(unix)]
+unsafe impl RecvUninit for UnixStream {
+    fn recv_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        (&*self).read(zeroed(buf))
+    }
+}
+
+#[cfg(feature = "socket2")]
+unsafe impl RecvUninit for socket2::Socket {
+    fn recv_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        self.recv(buf)
+    }
+}
+
+unsafe impl<S: RecvUninit + ?Sized> RecvUninit for &S {
+    fn recv_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        (**self).recv_uninit(buf)
+    }
+}
+
+/// Storage reserved in a container for bytes received from a socket.
+pub trait RecvTarget {
+    /// What the storage becomes once the bytes have been received.
+    type Output;
+
+    /// Passes the reserved bytes to `recv`, and finishes the storage with as
+    /// many of them as it returns.
+    ///
+    /// If `recv` fails, the reservation is given up and the error returned.
+    ///
+    /// # Safety
+    ///
+    /// If `recv` returns `Ok(n)`, `n` must be no larger than the bytes it was
+    /// passed, and it must have initialized the first `n` of them.
+    unsafe fn recv_with<F>(self, recv: F) -> io::Result<Self::Output>
+    where
+        F: FnOnce(&mut [MaybeUninit<u8>]) -> io::Result<usize>;
+}
+
+/// Receives from `socket` directly into `target`, storage reserved in a
+/// container.
+///
+/// The bytes go from the socket into their final place, which holds as many
+/// as were received once this returns. Targets include an element reserved in
+/// a [`DstArray<[u8]>`](crate::containers::dst_array::DstArray), whose
+/// allocation is shrunk to fit, and a slot reserved in a
+/// [queue](crate::queue) of [`Received`] buffers. Errors of the socket are
+/// returned as they are, giving up the reservation. As with `recv`, a
+/// datagram too long for the target is truncated, and zero bytes received
+/// from a stream means that it has ended.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() -> std::io::Result<()> {
+/// use std::net::UdpSocket;
+/// use inplace::containers::dst_array::DstArray;
+/// use inplace::io;
+///
+/// let rx = UdpSocket::bind("127.0.0.1:0")?;
+/// let tx = UdpSocket::bind("127.0.0.1:0")?;
+/// tx.send_to(b"first", rx.local_addr()?)?;
+/// tx.send_to(b"second", rx.local_addr()?)?;
+///
+/// let mut datagrams: DstArray<[u8]> = DstArray::new();
+/// for _ in 0..2 {
+///     io::recv_emplaced(&rx, datagrams.reserve_bytes(65_536))?;
+/// }
+/// assert_eq!(&datagrams[1], b"second");
+/// # Ok::<(), std::io::Error>(())
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn recv_emplaced<S, T>(socket: &S, target: T) -> io::Result<T::Output>
+where
+    S: RecvUninit + ?Sized,
+    T: RecvTarget,
+{
+    unsafe { target.recv_with(|buf| socket.recv_uninit(buf)) }
+}
+
+impl<'a> RecvTarget for ReservedBytes<'a> {
+    type Output = &'a mut [u8];
+
+    unsafe fn recv_with<F>(mut self, recv: F) -> io::Result<&'a mut [u8]>
+    where
+        F: FnOnce(&mut [MaybeUninit<u8>]) -> io::Result<usize>,
+    {
+        let len = recv(self.as_uninit_mut())?;
+        Ok(self.assume_init(len))
+    }
+}
+
+/// Up to `M` bytes received from a socket, stored inline.
+///
+/// This is the element type for receiving into the slots of a
+/// [queue](crate::queue) with [`recv_emplaced`], which fills the buffer and
+/// sets its length in place. It dereferences to the bytes received.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() -> std::io::Result<()> {
+/// use std::net::UdpSocket;
+/// use inplace::io::{self, Received};
+/// use inplace::queue::spsc::Ring;
+/// use inplace::BoxExt;
+///
+/// let rx = UdpSocket::bind("127.0.0.1:0")?;
+/// UdpSocket::bind("127.0.0.1:0")?.send_to(b"ping", rx.local_addr()?)?;
+///
+/// let mut ring: Box<Ring<Received<1500>, 64>> = Box::emplace(Ring::new());
+/// let (mut tx, mut rx_ring) = ring.split();
+/// io::recv_emplaced(&rx, tx.reserve().unwrap())?.commit();
+/// assert_eq!(&**rx_ring.front().unwrap(), b"ping");
+/// # Ok::<(), std::io::Error>(())
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub struct Received<const M: usize> {
+    len: usize,
+    bytes: [MaybeUninit<u8>; M],
+}
+
+impl<const M: usize> Received<M> {
+    /// Returns the most bytes the buffer can hold.
+    pub const fn capacity(&self) -> usize {
+        M
+    }
+
+    /// Returns an initializer which fills the buffer with `recv`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`RecvTarget::recv_with`].
+    unsafe fn recv_with<F>(recv: F) -> impl TryInit<Self, io::Error>
+    where
+        F: FnOnce(&mut [MaybeUninit<u8>]) -> io::Result<usize>,
+    {
+        init::try_from_raw(|dest: *mut Self| {
+            let len = recv(&mut *addr_of_mut!((*dest).bytes))?;
+            addr_of_mut!((*dest).len).write(len);
+            Ok(())
+        })
+    }
+}
+
+impl<const M: usize> Deref for Received<M> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.bytes.as_ptr().cast(), self.len) }
+    }
+}
+
+impl<const M: usize> DerefMut for Received<M> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.bytes.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl<const M: usize> fmt::Debug for Received<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<'a, const M: usize, const N: usize> RecvTarget for spsc::Reserved<'a, Received<M>, N> {
+    type Output = spsc::Staged<'a, Received<M>, N>;
+
+    unsafe fn recv_with<F>(self, recv: F) -> io::Result<Self::Output>
+    where
+        F: FnOnce(&mut [MaybeUninit<u8>]) -> io::Result<usize>,
+    {
+        self.try_emplace(Received::recv_with(recv))
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<const M: usize, const N: usize> RecvTarget for mpmc::Reserved<'_, Received<M>, N> {
+    type Output = ();
+
+    unsafe fn recv_with<F>(self, recv: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut [MaybeUninit<u8>]) -> io::Result<usize>,
+    {
+        self.try_emplace(Received::recv_with(recv))
+    }
+}
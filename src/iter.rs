@@ -0,0 +1,256 @@
+//! Construction of slices from iterators.
+//!
+//! Collecting an iterator into a `Box<[T]>` or `Arc<[T]>` normally goes
+//! through a `Vec`, which may grow and reallocate, and for `Arc` is then
+//! copied into the shared allocation. When the iterator knows its exact
+//! length, the final allocation can instead be made once, up front, and each
+//! item written straight into it.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+#[cfg(all(
+    feature = "alloc",
+    target_has_atomic = "ptr",
+    not(inplace_no_global_oom_handling)
+))]
+use alloc::sync::Arc;
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use alloc::{alloc::handle_alloc_error, boxed::Box, rc::Rc};
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use crate::boxed::BoxExt;
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use crate::error::InplaceError;
+use crate::init::{Prefix, TryInit};
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use crate::rc::RcExt;
+#[cfg(all(
+    feature = "alloc",
+    target_has_atomic = "ptr",
+    not(inplace_no_global_oom_handling)
+))]
+use crate::sync::ArcExt;
+
+/// The error returned when an iterator yields a different number of items
+/// from the length it reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LengthMismatch {
+    reported: usize,
+    yielded: usize,
+}
+
+impl LengthMismatch {
+    /// Returns the length the iterator reported.
+    pub fn reported(&self) -> usize {
+        self.reported
+    }
+
+    /// Returns the number of items the iterator yielded.
+    ///
+    /// Iteration stops at the first item beyond the reported length, so an
+    /// iterator which yielded too many items gives `reported() + 1`.
+    pub fn yielded(&self) -> usize {
+        self.yielded
+    }
+}
+
+/// An initializer for a slice filled from an iterator of known length.
+///
+/// Created by [`exact`] or [`from_iter`].
+pub struct Exact<T, I> {
+    iter: I,
+    len: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer for a slice of the items of an exact-size
+/// iterator.
+///
+/// The length of the slice is the length reported by the iterator. If the
+/// iterator yields a different number of items, the items already written are
+/// dropped and [`LengthMismatch`] is returned.
+pub fn exact<T, I>(iter: I) -> Exact<T, I::IntoIter>
+where
+    I: IntoIterator<Item = T>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let iter = iter.into_iter();
+    from_iter(iter.len(), iter)
+}
+
+/// Creates an initializer for a slice of `len` items taken from an iterator.
+///
+/// This accepts any iterator, for when the length is known to the caller
+/// rather than to the iterator. If the iterator yields fewer or more than
+/// `len` items, the items already written are dropped and
+/// [`LengthMismatch`] is returned, with `len` as the reported length.
+///
+/// # Example
+///
+/// ```
+/// use inplace::{init, BoxExt, InplaceError};
+///
+/// let words = "in place at last".split(' ');
+/// let words: Box<[&str]> = Box::try_emplace(init::from_iter(4, words)).unwrap();
+/// assert_eq!(words[3], "last");
+///
+/// let short = Box::<[u32]>::try_emplace(init::from_iter(5, (0..).take_while(|&n| n < 3)));
+/// let Err(InplaceError::Init(error)) = short else { panic!() };
+/// assert_eq!((error.reported(), error.yielded()), (5, 3));
+/// ```
+pub fn from_iter<T, I>(len: usize, iter: I) -> Exact<T, I::IntoIter>
+where
+    I: IntoIterator<Item = T>,
+{
+    Exact {
+        len,
+        iter: iter.into_iter(),
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I> TryInit<[T], LengthMismatch> for Exact<T, I>
+where
+    I: Iterator<Item = T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn try_initialize(mut self, dest: *mut [T]) -> Result<(), LengthMismatch> {
+        let mut guard = Prefix {
+            start: dest.cast::<T>(),
+            len: 0,
+        };
+        while guard.len < self.len {
+            let Some(item) = self.iter.next() else {
+                return Err(LengthMismatch {
+                    reported: self.len,
+                    yielded: guard.len,
+                });
+            };
+            guard.start.add(guard.len).write(item);
+            guard.len += 1;
+        }
+        if self.iter.next().is_some() {
+            return Err(LengthMismatch {
+                reported: self.len,
+                yielded: self.len + 1,
+            });
+        }
+        mem::forget(guard);
+        Ok(())
+    }
+}
+
+/// Slice containers which can be built from an exact-size iterator with a
+/// single allocation.
+pub trait FromExactIter<T>: Sized {
+    /// Allocates space for the iterator's reported length, and writes each
+    /// item into it.
+    ///
+    /// If the iterator yields a different number of items, they are dropped,
+    /// the allocation is freed, and [`LengthMismatch`] is returned.
+    fn from_exact_iter<I>(iter: I) -> Result<Self, LengthMismatch>
+    where
+        I: ExactSizeIterator<Item = T>;
+}
+
+/// Passes allocation failures to the global allocation error handler, as
+/// collecting into a `Vec` would.
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+fn alloc_or_mismatch(error: InplaceError<LengthMismatch>) -> LengthMismatch {
+    match error {
+        InplaceError::Alloc(layout) => handle_alloc_error(layout),
+        InplaceError::Init(error) => error,
+    }
+}
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+impl<T> FromExactIter<T> for Box<[T]> {
+    fn from_exact_iter<I>(iter: I) -> Result<Self, LengthMismatch>
+    where
+        I: ExactSizeIterator<Item = T>,
+    {
+        Box::try_emplace(exact(iter)).map_err(alloc_or_mismatch)
+    }
+}
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+impl<T> FromExactIter<T> for Rc<[T]> {
+    fn from_exact_iter<I>(iter: I) -> Result<Self, LengthMismatch>
+    where
+        I: ExactSizeIterator<Item = T>,
+    {
+        Rc::try_emplace(exact(iter)).map_err(alloc_or_mismatch)
+    }
+}
+
+#[cfg(all(
+    feature = "alloc",
+    target_has_atomic = "ptr",
+    not(inplace_no_global_oom_handling)
+))]
+impl<T> FromExactIter<T> for Arc<[T]> {
+    fn from_exact_iter<I>(iter: I) -> Result<Self, LengthMismatch>
+    where
+        I: ExactSizeIterator<Item = T>,
+    {
+        Arc::try_emplace(exact(iter)).map_err(alloc_or_mismatch)
+    }
+}
+
+/// Extension methods for collecting exact-size iterators in place.
+pub trait CollectEmplaced: ExactSizeIterator + Sized {
+    /// Collects the items into a slice container with a single allocation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(not(inplace_no_global_oom_handling))]
+    /// # fn main() {
+    /// use std::sync::Arc;
+    /// use inplace::CollectEmplaced;
+    ///
+    /// let squares: Arc<[u32]> = (0..5).map(|n| n * n).collect_emplaced().unwrap();
+    /// assert_eq!(&*squares, &[0, 1, 4, 9, 16]);
+    ///
+    /// struct Liar(u32);
+    ///
+    /// impl Iterator for Liar {
+    ///     type Item = u32;
+    ///
+    ///     fn next(&mut self) -> Option<u32> {
+    ///         self.0 += 1;
+    ///         (self.0 <= 2).then_some(self.0)
+    ///     }
+    ///
+    ///     fn size_hint(&self) -> (usize, Option<usize>) {
+    ///         (3, Some(3))
+    ///     }
+    /// }
+    ///
+    /// impl ExactSizeIterator for Liar {}
+    ///
+    /// let error = Liar(0).collect_emplaced::<Box<[u32]>>().unwrap_err();
+    /// assert_eq!((error.reported(), error.yielded()), (3, 2));
+    /// # }
+    /// # #[cfg(inplace_no_global_oom_handling)]
+    /// # fn main() {}
+    /// ```
+    fn collect_emplaced<C>(self) -> Result<C, LengthMismatch>
+    where
+        C: FromExactIter<Self::Item>,
+    {
+        C::from_exact_iter(self)
+    }
+}
+
+impl<I: ExactSizeIterator> CollectEmplaced for I {}
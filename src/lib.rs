@@ -0,0 +1,120 @@
+//! Library prototype of the `inplace` types described in the Pre-RFC.
+//!
+//! The Pre-RFC proposes language support for values that are initialized in
+//! place at their destination. Most of that design can be approximated today
+//! with ordinary library code, which this crate does so that the ideas can be
+//! tried out on real containers before any compiler changes are made.
+//!
+//! The central abstraction is the [`Init`] trait, the library form of
+//! `inplace T`: a potential value which knows its layout and can later be
+//! constructed at a destination chosen by the consumer. The weaker [`PinInit`]
+//! trait describes values which must never move once constructed.
+
+#![no_std]
+#![cfg_attr(feature = "nightly", feature(layout_for_ptr, ptr_metadata))]
+#![cfg_attr(
+    all(feature = "nightly", feature = "alloc"),
+    feature(allocator_api, core_io, core_io_borrowed_buf)
+)]
+#![cfg_attr(all(feature = "nightly", feature = "std"), feature(read_buf))]
+#![cfg_attr(
+    all(
+        feature = "nightly",
+        feature = "alloc",
+        target_has_atomic = "ptr",
+        not(inplace_no_global_oom_handling)
+    ),
+    feature(unique_rc_arc)
+)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+// Allows the macros to refer to this crate as `::inplace` from within it.
+extern crate self as inplace;
+
+#[doc(hidden)]
+pub mod __private;
+pub mod align;
+pub mod any;
+pub mod arena;
+#[cfg(feature = "alloc")]
+pub mod boxed;
+#[cfg(feature = "alloc")]
+pub mod boxed_slice;
+#[cfg(feature = "bytes")]
+pub mod bytes;
+pub mod cell;
+#[cfg(feature = "alloc")]
+pub mod collections;
+#[cfg(any(feature = "moveit", feature = "pin-init"))]
+pub mod compat;
+#[cfg(feature = "alloc")]
+pub mod containers;
+pub mod dst;
+pub mod error;
+pub mod ffi;
+pub mod init;
+pub mod intrusive;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod iter;
+pub mod maybe;
+#[cfg(feature = "memmap2")]
+pub mod mmap;
+#[cfg(feature = "rayon")]
+pub mod par;
+pub mod pin;
+pub mod pool;
+#[cfg(target_has_atomic = "ptr")]
+pub mod queue;
+#[cfg(feature = "alloc")]
+mod raw;
+#[cfg(feature = "alloc")]
+pub mod rc;
+pub mod replace;
+pub mod resume;
+#[cfg(all(feature = "vmem", any(unix, windows)))]
+pub mod scratch;
+pub mod section;
+pub mod slot;
+pub mod string;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+#[cfg(feature = "alloc")]
+pub mod vec;
+pub mod volatile;
+pub mod write;
+
+#[cfg(feature = "alloc")]
+pub use boxed::BoxExt;
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+pub use boxed::BoxInExt;
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+pub use collections::EmplaceEntry;
+#[cfg(all(feature = "slab", not(inplace_no_global_oom_handling)))]
+pub use collections::SlabExt;
+#[cfg(feature = "alloc")]
+pub use collections::{BinaryHeapExt, VecDequeExt};
+pub use error::InplaceError;
+pub use init::{Init, PinInit, TryInit};
+pub use inplace_macros::{init, pin_data, pin_init, self_referencing, Init, PinInit};
+pub use iter::CollectEmplaced;
+pub use maybe::MaybeInplace;
+pub use pin::PinnedDrop;
+#[cfg(feature = "alloc")]
+pub use rc::RcExt;
+#[cfg(all(feature = "alloc", feature = "nightly"))]
+pub use rc::RcInExt;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub use sync::ArcExt;
+#[cfg(all(feature = "alloc", feature = "nightly", target_has_atomic = "ptr"))]
+pub use sync::ArcInExt;
+#[cfg(feature = "alloc")]
+pub use vec::VecExt;
@@ -0,0 +1,92 @@
+//! An emulation of `?inplace T` as a concrete type.
+
+use core::alloc::Layout;
+
+use crate::init::{Init, PinInit};
+
+/// Either a value of type `T`, or an initializer for one.
+///
+/// This is a concrete form of the sketch's `?inplace T`. In most signatures a
+/// generic `impl Init<T>` parameter is the closer emulation, since like the
+/// proposed language feature it is monomorphized separately for values and
+/// initializers. `MaybeInplace` is for cases where the choice between the two
+/// is made at runtime, or where a single named type is needed, and it
+/// dispatches between them when initialized.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::{init, Init, MaybeInplace};
+///
+/// fn table(precomputed: Option<[u32; 256]>) -> MaybeInplace<[u32; 256], impl Init<[u32; 256]>> {
+///     match precomputed {
+///         Some(table) => MaybeInplace::from(table),
+///         None => MaybeInplace::Inplace(init::with(|| core::array::from_fn(|i| i as u32))),
+///     }
+/// }
+///
+/// let table = table(None);
+/// assert!(table.is_inplace());
+///
+/// let mut slot = MaybeUninit::<[u32; 256]>::uninit();
+/// unsafe { table.initialize(slot.as_mut_ptr()) };
+/// assert_eq!(unsafe { slot.assume_init() }[255], 255);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub enum MaybeInplace<T, I> {
+    /// A value which already exists, and will be moved into place.
+    Value(T),
+    /// An initializer which will construct the value in place.
+    Inplace(I),
+}
+
+impl<T, I> MaybeInplace<T, I> {
+    /// Returns true if this holds an initializer rather than a value.
+    pub fn is_inplace(&self) -> bool {
+        matches!(self, MaybeInplace::Inplace(_))
+    }
+}
+
+impl<T, I> From<T> for MaybeInplace<T, I> {
+    fn from(value: T) -> Self {
+        MaybeInplace::Value(value)
+    }
+}
+
+unsafe impl<T, I> PinInit<T> for MaybeInplace<T, I>
+where
+    I: PinInit<T>,
+{
+    /// Returns the layout of the value, as `Layout::for_maybe_inplace_value`
+    /// does in the sketch.
+    fn layout(&self) -> Layout {
+        match self {
+            MaybeInplace::Value(_) => Layout::new::<T>(),
+            MaybeInplace::Inplace(init) => init.layout(),
+        }
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut T {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut T) {
+        match self {
+            MaybeInplace::Value(value) => dest.write(value),
+            MaybeInplace::Inplace(init) => init.pin_initialize(dest),
+        }
+    }
+}
+
+unsafe impl<T, I> Init<T> for MaybeInplace<T, I>
+where
+    I: Init<T>,
+{
+    unsafe fn initialize(self, dest: *mut T) {
+        match self {
+            MaybeInplace::Value(value) => dest.write(value),
+            MaybeInplace::Inplace(init) => init.initialize(dest),
+        }
+    }
+}
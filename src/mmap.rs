@@ -0,0 +1,476 @@
+//! Emplacement into memory mappings.
+//!
+//! Values in the hundreds of megabytes are too large for the stack, and often
+//! for the heap allocator to handle well. An [`MmapBox`] instead constructs
+//! its value at the start of a mapping of its own, made with `memmap2`, which
+//! is either anonymous or backed by a file.
+//!
+//! Large lookup tables and caches can also be placed on huge pages with
+//! [`MmapBox::emplace_huge`], to reduce pressure on the TLB. On Linux, with
+//! the `numa` feature, [`MmapBox::emplace_on_node`] places a value in the
+//! memory of a chosen NUMA node.
+//!
+//! A [`PersistentBox`] constructs a value in a file, with a header which is
+//! checked when the file is reopened, for state which outlives the process.
+//! Unlike [`MmapBox::emplace_in_file`], it flushes the value once it has
+//! been constructed, and only then marks the file complete.
+//!
+//! On Unix, with the `shm` feature, a [`SharedBox`] constructs a value in
+//! shared memory, to which other processes can attach.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+use std::fs::File;
+use std::io;
+
+#[cfg(unix)]
+use memmap2::Advice;
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::error::InplaceError;
+use crate::init::{Init, PinInit, TryInit};
+
+mod persistent;
+#[cfg(all(feature = "shm", unix))]
+mod shared;
+
+pub use persistent::PersistentBox;
+#[cfg(all(feature = "shm", unix))]
+pub use shared::SharedBox;
+
+/// A value constructed in place in a memory mapping, which it owns.
+///
+/// The value is dropped before the mapping is unmapped.
+///
+/// # Example
+///
+/// ```
+/// use inplace::init;
+/// use inplace::mmap::MmapBox;
+///
+/// // 256 MiB, which is never on the stack or the heap.
+/// let mut counts: MmapBox<[u64; 1 << 25]> = MmapBox::emplace(init::zeroed())?;
+/// counts[12345] += 1;
+/// assert_eq!(counts.iter().sum::<u64>(), 1);
+/// # Ok::<_, std::io::Error>(())
+/// ```
+pub struct MmapBox<T: ?Sized> {
+    value: NonNull<T>,
+    map: MmapMut,
+    huge: Option<HugePageSize>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for MmapBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MmapBox<T> {}
+
+/// A size of huge page.
+///
+/// Which sizes are available depends on the processor, and on how many pages
+/// of each size the system administrator has reserved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HugePageSize {
+    /// 2 MiB pages.
+    Size2M,
+    /// 1 GiB pages.
+    Size1G,
+}
+
+impl HugePageSize {
+    /// Returns the size of a page in bytes.
+    pub const fn bytes(self) -> usize {
+        1 << self.bits()
+    }
+
+    const fn bits(self) -> u8 {
+        match self {
+            HugePageSize::Size2M => 21,
+            HugePageSize::Size1G => 30,
+        }
+    }
+}
+
+/// Maps anonymous memory on huge pages of `size`, or on smaller pages if
+/// those cannot be had, returning the size of huge page obtained.
+fn map_huge(len: usize, size: HugePageSize) -> io::Result<(MmapMut, Option<HugePageSize>)> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut sizes = [Some(size), Some(HugePageSize::Size2M)];
+        if size == HugePageSize::Size2M {
+            sizes[1] = None;
+        }
+        for size in sizes.into_iter().flatten() {
+            // Huge page mappings must be a whole number of pages long.
+            let len = len.div_ceil(size.bytes()) * size.bytes();
+            if let Ok(map) = MmapOptions::new()
+                .len(len)
+                .huge(Some(size.bits()))
+                .map_anon()
+            {
+                return Ok((map, Some(size)));
+            }
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let _ = size;
+
+    let map = MmapOptions::new().len(len).map_anon()?;
+    // Ask for transparent huge pages instead, where the kernel supports
+    // them. This is only advice, so failure is not an error.
+    #[cfg(target_os = "linux")]
+    let _ = map.advise(memmap2::Advice::HugePage);
+    Ok((map, None))
+}
+
+/// Binds the pages of a mapping to a NUMA node, before any are touched.
+#[cfg(all(feature = "numa", target_os = "linux"))]
+fn bind_to_node(map: &mut MmapMut, node: usize) -> io::Result<()> {
+    const MPOL_BIND: libc::c_int = 2;
+    const MPOL_MF_STRICT: libc::c_uint = 1;
+
+    let bits = libc::c_ulong::BITS as usize;
+    let mut mask = std::vec![0 as libc::c_ulong; node / bits + 1];
+    mask[node / bits] |= 1 << (node % bits);
+    // The kernel reads one bit fewer than `maxnode`, so pass one more.
+    let maxnode = mask.len() * bits + 1;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            map.as_mut_ptr(),
+            map.len(),
+            MPOL_BIND,
+            mask.as_ptr(),
+            maxnode,
+            MPOL_MF_STRICT,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The smallest page size of any supported platform, to which every mapping
+/// is therefore aligned.
+const MIN_PAGE: usize = 4096;
+
+/// Returns the length of an anonymous mapping for a value of `layout`.
+///
+/// A value aligned more strictly than a page needs room to be moved up to an
+/// aligned address within the mapping.
+fn anon_len(layout: Layout) -> usize {
+    layout.size().max(1) + layout.align().saturating_sub(MIN_PAGE)
+}
+
+/// Returns the first address in a mapping aligned for a value of `layout`,
+/// checking that the value fits after it.
+fn map_addr(map: &mut MmapMut, layout: Layout) -> io::Result<*mut u8> {
+    let addr = map.as_mut_ptr();
+    let offset = addr.align_offset(layout.align());
+    if offset > map.len() || layout.size() > map.len() - offset {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "value is aligned more strictly than a page",
+        ));
+    }
+    Ok(unsafe { addr.add(offset) })
+}
+
+/// Returns the offset within `map` of the `len` bytes at `offset` within its
+/// value.
+///
+/// # Panics
+///
+/// Panics if the range is not within the value.
+fn value_range<T: ?Sized>(map: &MmapMut, value: NonNull<T>, offset: usize, len: usize) -> usize {
+    let size = unsafe { mem::size_of_val(value.as_ref()) };
+    assert!(
+        offset <= size && len <= size - offset,
+        "range {offset}..{offset}+{len} is outside the value of {size} bytes"
+    );
+    value.as_ptr().cast::<u8>() as usize - map.as_ptr() as usize + offset
+}
+
+impl<T: ?Sized> MmapBox<T> {
+    /// Maps anonymous memory and constructs the value there.
+    ///
+    /// The value is placed at the start of the mapping, which is aligned to a
+    /// page. For a value aligned more strictly than that, the mapping is
+    /// enlarged so that the value can start at an aligned address within it.
+    /// If the initializer panics, the mapping is unmapped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::align::{self, Aligned};
+    /// use inplace::mmap::MmapBox;
+    ///
+    /// #[repr(align(65536))]
+    /// struct Block;
+    ///
+    /// let block: MmapBox<Aligned<Block, [u8; 4096]>> = MmapBox::emplace(align::aligned([7; 4096]))?;
+    /// assert_eq!(block.as_ptr() as usize % 65536, 0);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    pub fn emplace<I>(init: I) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let layout = PinInit::layout(&init);
+        let mut map = MmapOptions::new().len(anon_len(layout)).map_anon()?;
+        let dest = init.dest(map_addr(&mut map, layout)?);
+        unsafe {
+            init.initialize(dest);
+            Ok(Self::from_parts(dest, map))
+        }
+    }
+
+    /// Attempts to map anonymous memory and construct the value there.
+    ///
+    /// If the memory cannot be mapped, [`InplaceError::Alloc`] is returned
+    /// and the initializer is not run. If the initializer fails, the mapping
+    /// is unmapped and its error is returned as [`InplaceError::Init`].
+    pub fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let layout = init.layout();
+        let mut map = MmapOptions::new()
+            .len(anon_len(layout))
+            .map_anon()
+            .map_err(|_| InplaceError::Alloc(layout))?;
+        let addr = map_addr(&mut map, layout).map_err(|_| InplaceError::Alloc(layout))?;
+        let dest = init.dest(addr);
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            Ok(Self::from_parts(dest, map))
+        }
+    }
+
+    /// Maps anonymous memory and constructs a pinned value there.
+    pub fn pin_emplace<I>(init: I) -> io::Result<Pin<Self>>
+    where
+        I: PinInit<T>,
+    {
+        let layout = init.layout();
+        let mut map = MmapOptions::new().len(anon_len(layout)).map_anon()?;
+        let dest = init.dest(map_addr(&mut map, layout)?);
+        unsafe {
+            init.pin_initialize(dest);
+            Ok(Pin::new_unchecked(Self::from_parts(dest, map)))
+        }
+    }
+
+    /// Maps anonymous memory on huge pages and constructs the value there.
+    ///
+    /// Pages of the requested `size` are tried first, then 2 MiB pages. If
+    /// neither can be mapped, because the platform has no explicit huge
+    /// pages or none are reserved, the value is placed on ordinary pages,
+    /// which on Linux are marked as eligible for transparent huge pages.
+    /// [`huge_page_size`](MmapBox::huge_page_size) reports which was used.
+    ///
+    /// The mapping is rounded up to a whole number of huge pages, so values
+    /// much smaller than a page waste most of it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::init;
+    /// use inplace::mmap::{HugePageSize, MmapBox};
+    ///
+    /// let table: MmapBox<[u32; 1 << 20]> =
+    ///     MmapBox::emplace_huge(init::zeroed(), HugePageSize::Size1G)?;
+    /// if let Some(size) = MmapBox::huge_page_size(&table) {
+    ///     assert!(size.bytes() >= 2 << 20);
+    /// }
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    pub fn emplace_huge<I>(init: I, size: HugePageSize) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let layout = PinInit::layout(&init);
+        let (mut map, huge) = map_huge(anon_len(layout), size)?;
+        let dest = init.dest(map_addr(&mut map, layout)?);
+        unsafe {
+            init.initialize(dest);
+            let mut this = Self::from_parts(dest, map);
+            this.huge = huge;
+            Ok(this)
+        }
+    }
+
+    /// Maps anonymous memory bound to NUMA node `node`, and constructs the
+    /// value there.
+    ///
+    /// The binding is made before the initializer runs, so every page the
+    /// value touches while it is being constructed is allocated from the
+    /// node's memory, and stays there. This is how large per-node state is
+    /// given locality: construct it on the node whose threads will use it.
+    ///
+    /// An error is returned if the node does not exist, or if the process
+    /// is not allowed to use it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::init;
+    /// use inplace::mmap::MmapBox;
+    ///
+    /// let state: MmapBox<[u64; 1 << 20]> = MmapBox::emplace_on_node(init::zeroed(), 0)?;
+    /// assert_eq!(state[0], 0);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    pub fn emplace_on_node<I>(init: I, node: usize) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let layout = PinInit::layout(&init);
+        let mut map = MmapOptions::new().len(anon_len(layout)).map_anon()?;
+        bind_to_node(&mut map, node)?;
+        let dest = init.dest(map_addr(&mut map, layout)?);
+        unsafe {
+            init.initialize(dest);
+            Ok(Self::from_parts(dest, map))
+        }
+    }
+
+    /// Maps the start of `file` and constructs the value there.
+    ///
+    /// The file is extended if it is too short to hold the value. What the
+    /// value leaves in the file is written back by the operating system in
+    /// its own time, or when it is flushed with [`flush`](MmapBox::flush) or
+    /// [`flush_range`](MmapBox::flush_range). A value aligned more
+    /// strictly than a page cannot be placed at the start of a file, so is
+    /// rejected with an error of kind
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput).
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, truncated or mapped by anything else,
+    /// in this process or another, while the `MmapBox` exists.
+    pub unsafe fn emplace_in_file<I>(file: &File, init: I) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let layout = PinInit::layout(&init);
+        let len = layout.size().max(1);
+        if file.metadata()?.len() < len as u64 {
+            file.set_len(len as u64)?;
+        }
+        let mut map = MmapOptions::new().len(len).map_mut(file)?;
+        let dest = init.dest(map_addr(&mut map, layout)?);
+        init.initialize(dest);
+        Ok(Self::from_parts(dest, map))
+    }
+
+    unsafe fn from_parts(value: *mut T, map: MmapMut) -> Self {
+        MmapBox {
+            value: NonNull::new_unchecked(value),
+            map,
+            huge: None,
+        }
+    }
+
+    /// Returns the size of huge page the value was placed on, or `None` if it
+    /// is on ordinary pages.
+    pub fn huge_page_size(this: &Self) -> Option<HugePageSize> {
+        this.huge
+    }
+
+    /// Advises the kernel how the mapping will be accessed.
+    #[cfg(unix)]
+    pub fn advise(this: &Self, advice: Advice) -> io::Result<()> {
+        this.map.advise(advice)
+    }
+
+    /// Locks the mapping into memory, so that it is not paged out.
+    #[cfg(unix)]
+    pub fn lock(this: &Self) -> io::Result<()> {
+        this.map.lock()
+    }
+
+    /// Unlocks the mapping, after [`lock`](MmapBox::lock).
+    #[cfg(unix)]
+    pub fn unlock(this: &Self) -> io::Result<()> {
+        this.map.unlock()
+    }
+
+    /// Writes changes to a value constructed with
+    /// [`emplace_in_file`](MmapBox::emplace_in_file) back to the file.
+    pub fn flush(this: &Self) -> io::Result<()> {
+        this.map.flush()
+    }
+
+    /// Writes changes to `len` bytes of the value, starting `offset` bytes
+    /// into it, back to the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is not within the value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use inplace::init;
+    /// use inplace::mmap::MmapBox;
+    ///
+    /// let path = std::env::temp_dir().join(format!("inplace-flush-{}", std::process::id()));
+    /// let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+    /// let mut log: MmapBox<[u8; 1 << 20]> = unsafe { MmapBox::emplace_in_file(&file, init::zeroed())? };
+    /// log[8192..8196].copy_from_slice(b"done");
+    /// MmapBox::flush_range(&log, 8192, 4)?;
+    /// # drop(log);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    pub fn flush_range(this: &Self, offset: usize, len: usize) -> io::Result<()> {
+        this.map
+            .flush_range(value_range(&this.map, this.value, offset, len), len)
+    }
+
+    /// Moves the value out of the mapping, and unmaps it.
+    pub fn into_inner(this: Self) -> T
+    where
+        T: Sized,
+    {
+        let this = mem::ManuallyDrop::new(this);
+        unsafe {
+            let value = this.value.as_ptr().read();
+            drop(core::ptr::read(&this.map));
+            value
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for MmapBox<T> {
+    fn drop(&mut self) {
+        unsafe { self.value.as_ptr().drop_in_place() }
+    }
+}
+
+impl<T: ?Sized> Deref for MmapBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MmapBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MmapBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
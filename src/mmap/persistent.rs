@@ -0,0 +1,290 @@
+//! Values persisted in files, constructed in place in a mapping.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+use std::fs::File;
+use std::io;
+
+#[cfg(unix)]
+use memmap2::Advice;
+use memmap2::{MmapMut, MmapOptions};
+
+use super::value_range;
+use crate::init::Init;
+
+/// Identifies a file made by [`PersistentBox`].
+const MAGIC: u64 = u64::from_le_bytes(*b"inplpers");
+
+/// The value is being constructed, or its construction was interrupted.
+const CREATING: u32 = 0;
+/// The value has been constructed and flushed.
+const READY: u32 = 1;
+
+/// The start of a persistent file, before the value.
+#[repr(C)]
+struct Header {
+    magic: u64,
+    size: u64,
+    align: u64,
+    tag: u64,
+    state: u32,
+}
+
+/// Returns the layout of a file holding a `T`, and the offset of the value.
+fn file_layout<T>() -> (Layout, usize) {
+    let (layout, offset) = Layout::new::<Header>()
+        .extend(Layout::new::<T>())
+        .expect("persistent value is too large");
+    (layout.pad_to_align(), offset)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A `repr(C)` value constructed in place in a file, which can be reopened
+/// later, by this process or another run of the program.
+///
+/// The value is preceded by a header recording its layout, a tag chosen by
+/// the application, such as a schema version, and whether construction
+/// finished. [`create`](PersistentBox::create) writes the header, constructs
+/// the value after it, and flushes both, the value first, so a file whose
+/// construction was interrupted by a crash is never mistaken for a complete
+/// one. [`open`](PersistentBox::open) checks the header before giving access
+/// to the value.
+///
+/// Changes made through the box reach the file when the operating system
+/// writes them back, or when they are flushed with
+/// [`flush`](PersistentBox::flush), or with
+/// [`flush_range`](PersistentBox::flush_range) for part of the value.
+/// Flushing makes changes durable, but not atomic: a crash during an update
+/// may leave the value partly updated.
+///
+/// The value is never dropped, since it lives on in the file.
+///
+/// # Example
+///
+/// ```
+/// use std::fs::File;
+/// use inplace::init;
+/// use inplace::mmap::PersistentBox;
+///
+/// #[repr(C)]
+/// struct Index {
+///     entries: u64,
+///     buckets: [u32; 1 << 16],
+/// }
+///
+/// const SCHEMA: u64 = 3;
+///
+/// let path = std::env::temp_dir().join(format!("inplace-doc-{}", std::process::id()));
+/// let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+/// let index = init!(Index { entries: 0, buckets <- init::zeroed() });
+/// let mut created: PersistentBox<Index> = unsafe { PersistentBox::create(file, SCHEMA, index)? };
+/// created.buckets[42] = 7;
+/// created.entries = 1;
+/// PersistentBox::flush(&created)?;
+/// drop(created);
+///
+/// // Usually in a later run.
+/// let file = File::options().read(true).write(true).open(&path)?;
+/// let reopened = unsafe { PersistentBox::<Index>::open(file, SCHEMA)? };
+/// assert_eq!((reopened.entries, reopened.buckets[42]), (1, 7));
+///
+/// let file = File::options().read(true).write(true).open(&path)?;
+/// let Err(stale) = (unsafe { PersistentBox::<Index>::open(file, SCHEMA - 1) }) else {
+///     panic!()
+/// };
+/// assert_eq!(stale.kind(), std::io::ErrorKind::InvalidData);
+/// # drop(reopened);
+/// # std::fs::remove_file(path)?;
+/// # Ok::<_, std::io::Error>(())
+/// ```
+pub struct PersistentBox<T> {
+    map: MmapMut,
+    file: File,
+    value: NonNull<T>,
+    marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for PersistentBox<T> {}
+unsafe impl<T: Sync> Sync for PersistentBox<T> {}
+
+impl<T> PersistentBox<T> {
+    /// Constructs the value in `file`, which must be empty, and flushes it
+    /// to the file with a header holding `tag`.
+    ///
+    /// An error of kind [`AlreadyExists`](io::ErrorKind::AlreadyExists) is
+    /// returned if the file is not empty. If the initializer panics, the file
+    /// is left marked as incomplete, and opening it returns an error.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, truncated or mapped by anything else,
+    /// in this process or another, while the `PersistentBox` exists.
+    pub unsafe fn create<I>(file: File, tag: u64, init: I) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let (layout, offset) = file_layout::<T>();
+        if file.metadata()?.len() != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "file is not empty",
+            ));
+        }
+        file.set_len(layout.size() as u64)?;
+        let mut map = MmapOptions::new().len(layout.size()).map_mut(&file)?;
+        let base = map.as_mut_ptr();
+        if base.align_offset(layout.align()) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "value is aligned more strictly than a page",
+            ));
+        }
+
+        let header = base.cast::<Header>();
+        header.write(Header {
+            magic: MAGIC,
+            size: mem::size_of::<T>() as u64,
+            align: mem::align_of::<T>() as u64,
+            tag,
+            state: CREATING,
+        });
+        let value = base.add(offset).cast::<T>();
+        init.initialize(value);
+        // The value must reach the file before the header says it has.
+        map.flush()?;
+        ptr::addr_of_mut!((*header).state).write(READY);
+        map.flush_range(0, mem::size_of::<Header>())?;
+        file.sync_all()?;
+
+        Ok(PersistentBox {
+            map,
+            file,
+            value: NonNull::new_unchecked(value),
+            marker: PhantomData,
+        })
+    }
+
+    /// Opens a value created in `file` by a `PersistentBox` with the same
+    /// `tag`.
+    ///
+    /// An error of kind [`InvalidData`](io::ErrorKind::InvalidData) is
+    /// returned if the file was not made by a `PersistentBox` of a type with
+    /// the same layout, if its tag differs, or if its construction never
+    /// finished.
+    ///
+    /// # Safety
+    ///
+    /// The file must have been created for the same type `T`, by a build
+    /// which lays it out identically, and `T` must hold nothing meaningful
+    /// to only one process, such as pointers or handles. The file must not
+    /// be modified, truncated or mapped by anything else while the
+    /// `PersistentBox` exists.
+    pub unsafe fn open(file: File, tag: u64) -> io::Result<Self> {
+        let (layout, offset) = file_layout::<T>();
+        if file.metadata()?.len() != layout.size() as u64 {
+            return Err(invalid("file holds a value of a different size"));
+        }
+        let mut map = MmapOptions::new().len(layout.size()).map_mut(&file)?;
+        let base = map.as_mut_ptr();
+        let header = &*base.cast::<Header>();
+        if header.magic != MAGIC
+            || header.size != mem::size_of::<T>() as u64
+            || header.align != mem::align_of::<T>() as u64
+        {
+            return Err(invalid("file holds a value of a different type"));
+        }
+        if header.tag != tag {
+            return Err(invalid("file holds a value with a different tag"));
+        }
+        if header.state != READY {
+            return Err(invalid("persistent value was never fully constructed"));
+        }
+
+        Ok(PersistentBox {
+            map,
+            file,
+            value: NonNull::new_unchecked(base.add(offset).cast()),
+            marker: PhantomData,
+        })
+    }
+
+    /// Writes changes to the value back to the file, and waits until they
+    /// are durable.
+    pub fn flush(this: &Self) -> io::Result<()> {
+        this.map.flush()
+    }
+
+    /// Starts writing changes to the value back to the file, without waiting
+    /// for them.
+    pub fn flush_async(this: &Self) -> io::Result<()> {
+        this.map.flush_async()
+    }
+
+    /// Returns the file holding the value.
+    pub fn file(this: &Self) -> &File {
+        &this.file
+    }
+
+    /// Writes changes to `len` bytes of the value, starting `offset` bytes
+    /// into it, back to the file, and waits until they are durable.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is not within the value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use inplace::init;
+    /// use inplace::mmap::PersistentBox;
+    ///
+    /// let path = std::env::temp_dir().join(format!("inplace-persist-{}", std::process::id()));
+    /// let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+    /// let mut log: PersistentBox<[u8; 1 << 20]> =
+    ///     unsafe { PersistentBox::create(file, 0, init::zeroed())? };
+    /// log[8192..8196].copy_from_slice(b"done");
+    /// PersistentBox::flush_range(&log, 8192, 4)?;
+    /// # drop(log);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    pub fn flush_range(this: &Self, offset: usize, len: usize) -> io::Result<()> {
+        this.map
+            .flush_range(value_range(&this.map, this.value, offset, len), len)
+    }
+
+    /// Advises the kernel how the mapping will be accessed.
+    #[cfg(unix)]
+    pub fn advise(this: &Self, advice: Advice) -> io::Result<()> {
+        this.map.advise(advice)
+    }
+}
+
+impl<T> Deref for PersistentBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T> DerefMut for PersistentBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PersistentBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,313 @@
+//! Values shared between processes through shared memory.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::fd::FromRawFd;
+
+use memmap2::{Advice, MmapMut, MmapOptions};
+
+use crate::init::Init;
+
+/// Identifies a region made by [`SharedBox`].
+const MAGIC: u64 = u64::from_le_bytes(*b"inplace\0");
+
+/// The region is being sized or its value constructed.
+const CREATING: u32 = 0;
+/// The value has been constructed, and may be used.
+const READY: u32 = 1;
+/// The initializer panicked, so there is no value.
+const POISONED: u32 = 2;
+
+/// The start of a shared region, before the value.
+///
+/// A new region is filled with zeroes, so it reads as `CREATING` until the
+/// creator has finished and stored `READY`.
+#[repr(C)]
+struct Header {
+    magic: u64,
+    size: u64,
+    align: u64,
+    state: AtomicU32,
+}
+
+/// Returns the layout of a region holding a `T`, and the offset of the value.
+fn region_layout<T>() -> (Layout, usize) {
+    let (layout, offset) = Layout::new::<Header>()
+        .extend(Layout::new::<T>())
+        .expect("shared value is too large");
+    (layout.pad_to_align(), offset)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Stores `POISONED` if the initializer unwinds.
+struct Poison<'a>(&'a AtomicU32);
+
+impl Drop for Poison<'_> {
+    fn drop(&mut self) {
+        self.0.store(POISONED, Ordering::Release);
+    }
+}
+
+/// A `repr(C)` value constructed in place in memory shared with other
+/// processes.
+///
+/// One process creates the region and constructs the value, and any number
+/// of others attach to it, by name with [`create`](SharedBox::create) and
+/// [`attach`](SharedBox::attach), or through a file descriptor passed down to
+/// a child or over a socket. The value is preceded by a header recording its
+/// layout and whether construction has finished, so an attaching process
+/// never sees a value which is still being built.
+///
+/// Every process sees the value only through a shared reference, so `T`
+/// coordinates access itself, with atomics for example. The value is never
+/// dropped, since any process may still be using it.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use inplace::init;
+/// use inplace::mmap::SharedBox;
+///
+/// #[repr(C)]
+/// struct Counters {
+///     hits: [AtomicU64; 1024],
+/// }
+///
+/// let name = format!("/inplace-doc-{}", std::process::id());
+/// let counters = init!(Counters {
+///     hits <- init::with(|| [const { AtomicU64::new(0) }; 1024]),
+/// });
+/// let created: SharedBox<Counters> = SharedBox::create(&name, counters)?;
+/// created.hits[7].fetch_add(1, Ordering::Relaxed);
+///
+/// // Usually in another process.
+/// let attached = unsafe { SharedBox::<Counters>::attach(&name)? };
+/// assert_eq!(attached.hits[7].load(Ordering::Relaxed), 1);
+/// SharedBox::<Counters>::unlink(&name)?;
+/// # Ok::<_, std::io::Error>(())
+/// ```
+pub struct SharedBox<T> {
+    map: MmapMut,
+    file: File,
+    value: NonNull<T>,
+    marker: PhantomData<T>,
+}
+
+unsafe impl<T: Sync> Send for SharedBox<T> {}
+unsafe impl<T: Sync> Sync for SharedBox<T> {}
+
+impl<T> SharedBox<T> {
+    /// Creates the POSIX shared memory object `name`, and constructs the
+    /// value in it.
+    ///
+    /// The name must begin with a slash and contain no others. It is an
+    /// error if an object of that name already exists. The object persists
+    /// until it is removed with [`unlink`](SharedBox::unlink).
+    ///
+    /// If the object cannot be sized or mapped, it is removed again. If the
+    /// initializer panics, the object is left marked as failed, and
+    /// attaching to it returns an error.
+    pub fn create<I>(name: &str, init: I) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let file = shm_open(name, libc::O_CREAT | libc::O_EXCL | libc::O_RDWR)?;
+        unsafe { Self::create_in_file(file, init) }.inspect_err(|_| {
+            let _ = Self::unlink(name);
+        })
+    }
+
+    /// Creates an anonymous shared memory file, and constructs the value in
+    /// it.
+    ///
+    /// Other processes attach with [`attach_file`](SharedBox::attach_file),
+    /// given the descriptor of [`file`](SharedBox::file). It is closed on
+    /// `exec`, so must be inherited by `fork` or sent over a socket.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn create_anonymous<I>(init: I) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let fd = unsafe { libc::memfd_create(c"inplace".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        unsafe { Self::create_in_file(File::from_raw_fd(fd), init) }
+    }
+
+    /// Constructs the value in `file`, which must be empty, and publishes it
+    /// to processes attached to the same file.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may modify the file, other than through a `SharedBox`,
+    /// while it is mapped.
+    pub unsafe fn create_in_file<I>(file: File, init: I) -> io::Result<Self>
+    where
+        I: Init<T>,
+    {
+        let (layout, offset) = region_layout::<T>();
+        if file.metadata()?.len() != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "shared memory is already in use",
+            ));
+        }
+        file.set_len(layout.size() as u64)?;
+        let mut map = MmapOptions::new().len(layout.size()).map_mut(&file)?;
+        let base = map.as_mut_ptr();
+        if base.align_offset(layout.align()) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "value is aligned more strictly than a page",
+            ));
+        }
+
+        let header = base.cast::<Header>();
+        (*header).magic = MAGIC;
+        (*header).size = mem::size_of::<T>() as u64;
+        (*header).align = mem::align_of::<T>() as u64;
+        let state = &(*header).state;
+        let value = init.dest(base.add(offset));
+        let poison = Poison(state);
+        init.initialize(value);
+        mem::forget(poison);
+        state.store(READY, Ordering::Release);
+
+        Ok(SharedBox {
+            map,
+            file,
+            value: NonNull::new_unchecked(value),
+            marker: PhantomData,
+        })
+    }
+
+    /// Attaches to the POSIX shared memory object `name`.
+    ///
+    /// If the creator has not yet finished constructing the value, an error
+    /// of kind [`WouldBlock`](io::ErrorKind::WouldBlock) is returned, and
+    /// attaching can be tried again later. An error of kind
+    /// [`InvalidData`](io::ErrorKind::InvalidData) is returned if the region
+    /// was not made by a `SharedBox` of a type with the same layout, or if
+    /// its initializer panicked.
+    ///
+    /// # Safety
+    ///
+    /// The region must have been created for the same type `T`, by a build
+    /// which lays it out identically, and `T` must hold nothing meaningful
+    /// to only one process, such as pointers or handles.
+    pub unsafe fn attach(name: &str) -> io::Result<Self> {
+        Self::attach_file(shm_open(name, libc::O_RDWR)?)
+    }
+
+    /// Attaches to a region created in `file` by another `SharedBox`.
+    ///
+    /// Errors are reported as for [`attach`](SharedBox::attach).
+    ///
+    /// # Safety
+    ///
+    /// As for [`attach`](SharedBox::attach).
+    pub unsafe fn attach_file(file: File) -> io::Result<Self> {
+        let (layout, offset) = region_layout::<T>();
+        let len = file.metadata()?.len();
+        if len == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        if len != layout.size() as u64 {
+            return Err(invalid("shared memory holds a value of a different size"));
+        }
+        let mut map = MmapOptions::new().len(layout.size()).map_mut(&file)?;
+        let base = map.as_mut_ptr();
+        let header = &*base.cast::<Header>();
+        match header.state.load(Ordering::Acquire) {
+            CREATING => return Err(io::ErrorKind::WouldBlock.into()),
+            READY => {}
+            _ => return Err(invalid("shared value failed to initialize")),
+        }
+        if header.magic != MAGIC
+            || header.size != mem::size_of::<T>() as u64
+            || header.align != mem::align_of::<T>() as u64
+        {
+            return Err(invalid("shared memory holds a value of a different type"));
+        }
+
+        Ok(SharedBox {
+            map,
+            file,
+            value: NonNull::new_unchecked(base.add(offset).cast()),
+            marker: PhantomData,
+        })
+    }
+
+    /// Removes the POSIX shared memory object `name`.
+    ///
+    /// Processes which have attached keep their mappings.
+    pub fn unlink(name: &str) -> io::Result<()> {
+        let name = shm_name(name)?;
+        if unsafe { libc::shm_unlink(name.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Returns the file holding the region, for passing to another process.
+    pub fn file(this: &Self) -> &File {
+        &this.file
+    }
+
+    /// Advises the kernel how this process will access the region.
+    pub fn advise(this: &Self, advice: Advice) -> io::Result<()> {
+        this.map.advise(advice)
+    }
+
+    /// Locks this process's mapping of the region into memory, so that it is
+    /// not paged out.
+    pub fn lock(this: &Self) -> io::Result<()> {
+        this.map.lock()
+    }
+
+    /// Unlocks this process's mapping, after [`lock`](SharedBox::lock).
+    pub fn unlock(this: &Self) -> io::Result<()> {
+        this.map.unlock()
+    }
+}
+
+fn shm_name(name: &str) -> io::Result<CString> {
+    CString::new(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name contains NUL"))
+}
+
+fn shm_open(name: &str, flags: libc::c_int) -> io::Result<File> {
+    let name = shm_name(name)?;
+    let fd = unsafe { libc::shm_open(name.as_ptr(), flags, 0o600 as libc::c_uint) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+impl<T> Deref for SharedBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SharedBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,159 @@
+//! Parallel construction of large slices with `rayon`.
+//!
+//! Filling a buffer of gigabytes one element at a time is limited by a single
+//! core. [`par_init_slice`] instead splits the destination into disjoint
+//! chunks and constructs the elements of each chunk on the `rayon` thread
+//! pool, still directly in place.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+use crate::init::{Init, PinInit, Prefix};
+
+/// An initializer for a slice whose elements are constructed in parallel.
+///
+/// Created by [`par_init_slice`].
+pub struct ParInitSlice<T, F> {
+    len: usize,
+    f: F,
+    min_len: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates an initializer for a slice of `len` elements, where element `i` is
+/// constructed in place by the initializer `f(i)`, with the elements divided
+/// among the threads of the current `rayon` pool.
+///
+/// If any element's initializer panics, the other chunks are finished, every
+/// element already constructed is dropped, and the panic is propagated to the
+/// caller, so the destination is left uninitialized.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, par, BoxExt};
+///
+/// let pages: Box<[[u64; 512]]> =
+///     Box::emplace(par::par_init_slice(1 << 12, |i| init::with(move || [i as u64; 512])));
+/// assert_eq!(pages[4095][511], 4095);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn par_init_slice<T, I, F>(len: usize, f: F) -> ParInitSlice<T, F>
+where
+    T: Send,
+    F: Fn(usize) -> I + Sync,
+    I: Init<T>,
+{
+    ParInitSlice {
+        len,
+        f,
+        min_len: 1,
+        _marker: PhantomData,
+    }
+}
+
+impl<T, F> ParInitSlice<T, F> {
+    /// Sets the fewest elements which are constructed as one task.
+    ///
+    /// By default the slice is split into about eight chunks for each thread
+    /// in the pool. A larger minimum reduces the overhead of scheduling
+    /// initializers which are cheap.
+    pub fn with_min_len(mut self, min_len: usize) -> Self {
+        self.min_len = min_len.max(1);
+        self
+    }
+}
+
+/// A pointer to elements which are constructed on other threads.
+struct Start<T>(*mut T);
+
+impl<T> Clone for Start<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Start<T> {}
+
+unsafe impl<T: Send> Send for Start<T> {}
+unsafe impl<T: Send> Sync for Start<T> {}
+
+/// A run of constructed elements, which are dropped unless the run is
+/// forgotten or merged with the run after it.
+struct Run<T>(Prefix<T>);
+
+unsafe impl<T: Send> Send for Run<T> {}
+
+/// Constructs elements `offset..offset + len`, returning them as a run.
+///
+/// If either half panics, `rayon::join` waits for the other, whose run is
+/// then dropped with its elements while the panic propagates.
+fn construct<T, I, F>(start: Start<T>, offset: usize, len: usize, min_len: usize, f: &F) -> Run<T>
+where
+    T: Send,
+    F: Fn(usize) -> I + Sync,
+    I: Init<T>,
+{
+    if len <= min_len || len < 2 {
+        let mut guard = Prefix {
+            start: unsafe { start.0.add(offset) },
+            len: 0,
+        };
+        while guard.len < len {
+            unsafe { f(offset + guard.len).initialize(guard.start.add(guard.len)) };
+            guard.len += 1;
+        }
+        return Run(guard);
+    }
+    let half = len / 2;
+    let (left, right) = rayon::join(
+        || construct(start, offset, half, min_len, f),
+        || construct(start, offset + half, len - half, min_len, f),
+    );
+    let merged = Prefix {
+        start: left.0.start,
+        len: left.0.len + right.0.len,
+    };
+    mem::forget(left);
+    mem::forget(right);
+    Run(merged)
+}
+
+unsafe impl<T, I, F> PinInit<[T]> for ParInitSlice<T, F>
+where
+    T: Send,
+    F: Fn(usize) -> I + Sync,
+    I: Init<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut [T]) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl<T, I, F> Init<[T]> for ParInitSlice<T, F>
+where
+    T: Send,
+    F: Fn(usize) -> I + Sync,
+    I: Init<T>,
+{
+    unsafe fn initialize(self, dest: *mut [T]) {
+        let chunks = rayon::current_num_threads().saturating_mul(8);
+        let min_len = self.min_len.max(self.len / chunks);
+        let run = construct(Start(dest.cast()), 0, self.len, min_len, &self.f);
+        mem::forget(run);
+    }
+}
@@ -0,0 +1,78 @@
+//! Destructors for pinned values.
+//!
+//! A struct deriving [`PinInit`](crate::PinInit) cannot implement `Drop`,
+//! since `Drop::drop` receives `&mut Self` and could move the struct's pinned
+//! fields. It can instead be marked `#[pinned_drop]` and implement
+//! [`PinnedDrop`], whose destructor receives `Pin<&mut Self>`. This is what an
+//! intrusive structure needs to unlink itself safely when it is dropped.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(not(inplace_no_global_oom_handling))]
+//! # fn main() {
+//! use core::cell::Cell;
+//! use core::marker::PhantomPinned;
+//! use core::pin::Pin;
+//! use inplace::pin::{OnlyCallFromDrop, PinnedDrop};
+//! use inplace::{pin_init, BoxExt, PinInit};
+//!
+//! struct Registration {
+//!     _pin: PhantomPinned,
+//! }
+//!
+//! #[derive(PinInit)]
+//! #[pinned_drop]
+//! struct Device<'a> {
+//!     #[pin]
+//!     registration: Registration,
+//!     unregistered: &'a Cell<bool>,
+//! }
+//!
+//! impl PinnedDrop for Device<'_> {
+//!     fn drop(self: Pin<&mut Self>, _: OnlyCallFromDrop) {
+//!         self.unregistered.set(true);
+//!     }
+//! }
+//!
+//! let unregistered = Cell::new(false);
+//! let device: Pin<Box<Device>> = Box::pin_emplace(pin_init!(Device {
+//!     registration: Registration { _pin: PhantomPinned },
+//!     unregistered: &unregistered,
+//! }));
+//! drop(device);
+//! assert!(unregistered.get());
+//! # }
+//! # #[cfg(inplace_no_global_oom_handling)]
+//! # fn main() {}
+//! ```
+
+use core::pin::Pin;
+
+/// A destructor which receives the value pinned.
+///
+/// This is implemented in place of `Drop` by structs which derive
+/// [`PinInit`](crate::PinInit) and are marked `#[pinned_drop]`. The derive
+/// generates a `Drop` implementation which calls [`PinnedDrop::drop`], after
+/// which the fields are dropped as usual.
+pub trait PinnedDrop {
+    /// Runs the destructor.
+    ///
+    /// This can only be called by the generated `Drop` implementation, which
+    /// alone can provide an [`OnlyCallFromDrop`].
+    fn drop(self: Pin<&mut Self>, only_call_from_drop: OnlyCallFromDrop);
+}
+
+/// A token proving that [`PinnedDrop::drop`] is being called from `Drop`.
+pub struct OnlyCallFromDrop(());
+
+impl OnlyCallFromDrop {
+    /// # Safety
+    ///
+    /// Must only be called by the `Drop` implementation generated for a
+    /// `#[pinned_drop]` struct.
+    #[doc(hidden)]
+    pub unsafe fn new() -> Self {
+        OnlyCallFromDrop(())
+    }
+}
@@ -0,0 +1,227 @@
+//! A fixed-capacity pool of values constructed in place.
+//!
+//! A [`Pool`] holds storage for a fixed number of values, reserved when the
+//! pool is created, and constructs each value in a free slot when it is
+//! acquired. Releasing the value drops it in place and returns the slot.
+//! Neither acquiring nor releasing allocates, takes a lock or moves the value,
+//! which makes a pool suitable for realtime code such as audio callbacks, as
+//! long as the initializers used are themselves realtime-safe.
+
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::init::Init;
+
+/// Marks the end of the free list.
+const NONE: usize = usize::MAX;
+
+/// A pool of `N` slots for values of type `T`.
+///
+/// The storage is part of the pool, so a large pool should itself be
+/// emplaced, for example in a `Box` or a `static`, before realtime use begins.
+///
+/// # Example
+///
+/// ```
+/// use inplace::init;
+/// use inplace::pool::Pool;
+///
+/// struct Voice {
+///     phase: f32,
+///     buffer: [f32; 512],
+/// }
+///
+/// let voices: Pool<Voice, 16> = Pool::new();
+/// let voice = voices
+///     .acquire(init!(Voice { phase: 0.0, buffer <- init::with(|| [0.0; 512]) }))
+///     .ok()
+///     .unwrap();
+/// assert_eq!(voices.len(), 1);
+/// drop(voice);
+/// assert!(voices.is_empty());
+/// ```
+pub struct Pool<T, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// For each free slot, the index of the next free slot.
+    next: [Cell<usize>; N],
+    /// The first free slot which has been used before.
+    free: Cell<usize>,
+    /// The first slot which has never been used.
+    fresh: Cell<usize>,
+    len: Cell<usize>,
+}
+
+unsafe impl<T: Send, const N: usize> Send for Pool<T, N> {}
+
+impl<T, const N: usize> Pool<T, N> {
+    /// Creates an empty pool.
+    pub const fn new() -> Self {
+        Pool {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            next: [const { Cell::new(NONE) }; N],
+            free: Cell::new(NONE),
+            fresh: Cell::new(0),
+            len: Cell::new(0),
+        }
+    }
+
+    /// Returns the number of slots in the pool.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of values currently in the pool.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Returns true if the pool holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if every slot holds a value.
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Takes a free slot, returning its index.
+    fn take(&self) -> Option<usize> {
+        let index = match self.free.get() {
+            NONE if self.fresh.get() < N => {
+                let index = self.fresh.get();
+                self.fresh.set(index + 1);
+                index
+            }
+            NONE => return None,
+            index => {
+                self.free.set(self.next[index].get());
+                index
+            }
+        };
+        self.len.set(self.len.get() + 1);
+        Some(index)
+    }
+
+    /// Returns a slot to the free list.
+    fn give(&self, index: usize) {
+        self.next[index].set(self.free.get());
+        self.free.set(index);
+        self.len.set(self.len.get() - 1);
+    }
+
+    /// Constructs a value in a free slot.
+    ///
+    /// If every slot is in use, the initializer is returned. If it panics,
+    /// the slot is returned to the pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::pool::Pool;
+    ///
+    /// let pool: Pool<String, 1> = Pool::new();
+    /// let first = pool.acquire(String::from("first")).ok().unwrap();
+    /// assert_eq!(pool.acquire(String::from("second")).err().unwrap(), "second");
+    /// drop(first);
+    /// assert!(pool.acquire(String::from("third")).is_ok());
+    /// ```
+    pub fn acquire<I>(&self, init: I) -> Result<PoolBox<'_, T, N>, I>
+    where
+        I: Init<T>,
+    {
+        let Some(index) = self.take() else {
+            return Err(init);
+        };
+        let guard = Give { pool: self, index };
+        let dest = self.slots[index].get().cast::<T>();
+        unsafe { init.initialize(dest) };
+        mem::forget(guard);
+        Ok(PoolBox {
+            pool: self,
+            index,
+            value: unsafe { NonNull::new_unchecked(dest) },
+        })
+    }
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Pool<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns a slot to the pool if its initializer panics.
+struct Give<'a, T, const N: usize> {
+    pool: &'a Pool<T, N>,
+    index: usize,
+}
+
+impl<T, const N: usize> Drop for Give<'_, T, N> {
+    fn drop(&mut self) {
+        self.pool.give(self.index)
+    }
+}
+
+/// A value in a [`Pool`], which is dropped in place and its slot released
+/// when this goes out of scope.
+///
+/// If a `PoolBox` is forgotten, its slot is never released, and its value is
+/// never dropped.
+pub struct PoolBox<'a, T, const N: usize> {
+    pool: &'a Pool<T, N>,
+    index: usize,
+    value: NonNull<T>,
+}
+
+impl<T, const N: usize> PoolBox<'_, T, N> {
+    /// Moves the value out of the pool, releasing its slot.
+    pub fn into_inner(this: Self) -> T {
+        let this = mem::ManuallyDrop::new(this);
+        let value = unsafe { this.value.as_ptr().read() };
+        this.pool.give(this.index);
+        value
+    }
+}
+
+impl<T, const N: usize> Drop for PoolBox<'_, T, N> {
+    fn drop(&mut self) {
+        let _give = Give {
+            pool: self.pool,
+            index: self.index,
+        };
+        unsafe { self.value.as_ptr().drop_in_place() }
+    }
+}
+
+impl<T, const N: usize> Deref for PoolBox<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T, const N: usize> DerefMut for PoolBox<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for PoolBox<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,11 @@
+//! Bounded queues whose elements are constructed in place in their slots.
+//!
+//! Sending a large message through a queue normally builds it on the
+//! sender's stack and then moves it into the queue's storage. These queues
+//! instead let the sender claim a slot first and run an initializer directly
+//! in it, so the message is constructed once, where the receiver will read
+//! it.
+
+pub mod mpmc;
+mod primitives;
+pub mod spsc;
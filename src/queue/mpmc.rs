@@ -0,0 +1,438 @@
+//! A bounded multi-producer multi-consumer queue.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+
+use super::primitives::{self, AtomicUsize, Ordering, UnsafeCell};
+use crate::init::{self, Init, TryInit};
+
+/// A slot of a [`Queue`].
+struct Slot<T> {
+    /// The stamp of the position the slot is free for, or one more than the
+    /// stamp of the position whose value it holds.
+    stamp: AtomicUsize,
+    /// Whether the value was constructed, or the producer which claimed the
+    /// slot gave it up.
+    full: UnsafeCell<bool>,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A queue of up to `N` values of type `T`, shared by any number of producers
+/// and consumers.
+///
+/// As in the array queue of `crossbeam`, the head and tail are stamps which
+/// count laps around the slots, and each slot has a stamp saying whether it
+/// is free for the current lap or holds a value. A producer claims the slot
+/// at the tail by advancing the tail past it, constructs a value directly in
+/// the slot, and then commits it by advancing the slot's stamp; consumers
+/// claim values from the head in the same way. No value is ever built
+/// elsewhere and moved in.
+///
+/// A slot whose value is still being constructed holds up the consumers, so
+/// [`pop`](Queue::pop) reports the queue as empty until it is committed.
+/// Likewise, a slot whose value is still being read holds up the producers.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::thread;
+/// use inplace::queue::mpmc::Queue;
+/// use inplace::{init, BoxExt};
+///
+/// struct Frame {
+///     seq: u32,
+///     pixels: [u8; 1 << 16],
+/// }
+///
+/// let queue: Box<Queue<Frame, 8>> = Box::emplace(Queue::new());
+/// let total = thread::scope(|s| {
+///     for producer in 0..4 {
+///         let queue = &queue;
+///         s.spawn(move || {
+///             for n in 0..25 {
+///                 let seq = producer * 25 + n;
+///                 let mut frame = init!(Frame { seq, pixels <- init::filled(seq as u8) });
+///                 while let Err(rejected) = queue.push_init(frame) {
+///                     frame = rejected;
+///                     thread::yield_now();
+///                 }
+///             }
+///         });
+///     }
+///     let consumers: Vec<_> = (0..2)
+///         .map(|_| {
+///             s.spawn(|| {
+///                 let mut sum = 0;
+///                 for _ in 0..50 {
+///                     let frame = loop {
+///                         match queue.pop_in_place() {
+///                             Some(frame) => break frame,
+///                             None => thread::yield_now(),
+///                         }
+///                     };
+///                     assert_eq!(frame.pixels[1 << 15], frame.seq as u8);
+///                     sum += frame.seq;
+///                 }
+///                 sum
+///             })
+///         })
+///         .collect();
+///     consumers.into_iter().map(|c| c.join().unwrap()).sum::<u32>()
+/// });
+/// assert_eq!(total, (0..100).sum());
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub struct Queue<T, const N: usize> {
+    slots: [Slot<T>; N],
+    /// The stamp of the next position to be read.
+    head: AtomicUsize,
+    /// The stamp of the next position to be written.
+    tail: AtomicUsize,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// The stamp difference of a lap: the index of a position is in the low
+    /// bits of its stamp, and the lap in the rest.
+    const ONE_LAP: usize = (N + 1).next_power_of_two();
+
+    /// Creates an initializer for an empty queue.
+    ///
+    /// `N` must be nonzero and at most half of `usize::MAX`, which is checked
+    /// at compile time.
+    pub fn new() -> impl Init<Self> {
+        const { assert!(N > 0 && N <= usize::MAX / 2, "invalid queue capacity") };
+        crate::init!(Queue {
+            slots <- init::array_from_fn(|index| {
+                crate::init!(Slot {
+                    stamp: AtomicUsize::new(index),
+                    full: UnsafeCell::new(false),
+                    value <- primitives::uninit_cell(),
+                })
+            }),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the number of slots in the queue.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of claimed slots in the queue.
+    ///
+    /// This counts values still being constructed, and may be out of date as
+    /// soon as it returns.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            let head = self.head.load(Ordering::Acquire);
+            // Retry if a producer moved the tail in between.
+            if self.tail.load(Ordering::Acquire) != tail {
+                continue;
+            }
+            let head_index = head & (Self::ONE_LAP - 1);
+            let tail_index = tail & (Self::ONE_LAP - 1);
+            return if head_index < tail_index {
+                tail_index - head_index
+            } else if head_index > tail_index {
+                N - head_index + tail_index
+            } else if head == tail {
+                0
+            } else {
+                N
+            };
+        }
+    }
+
+    /// Returns true if no slots are claimed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the stamp of the position after `stamp`.
+    fn next(stamp: usize) -> usize {
+        let index = stamp & (Self::ONE_LAP - 1);
+        if index + 1 < N {
+            stamp + 1
+        } else {
+            (stamp & !(Self::ONE_LAP - 1)).wrapping_add(Self::ONE_LAP)
+        }
+    }
+
+    fn slot(&self, stamp: usize) -> &Slot<T> {
+        &self.slots[stamp & (Self::ONE_LAP - 1)]
+    }
+
+    /// Reserves the slot at the tail, if it is free.
+    ///
+    /// The slot must be committed by constructing a value in it, and no
+    /// consumer can read past it until it is, so the value should be
+    /// constructed promptly. Dropping the reservation without constructing a
+    /// value commits the slot empty, and consumers skip over it.
+    pub fn reserve(&self) -> Option<Reserved<'_, T, N>> {
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let stamp = self.slot(tail).stamp.load(Ordering::Acquire);
+            if stamp == tail {
+                // The slot is free for this lap, so claim it.
+                match self.tail.compare_exchange_weak(
+                    tail,
+                    Self::next(tail),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        return Some(Reserved {
+                            queue: self,
+                            stamp: tail,
+                            _marker: PhantomData,
+                        })
+                    }
+                    Err(current) => tail = current,
+                }
+            } else if stamp.wrapping_add(Self::ONE_LAP) == tail.wrapping_add(1) {
+                // The slot still holds a value from the last lap.
+                return None;
+            } else {
+                // Another producer claimed the slot first.
+                primitives::spin();
+                tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Constructs a value in the slot at the tail and commits it.
+    ///
+    /// If the slot is not free, the initializer is returned.
+    pub fn push_init<I>(&self, init: I) -> Result<(), I>
+    where
+        I: Init<T>,
+    {
+        match self.reserve() {
+            Some(slot) => {
+                slot.emplace(init);
+                Ok(())
+            }
+            None => Err(init),
+        }
+    }
+
+    /// Claims the value at the head, without moving it out of its slot.
+    ///
+    /// The value is dropped in place, and its slot released, when the
+    /// returned guard is dropped. Returns `None` if the queue is empty, or
+    /// the value at the head is still being constructed.
+    pub fn pop_in_place(&self) -> Option<Popped<'_, T, N>> {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(head);
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            if stamp == head.wrapping_add(1) {
+                // The slot has been committed, so claim it.
+                match self.head.compare_exchange_weak(
+                    head,
+                    Self::next(head),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        if slot.full.with_mut(|full| unsafe { *full }) {
+                            return Some(Popped {
+                                queue: self,
+                                stamp: head,
+                                _marker: PhantomData,
+                            });
+                        }
+                        // The producer gave the slot up, so skip it.
+                        let next_lap = head.wrapping_add(Self::ONE_LAP);
+                        slot.stamp.store(next_lap, Ordering::Release);
+                        head = self.head.load(Ordering::Relaxed);
+                    }
+                    Err(current) => head = current,
+                }
+            } else if stamp == head {
+                // The slot has not been committed for this lap.
+                return None;
+            } else {
+                // Another consumer claimed the slot first.
+                primitives::spin();
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Moves the value at the head out of the queue.
+    ///
+    /// Returns `None` if the queue is empty, or the value at the head is
+    /// still being constructed.
+    pub fn pop(&self) -> Option<T> {
+        self.pop_in_place().map(Popped::into_inner)
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Queue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        for (index, slot) in self.slots.iter().enumerate() {
+            let stamp = slot.stamp.load(Ordering::Relaxed);
+            let committed = stamp & (Self::ONE_LAP - 1) == index + 1;
+            if committed && slot.full.with_mut(|full| unsafe { *full }) {
+                slot.value
+                    .with_mut(|value| unsafe { ptr::drop_in_place(value.cast::<T>()) });
+            }
+        }
+    }
+}
+
+/// A slot of a [`Queue`] reserved by a producer.
+///
+/// Created by [`Queue::reserve`]. Dropping it commits the slot empty.
+pub struct Reserved<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+    stamp: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T, const N: usize> Reserved<'_, T, N> {
+    /// Constructs a value in place in the slot, and commits it.
+    ///
+    /// If the initializer panics, the slot is committed empty.
+    pub fn emplace<I>(self, init: I)
+    where
+        I: Init<T>,
+    {
+        let slot = self.queue.slot(self.stamp);
+        slot.value
+            .with_mut(|value| unsafe { init.initialize(value.cast()) });
+        self.commit(true);
+        mem::forget(self);
+    }
+
+    /// Constructs a value in place in the slot with a fallible initializer,
+    /// and commits it.
+    ///
+    /// If the initializer fails, the slot is committed empty and the error
+    /// returned.
+    pub fn try_emplace<I, E>(self, init: I) -> Result<(), E>
+    where
+        I: TryInit<T, E>,
+    {
+        let slot = self.queue.slot(self.stamp);
+        slot.value
+            .with_mut(|value| unsafe { init.try_initialize(value.cast()) })?;
+        self.commit(true);
+        mem::forget(self);
+        Ok(())
+    }
+
+    /// Makes the slot available to consumers.
+    fn commit(&self, full: bool) {
+        let slot = self.queue.slot(self.stamp);
+        slot.full.with_mut(|flag| unsafe { *flag = full });
+        slot.stamp
+            .store(self.stamp.wrapping_add(1), Ordering::Release);
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Reserved<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reserved").finish_non_exhaustive()
+    }
+}
+
+impl<T, const N: usize> Drop for Reserved<'_, T, N> {
+    fn drop(&mut self) {
+        self.commit(false)
+    }
+}
+
+/// The value at the head of a [`Queue`], claimed by a consumer and read in
+/// place.
+///
+/// Created by [`Queue::pop_in_place`].
+pub struct Popped<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+    stamp: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T, const N: usize> Popped<'_, T, N> {
+    /// Moves the value out of the queue.
+    pub fn into_inner(this: Self) -> T {
+        let this = mem::ManuallyDrop::new(this);
+        let value = this
+            .value()
+            .with_mut(|value| unsafe { value.read().assume_init() });
+        this.release();
+        value
+    }
+
+    fn value(&self) -> &UnsafeCell<MaybeUninit<T>> {
+        &self.queue.slot(self.stamp).value
+    }
+
+    /// Frees the slot for the next lap.
+    fn release(&self) {
+        self.queue.slot(self.stamp).stamp.store(
+            self.stamp.wrapping_add(Queue::<T, N>::ONE_LAP),
+            Ordering::Release,
+        );
+    }
+}
+
+impl<T, const N: usize> Deref for Popped<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value()
+            .with_mut(|value| unsafe { &*value.cast::<T>() })
+    }
+}
+
+impl<T, const N: usize> DerefMut for Popped<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value()
+            .with_mut(|value| unsafe { &mut *value.cast::<T>() })
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for Popped<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T, const N: usize> Drop for Popped<'_, T, N> {
+    fn drop(&mut self) {
+        /// Releases the slot even if dropping the value panics.
+        struct Release<'a, 'b, T, const N: usize>(&'a Popped<'b, T, N>);
+
+        impl<T, const N: usize> Drop for Release<'_, '_, T, N> {
+            fn drop(&mut self) {
+                self.0.release()
+            }
+        }
+
+        let _release = Release(self);
+        self.value()
+            .with_mut(|value| unsafe { ptr::drop_in_place(value.cast::<T>()) })
+    }
+}
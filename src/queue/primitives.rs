@@ -0,0 +1,53 @@
+//! The synchronization primitives of the queues, which are `loom`'s when
+//! model-checking.
+
+use core::mem::MaybeUninit;
+
+use crate::init::Init;
+
+#[cfg(not(inplace_loom))]
+pub(crate) use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(inplace_loom)]
+pub(crate) use loom::sync::atomic::{AtomicUsize, Ordering};
+
+/// A cell for data whose accesses are ordered by atomics.
+#[cfg(not(inplace_loom))]
+#[repr(transparent)]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(inplace_loom))]
+impl<T> UnsafeCell<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        UnsafeCell(core::cell::UnsafeCell::new(value))
+    }
+
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+#[cfg(inplace_loom)]
+pub(crate) use loom::cell::UnsafeCell;
+
+/// Returns an initializer for a cell which leaves its contents uninitialized.
+#[cfg(not(inplace_loom))]
+pub(crate) fn uninit_cell<T>() -> impl Init<UnsafeCell<MaybeUninit<T>>> {
+    unsafe { crate::init::from_raw(|_| {}) }
+}
+
+/// Returns an initializer for a cell which leaves its contents uninitialized.
+///
+/// A `loom` cell tracks its accesses alongside the contents, so it is built
+/// by value.
+#[cfg(inplace_loom)]
+pub(crate) fn uninit_cell<T>() -> impl Init<UnsafeCell<MaybeUninit<T>>> {
+    UnsafeCell::new(MaybeUninit::uninit())
+}
+
+/// Waits for another thread to make progress.
+pub(crate) fn spin() {
+    #[cfg(not(inplace_loom))]
+    core::hint::spin_loop();
+    #[cfg(inplace_loom)]
+    loom::thread::yield_now();
+}
@@ -0,0 +1,392 @@
+//! A bounded single-producer single-consumer ring.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::init::{self, Init, TryInit};
+
+/// A ring of `N` slots for values of type `T`, passed from one producer to
+/// one consumer.
+///
+/// The storage is part of the ring, so the ring is created by an initializer
+/// which leaves the slots uninitialized, and can be emplaced however large
+/// it is. [`split`](Ring::split) then gives the two ends, which may be moved
+/// to different threads. The producer [reserves](Producer::reserve) a slot,
+/// constructs a value in it, and [commits](Staged::commit) it, after which
+/// the consumer can read the value where it was constructed.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::thread;
+/// use inplace::queue::spsc::Ring;
+/// use inplace::{init, BoxExt};
+///
+/// struct Frame {
+///     seq: u32,
+///     pixels: [u8; 1 << 16],
+/// }
+///
+/// let mut ring: Box<Ring<Frame, 8>> = Box::emplace(Ring::new());
+/// let (mut tx, mut rx) = ring.split();
+/// thread::scope(|s| {
+///     s.spawn(move || {
+///         for seq in 0..100 {
+///             let slot = loop {
+///                 match tx.reserve() {
+///                     Some(slot) => break slot,
+///                     None => thread::yield_now(),
+///                 }
+///             };
+///             slot.emplace(init!(Frame { seq, pixels <- init::filled(seq as u8) })).commit();
+///         }
+///     });
+///     let mut next = 0;
+///     while next < 100 {
+///         match rx.front() {
+///             Some(frame) => {
+///                 assert_eq!((frame.seq, frame.pixels[1 << 15]), (next, next as u8));
+///                 next += 1;
+///             }
+///             None => thread::yield_now(),
+///         }
+///     }
+/// });
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub struct Ring<T, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// The position of the next value to be read, modulo `2 * N`.
+    head: AtomicUsize,
+    /// The position of the next slot to be written, modulo `2 * N`.
+    tail: AtomicUsize,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for Ring<T, N> {}
+
+impl<T, const N: usize> Ring<T, N> {
+    /// Creates an initializer for an empty ring.
+    ///
+    /// `N` must be nonzero and at most half of `usize::MAX`, which is checked
+    /// at compile time.
+    pub fn new() -> impl Init<Self> {
+        const { assert!(N > 0 && N <= usize::MAX / 2, "invalid ring capacity") };
+        crate::init!(Ring {
+            // Slots are uninitialized until written.
+            slots <- unsafe { init::from_raw(|_| {}) },
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the number of slots in the ring.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of values in the ring.
+    ///
+    /// While the ring is split, this may be out of date as soon as it
+    /// returns.
+    pub fn len(&self) -> usize {
+        distance::<N>(
+            self.head.load(Ordering::Acquire),
+            self.tail.load(Ordering::Acquire),
+        )
+    }
+
+    /// Returns true if the ring holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits the ring into its producer and consumer ends.
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        let tail = *self.tail.get_mut();
+        let head = *self.head.get_mut();
+        (Producer { ring: self, tail }, Consumer { ring: self, head })
+    }
+
+    fn slot(&self, pos: usize) -> *mut T {
+        self.slots[pos % N].get().cast()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Ring<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ring")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, const N: usize> Drop for Ring<T, N> {
+    fn drop(&mut self) {
+        let tail = *self.tail.get_mut();
+        let mut pos = *self.head.get_mut();
+        while pos != tail {
+            unsafe { ptr::drop_in_place(self.slot(pos)) };
+            pos = advance::<N>(pos);
+        }
+    }
+}
+
+/// Returns the position after `pos`.
+fn advance<const N: usize>(pos: usize) -> usize {
+    if pos + 1 == 2 * N {
+        0
+    } else {
+        pos + 1
+    }
+}
+
+/// Returns the number of positions from `head` to `tail`.
+fn distance<const N: usize>(head: usize, tail: usize) -> usize {
+    if tail >= head {
+        tail - head
+    } else {
+        tail + 2 * N - head
+    }
+}
+
+/// The producer end of a [`Ring`].
+pub struct Producer<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+    tail: usize,
+}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Returns true if every slot holds a value, or is reserved.
+    pub fn is_full(&self) -> bool {
+        distance::<N>(self.ring.head.load(Ordering::Acquire), self.tail) == N
+    }
+
+    /// Reserves the next slot, if the ring is not full.
+    pub fn reserve(&mut self) -> Option<Reserved<'_, T, N>> {
+        if self.is_full() {
+            return None;
+        }
+        Some(Reserved {
+            ring: self.ring,
+            tail: &mut self.tail,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Constructs a value in the next slot and commits it.
+    ///
+    /// If the ring is full, the initializer is returned.
+    pub fn push_init<I>(&mut self, init: I) -> Result<(), I>
+    where
+        I: Init<T>,
+    {
+        match self.reserve() {
+            Some(slot) => {
+                slot.emplace(init).commit();
+                Ok(())
+            }
+            None => Err(init),
+        }
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Producer<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer").finish_non_exhaustive()
+    }
+}
+
+/// A slot of a [`Ring`] reserved by the producer.
+///
+/// Created by [`Producer::reserve`]. Dropping it releases the slot.
+pub struct Reserved<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+    tail: &'a mut usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T, const N: usize> Reserved<'a, T, N> {
+    /// Constructs a value in place in the slot.
+    ///
+    /// If the initializer panics, the slot is released.
+    pub fn emplace<I>(self, init: I) -> Staged<'a, T, N>
+    where
+        I: Init<T>,
+    {
+        unsafe { init.initialize(self.ring.slot(*self.tail)) };
+        Staged {
+            ring: self.ring,
+            tail: self.tail,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constructs a value in place in the slot with a fallible initializer.
+    ///
+    /// If the initializer fails, the slot is released and the error
+    /// returned.
+    pub fn try_emplace<I, E>(self, init: I) -> Result<Staged<'a, T, N>, E>
+    where
+        I: TryInit<T, E>,
+    {
+        unsafe { init.try_initialize(self.ring.slot(*self.tail))? };
+        Ok(Staged {
+            ring: self.ring,
+            tail: self.tail,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// A value constructed in a reserved slot of a [`Ring`], which the consumer
+/// can't see until it is committed.
+///
+/// Created by [`Reserved::emplace`]. Dropping it drops the value and
+/// releases the slot.
+pub struct Staged<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+    tail: &'a mut usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T, const N: usize> Staged<'_, T, N> {
+    /// Makes the value available to the consumer.
+    pub fn commit(self) {
+        let next = advance::<N>(*self.tail);
+        self.ring.tail.store(next, Ordering::Release);
+        *self.tail = next;
+        mem::forget(self);
+    }
+}
+
+impl<T, const N: usize> Deref for Staged<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.ring.slot(*self.tail) }
+    }
+}
+
+impl<T, const N: usize> DerefMut for Staged<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ring.slot(*self.tail) }
+    }
+}
+
+impl<T, const N: usize> Drop for Staged<'_, T, N> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.ring.slot(*self.tail)) }
+    }
+}
+
+/// The consumer end of a [`Ring`].
+pub struct Consumer<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+    head: usize,
+}
+
+impl<T, const N: usize> Consumer<'_, T, N> {
+    /// Returns true if no committed values are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.ring.tail.load(Ordering::Acquire) == self.head
+    }
+
+    /// Returns the oldest value, without moving it out of its slot.
+    ///
+    /// The value is dropped in place, and its slot released, when the
+    /// returned guard is dropped.
+    pub fn front(&mut self) -> Option<Front<'_, T, N>> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(Front {
+            ring: self.ring,
+            head: &mut self.head,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Moves the oldest value out of the ring.
+    pub fn pop(&mut self) -> Option<T> {
+        self.front().map(Front::into_inner)
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Consumer<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer").finish_non_exhaustive()
+    }
+}
+
+/// The oldest value in a [`Ring`], read in place.
+///
+/// Created by [`Consumer::front`].
+pub struct Front<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+    head: &'a mut usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T, const N: usize> Front<'_, T, N> {
+    /// Moves the value out of the ring.
+    pub fn into_inner(this: Self) -> T {
+        let mut this = mem::ManuallyDrop::new(this);
+        let value = unsafe { this.ring.slot(*this.head).read() };
+        this.release();
+        value
+    }
+
+    /// Passes the slot back to the producer.
+    fn release(&mut self) {
+        let next = advance::<N>(*self.head);
+        self.ring.head.store(next, Ordering::Release);
+        *self.head = next;
+    }
+}
+
+impl<T, const N: usize> Deref for Front<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.ring.slot(*self.head) }
+    }
+}
+
+impl<T, const N: usize> DerefMut for Front<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ring.slot(*self.head) }
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for Front<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T, const N: usize> Drop for Front<'_, T, N> {
+    fn drop(&mut self) {
+        /// Releases the slot even if dropping the value panics.
+        struct Release<'a, 'b, T, const N: usize>(&'a mut Front<'b, T, N>);
+
+        impl<T, const N: usize> Drop for Release<'_, '_, T, N> {
+            fn drop(&mut self) {
+                self.0.release()
+            }
+        }
+
+        let slot = self.ring.slot(*self.head);
+        let _release = Release(self);
+        unsafe { ptr::drop_in_place(slot) }
+    }
+}
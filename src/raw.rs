@@ -0,0 +1,112 @@
+//! Allocation helpers shared by the emplacement APIs.
+
+#[cfg(feature = "nightly")]
+use core::alloc::Allocator;
+use core::alloc::Layout;
+use core::ptr::{self, NonNull};
+
+#[cfg(not(inplace_no_global_oom_handling))]
+use alloc::alloc::handle_alloc_error;
+use alloc::alloc::{alloc, dealloc, realloc};
+use alloc::vec::Vec;
+
+/// Allocates memory for `layout`, returning `None` on failure.
+///
+/// Zero-sized layouts are given a dangling, suitably aligned pointer without
+/// allocating, as the standard containers expect.
+pub(crate) fn try_allocate(layout: Layout) -> Option<NonNull<u8>> {
+    if layout.size() == 0 {
+        NonNull::new(ptr::without_provenance_mut(layout.align()))
+    } else {
+        NonNull::new(unsafe { alloc(layout) })
+    }
+}
+
+/// Allocates memory for `layout`, calling the global allocation error handler
+/// on failure.
+#[cfg(not(inplace_no_global_oom_handling))]
+pub(crate) fn allocate(layout: Layout) -> NonNull<u8> {
+    try_allocate(layout).unwrap_or_else(|| handle_alloc_error(layout))
+}
+
+/// Appends `value` to `vec`, whose capacity must already have been reserved.
+///
+/// Unlike `Vec::push`, this never allocates, so it is available without the
+/// global allocation error handler.
+pub(crate) fn push_reserved<T>(vec: &mut Vec<T>, value: T) {
+    let len = vec.len();
+    assert!(len < vec.capacity(), "push_reserved without spare capacity");
+    unsafe {
+        vec.as_mut_ptr().add(len).write(value);
+        vec.set_len(len + 1);
+    }
+}
+
+/// Frees memory obtained from [`try_allocate`] or [`allocate`].
+///
+/// # Safety
+///
+/// `addr` must have been allocated by one of those functions with `layout`.
+pub(crate) unsafe fn deallocate(addr: NonNull<u8>, layout: Layout) {
+    if layout.size() != 0 {
+        dealloc(addr.as_ptr(), layout)
+    }
+}
+
+/// Shrinks memory obtained from [`try_allocate`] or [`allocate`] to `size`
+/// bytes, returning its new address and layout.
+///
+/// If the allocator fails to shrink it, the memory is left as it was.
+///
+/// # Safety
+///
+/// `addr` must have been allocated by one of those functions with `layout`,
+/// and `size` must be no larger than `layout.size()`.
+pub(crate) unsafe fn shrink(
+    addr: NonNull<u8>,
+    layout: Layout,
+    size: usize,
+) -> (NonNull<u8>, Layout) {
+    let new_layout = Layout::from_size_align_unchecked(size, layout.align());
+    if size == layout.size() {
+        (addr, layout)
+    } else if size == 0 {
+        deallocate(addr, layout);
+        (try_allocate(new_layout).unwrap(), new_layout)
+    } else {
+        match NonNull::new(realloc(addr.as_ptr(), layout, size)) {
+            Some(addr) => (addr, new_layout),
+            None => (addr, layout),
+        }
+    }
+}
+
+/// Frees an allocation unless forgotten.
+///
+/// Held while an initializer runs, so that the allocation is released if the
+/// initializer panics.
+pub(crate) struct DeallocGuard {
+    pub(crate) addr: NonNull<u8>,
+    pub(crate) layout: Layout,
+}
+
+impl Drop for DeallocGuard {
+    fn drop(&mut self) {
+        unsafe { deallocate(self.addr, self.layout) }
+    }
+}
+
+/// Frees an allocation made by an [`Allocator`] unless forgotten.
+#[cfg(feature = "nightly")]
+pub(crate) struct DeallocInGuard<'a, A: Allocator> {
+    pub(crate) addr: NonNull<u8>,
+    pub(crate) layout: Layout,
+    pub(crate) alloc: &'a A,
+}
+
+#[cfg(feature = "nightly")]
+impl<A: Allocator> Drop for DeallocInGuard<'_, A> {
+    fn drop(&mut self) {
+        unsafe { self.alloc.deallocate(self.addr, self.layout) }
+    }
+}
@@ -0,0 +1,248 @@
+//! Emplacement into `Rc`.
+
+#[cfg(feature = "nightly")]
+use core::alloc::{Allocator, Layout};
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use core::mem::MaybeUninit;
+#[cfg(not(inplace_no_global_oom_handling))]
+use core::ptr;
+
+use alloc::rc::Rc;
+
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use crate::error::InplaceError;
+#[cfg(not(inplace_no_global_oom_handling))]
+use crate::init::Init;
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use crate::init::TryInit;
+
+/// Extension methods for constructing an `Rc` in place.
+///
+/// This mirrors [`ArcExt`](crate::ArcExt) for single-threaded reference
+/// counting, and is likewise implemented for sized types, slices and `str`.
+pub trait RcExt<T: ?Sized>: Sized {
+    /// Allocates a shared value and constructs it in place.
+    ///
+    /// If the initializer panics, the allocation is freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use inplace::{init, RcExt};
+    ///
+    /// let squares: Rc<[u64]> = Rc::emplace(&[0, 1, 4, 9][..]);
+    /// assert_eq!(squares[3], 9);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<T>;
+
+    /// Allocates a shared value and attempts to construct it in place.
+    ///
+    /// If the initializer fails, the allocation is freed and the error is
+    /// returned as [`InplaceError::Init`]. `Rc` has no stable fallible
+    /// allocation API, so allocation failure aborts, except for sized values
+    /// with the `nightly` feature, where it is reported as
+    /// [`InplaceError::Alloc`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::mem::MaybeUninit;
+    /// use core::num::ParseIntError;
+    /// use std::rc::Rc;
+    /// use inplace::{init, InplaceError, RcExt, TryInit};
+    ///
+    /// fn parse(text: &str) -> impl TryInit<u32, ParseIntError> + '_ {
+    ///     init::try_from_fn(move |slot: &mut MaybeUninit<u32>| Ok(slot.write(text.parse()?)))
+    /// }
+    ///
+    /// let value: Rc<u32> = Rc::try_emplace(parse("17")).unwrap();
+    /// assert_eq!(*value, 17);
+    /// assert!(matches!(Rc::try_emplace(parse("x")), Err(InplaceError::Init(_))));
+    /// ```
+    #[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+}
+
+/// Allocates an uninitialized `Rc`, reporting allocation failure if the
+/// standard library allows it.
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+fn try_new_uninit<T, E>() -> Result<Rc<MaybeUninit<T>>, InplaceError<E>> {
+    #[cfg(feature = "nightly")]
+    return Rc::try_new_uninit().map_err(|_| InplaceError::Alloc(Layout::new::<T>()));
+    #[cfg(not(feature = "nightly"))]
+    Ok(Rc::new_uninit())
+}
+
+impl<T> RcExt<T> for Rc<T> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<T>,
+    {
+        let mut rc = Rc::<T>::new_uninit();
+        let slot = Rc::get_mut(&mut rc).unwrap();
+        unsafe {
+            init.initialize(slot.as_mut_ptr());
+            rc.assume_init()
+        }
+    }
+
+    #[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let mut rc = try_new_uninit::<T, E>()?;
+        let slot = Rc::get_mut(&mut rc).unwrap();
+        unsafe {
+            init.try_initialize(slot.as_mut_ptr())
+                .map_err(InplaceError::Init)?;
+            Ok(rc.assume_init())
+        }
+    }
+}
+
+#[cfg(not(inplace_no_global_oom_handling))]
+impl<T> RcExt<[T]> for Rc<[T]> {
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<[T]>,
+    {
+        let len = init.dest(ptr::null_mut()).len();
+        let mut rc = Rc::<[T]>::new_uninit_slice(len);
+        let slots = Rc::get_mut(&mut rc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.initialize(dest);
+            rc.assume_init()
+        }
+    }
+
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<[T], E>,
+    {
+        let len = init.dest(ptr::null_mut()).len();
+        let mut rc = Rc::<[T]>::new_uninit_slice(len);
+        let slots = Rc::get_mut(&mut rc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            Ok(rc.assume_init())
+        }
+    }
+}
+
+#[cfg(not(inplace_no_global_oom_handling))]
+impl RcExt<str> for Rc<str> {
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<str>,
+    {
+        let len = init.layout().size();
+        let mut rc = Rc::<[u8]>::new_uninit_slice(len);
+        let slots: &mut [MaybeUninit<u8>] = Rc::get_mut(&mut rc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.initialize(dest);
+            // `str` has the same layout as `[u8]`, and the initializer wrote
+            // valid UTF-8.
+            Rc::from_raw(Rc::into_raw(rc.assume_init()) as *const str)
+        }
+    }
+
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<str, E>,
+    {
+        let len = init.layout().size();
+        let mut rc = Rc::<[u8]>::new_uninit_slice(len);
+        let slots: &mut [MaybeUninit<u8>] = Rc::get_mut(&mut rc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            Ok(Rc::from_raw(Rc::into_raw(rc.assume_init()) as *const str))
+        }
+    }
+}
+
+/// Extension methods for constructing an `Rc` in place with a custom
+/// allocator.
+///
+/// These are the [`RcExt`](crate::RcExt) methods for sized values in an
+/// `Rc` which uses an [`Allocator`] other than the global one, and require
+/// the unstable `allocator_api`.
+///
+/// # Example
+///
+/// ```
+/// #![feature(allocator_api)]
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::alloc::System;
+/// use std::rc::Rc;
+/// use inplace::{init, RcInExt};
+///
+/// let table: Rc<[u32; 4096], System> = Rc::emplace_in(init::with(|| [1; 4096]), System);
+/// assert_eq!(table[0], 1);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "nightly")]
+pub trait RcInExt<T, A: Allocator>: Sized {
+    /// Allocates a shared value with `alloc` and constructs it in place.
+    ///
+    /// If the initializer panics, the allocation is freed.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_in<I>(init: I, alloc: A) -> Self
+    where
+        I: Init<T>;
+
+    /// Allocates a shared value with `alloc` and attempts to construct it in
+    /// place.
+    ///
+    /// If the value cannot be allocated, [`InplaceError::Alloc`] is returned
+    /// and the initializer is not run. If the initializer fails, the
+    /// allocation is freed and the error is returned as
+    /// [`InplaceError::Init`].
+    fn try_emplace_in<I, E>(init: I, alloc: A) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+}
+
+#[cfg(feature = "nightly")]
+impl<T, A: Allocator> RcInExt<T, A> for Rc<T, A> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_in<I>(init: I, alloc: A) -> Self
+    where
+        I: Init<T>,
+    {
+        let mut rc = Rc::<T, A>::new_uninit_in(alloc);
+        let slot = Rc::get_mut(&mut rc).unwrap();
+        unsafe {
+            init.initialize(slot.as_mut_ptr());
+            rc.assume_init()
+        }
+    }
+
+    fn try_emplace_in<I, E>(init: I, alloc: A) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let mut rc = Rc::<T, A>::try_new_uninit_in(alloc)
+            .map_err(|_| InplaceError::Alloc(Layout::new::<T>()))?;
+        let slot = Rc::get_mut(&mut rc).unwrap();
+        unsafe {
+            init.try_initialize(slot.as_mut_ptr())
+                .map_err(InplaceError::Init)?;
+            Ok(rc.assume_init())
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Replacement of a value at its current address.
+//!
+//! Both operations leave the destination without a value while the
+//! replacement is constructed, and so abort the process if that panics.
+
+use core::pin::Pin;
+use core::ptr;
+
+use crate::__private::AbortOnUnwind;
+use crate::init::{Init, PinInit};
+
+/// Moves the value out of `dest`, and constructs its replacement in place.
+///
+/// `f` consumes the old value and returns an initializer for the new one,
+/// which is then run at the same address, so that a large value can be
+/// transformed without a copy of the result passing through the stack.
+///
+/// While this runs, `dest` holds no value. If `f` or the initializer panics,
+/// there is nothing that could be left there for the owner of `dest` to
+/// observe or drop, so the process is aborted. Like any panic during
+/// unwinding, this aborts even with `panic = "unwind"`.
+///
+/// # Example
+///
+/// ```
+/// use inplace::init;
+/// use inplace::replace::replace_in_place;
+///
+/// enum Connection {
+///     Idle { buffer: Box<[u8]> },
+///     Active { buffer: Box<[u8]>, peer: u32 },
+/// }
+///
+/// let mut conn = Connection::Idle { buffer: vec![0; 4096].into_boxed_slice() };
+/// replace_in_place(&mut conn, |old| match old {
+///     Connection::Idle { buffer } => Connection::Active { buffer, peer: 7 },
+///     active => active,
+/// });
+/// assert!(matches!(conn, Connection::Active { peer: 7, .. }));
+/// ```
+///
+/// A panic while the replacement is constructed aborts the process, rather
+/// than unwinding out with `count` holding no value:
+///
+/// ```
+/// use inplace::init;
+/// use inplace::replace::replace_in_place;
+/// # use std::process::{Command, Stdio};
+/// # if std::env::var_os("INPLACE_REPLACE_CHILD").is_some() {
+///
+/// let mut count = 1u64;
+/// replace_in_place(&mut count, |old| {
+///     init::with(move || -> u64 { old.checked_add(u64::MAX).expect("overflow") })
+/// });
+/// unreachable!("replace_in_place returned after its initializer panicked");
+/// # }
+/// # // The example runs in a child process, which must abort rather than exit
+/// # // with the status of an ordinary panic.
+/// # let status = Command::new(std::env::current_exe().unwrap())
+/// #     .env("INPLACE_REPLACE_CHILD", "1")
+/// #     .stderr(Stdio::null())
+/// #     .status()
+/// #     .unwrap();
+/// # assert!(!status.success() && status.code() != Some(101), "{status}");
+/// ```
+pub fn replace_in_place<T, I, F>(dest: &mut T, f: F)
+where
+    F: FnOnce(T) -> I,
+    I: Init<T>,
+{
+    let dest: *mut T = dest;
+    let abort = AbortOnUnwind;
+    unsafe {
+        let init = f(ptr::read(dest));
+        init.initialize(dest);
+    }
+    core::mem::forget(abort);
+}
+
+/// Drops the pinned value behind `dest`, and constructs a new one in place.
+///
+/// The old value is dropped in place first, running its
+/// [`PinnedDrop`](crate::PinnedDrop) if it has one, which satisfies the pinning
+/// guarantee before the memory is reused. This suits long-lived intrusive
+/// nodes which are recycled rather than freed. If the drop or the
+/// initializer panics, the process is aborted, as for [`replace_in_place`].
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use inplace::replace::pin_replace;
+/// use inplace::{pin_init, BoxExt};
+///
+/// struct Request {
+///     id: u32,
+///     _pin: PhantomPinned,
+/// }
+///
+/// let mut request: Pin<Box<Request>> = Box::pin_emplace(pin_init!(Request {
+///     id: 1,
+///     _pin: PhantomPinned,
+/// }));
+/// pin_replace(request.as_mut(), pin_init!(Request {
+///     id: 2,
+///     _pin: PhantomPinned,
+/// }));
+/// assert_eq!(request.id, 2);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn pin_replace<T, I>(dest: Pin<&mut T>, init: I)
+where
+    I: PinInit<T>,
+{
+    let abort = AbortOnUnwind;
+    unsafe {
+        let dest: *mut T = dest.get_unchecked_mut();
+        ptr::drop_in_place(dest);
+        init.pin_initialize(dest);
+    }
+    core::mem::forget(abort);
+}
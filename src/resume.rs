@@ -0,0 +1,274 @@
+//! Construction spread over many bounded steps.
+//!
+//! Constructing a value of gigabytes in place can take far longer than a
+//! frame, a tick of an event loop, or any other deadline a latency-sensitive
+//! loop has to meet. A [`ResumableInit`] instead constructs its value in
+//! steps which each do a bounded amount of work, and [`Resumable`] drives one
+//! in a heap allocation, a step at a time, until the value is complete or the
+//! construction is abandoned and the part already built is dropped.
+
+use core::alloc::Layout;
+#[cfg(feature = "alloc")]
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::mem;
+#[cfg(feature = "alloc")]
+use core::mem::ManuallyDrop;
+use core::ptr;
+#[cfg(feature = "alloc")]
+use core::ptr::NonNull;
+use core::task::Poll;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+#[cfg(feature = "alloc")]
+use crate::error::InplaceError;
+use crate::init::Init;
+#[cfg(feature = "alloc")]
+use crate::raw;
+
+/// An initializer which constructs its value over any number of steps.
+///
+/// # Safety
+///
+/// Once [`step`](ResumableInit::step) returns `Poll::Ready`, the destination
+/// must be fully initialized. Until then, whether `step` returns
+/// `Poll::Pending` or panics, the destination must be left in a state which
+/// [`cancel`](ResumableInit::cancel) returns to uninitialized, dropping
+/// whatever was constructed. `layout` and `dest` are as for
+/// [`PinInit`](crate::PinInit).
+pub unsafe trait ResumableInit<T: ?Sized> {
+    /// Returns the layout of the value which will be constructed.
+    fn layout(&self) -> Layout;
+
+    /// Converts the address of the allocated space into a pointer to the
+    /// destination, attaching any pointer metadata.
+    fn dest(&self, addr: *mut u8) -> *mut T;
+
+    /// Continues construction at `dest`, doing at most `budget` units of
+    /// work, and reports whether the value is complete.
+    ///
+    /// What a unit is depends on the initializer, such as an element of a
+    /// slice. A nonzero budget must make some progress.
+    ///
+    /// # Safety
+    ///
+    /// `dest` must be the same destination on every call, valid for writes
+    /// of the layout, and must not be accessed in between except by `step`
+    /// and `cancel`. `step` may not be called again after it returns
+    /// `Poll::Ready` or panics.
+    unsafe fn step(&mut self, dest: *mut T, budget: usize) -> Poll<()>;
+
+    /// Drops the part of the value constructed so far, leaving `dest`
+    /// uninitialized.
+    ///
+    /// # Safety
+    ///
+    /// `dest` must be the destination passed to `step`, whose value is not
+    /// complete. Neither method may be called again afterwards.
+    unsafe fn cancel(&mut self, dest: *mut T);
+}
+
+/// A resumable initializer for a slice which constructs each element in
+/// place.
+///
+/// Created by [`from_fn`].
+pub struct FromFn<T, F> {
+    len: usize,
+    done: usize,
+    f: F,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Creates a resumable initializer for a slice of `len` elements, where
+/// element `i` is constructed in place by the initializer `f(i)`.
+///
+/// Each step constructs at most its budget of elements.
+pub fn from_fn<T, I, F>(len: usize, f: F) -> FromFn<T, F>
+where
+    F: FnMut(usize) -> I,
+    I: Init<T>,
+{
+    FromFn {
+        len,
+        done: 0,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<T, I, F> ResumableInit<[T]> for FromFn<T, F>
+where
+    F: FnMut(usize) -> I,
+    I: Init<T>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<T>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [T] {
+        ptr::slice_from_raw_parts_mut(addr.cast(), self.len)
+    }
+
+    unsafe fn step(&mut self, dest: *mut [T], budget: usize) -> Poll<()> {
+        let start = dest.cast::<T>();
+        let end = self.len.min(self.done.saturating_add(budget));
+        while self.done < end {
+            (self.f)(self.done).initialize(start.add(self.done));
+            self.done += 1;
+        }
+        if self.done == self.len {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    unsafe fn cancel(&mut self, dest: *mut [T]) {
+        let done = mem::replace(&mut self.done, 0);
+        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(dest.cast::<T>(), done));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Building,
+    Complete,
+    /// A step panicked, so the value can only be cancelled.
+    Poisoned,
+}
+
+/// A value under construction on the heap by a [`ResumableInit`].
+///
+/// Each call to [`step`](Resumable::step) continues the construction, and
+/// [`finish`](Resumable::finish) completes it and returns the `Box`. Dropping
+/// a `Resumable` before then cancels the construction, dropping the part of
+/// the value already built and freeing the allocation.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{init, resume::{self, Resumable}};
+///
+/// let mut table = Resumable::new(resume::from_fn(1 << 12, |i| init::with(move || [i as u32; 64])));
+/// let mut steps = 1;
+/// while table.step(256).is_pending() {
+///     // Other work can run between steps.
+///     steps += 1;
+/// }
+/// let table: Box<[[u32; 64]]> = table.finish();
+/// assert_eq!((steps, table[4095][63]), (16, 4095));
+///
+/// let mut abandoned = Resumable::<[String], _>::new(resume::from_fn(1 << 12, |i| i.to_string()));
+/// assert!(abandoned.step(100).is_pending());
+/// drop(abandoned);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "alloc")]
+pub struct Resumable<T: ?Sized, R: ResumableInit<T>> {
+    addr: NonNull<u8>,
+    layout: Layout,
+    dest: *mut T,
+    init: R,
+    state: State,
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Send, R: ResumableInit<T> + Send> Send for Resumable<T, R> {}
+#[cfg(feature = "alloc")]
+unsafe impl<T: ?Sized + Sync, R: ResumableInit<T> + Sync> Sync for Resumable<T, R> {}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R: ResumableInit<T>> Resumable<T, R> {
+    /// Allocates space for the value, without constructing any of it.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    pub fn new(init: R) -> Self {
+        let layout = init.layout();
+        Self::with_addr(init, raw::allocate(layout), layout)
+    }
+
+    /// Attempts to allocate space for the value, without constructing any of
+    /// it.
+    ///
+    /// If the allocation fails, the initializer is dropped and
+    /// [`InplaceError::Alloc`] is returned.
+    pub fn try_new(init: R) -> Result<Self, InplaceError<Infallible>> {
+        let layout = init.layout();
+        match raw::try_allocate(layout) {
+            Some(addr) => Ok(Self::with_addr(init, addr, layout)),
+            None => Err(InplaceError::Alloc(layout)),
+        }
+    }
+
+    fn with_addr(init: R, addr: NonNull<u8>, layout: Layout) -> Self {
+        Resumable {
+            addr,
+            layout,
+            dest: init.dest(addr.as_ptr()),
+            init,
+            state: State::Building,
+        }
+    }
+
+    /// Continues construction, doing at most `budget` units of work, and
+    /// reports whether the value is complete.
+    ///
+    /// Once the value is complete, further steps do nothing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an earlier step panicked. The value can then only be
+    /// cancelled, by dropping the `Resumable`.
+    pub fn step(&mut self, budget: usize) -> Poll<()> {
+        match self.state {
+            State::Building => {}
+            State::Complete => return Poll::Ready(()),
+            State::Poisoned => panic!("resumable initializer panicked in an earlier step"),
+        }
+        self.state = State::Poisoned;
+        let poll = unsafe { self.init.step(self.dest, budget) };
+        self.state = match poll {
+            Poll::Ready(()) => State::Complete,
+            Poll::Pending => State::Building,
+        };
+        poll
+    }
+
+    /// Returns true if the value is complete.
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Complete
+    }
+
+    /// Completes the value, without a bound on the work, and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an earlier step panicked.
+    pub fn finish(mut self) -> Box<T> {
+        while self.step(usize::MAX).is_pending() {}
+        let this = ManuallyDrop::new(self);
+        unsafe {
+            drop(ptr::read(&this.init));
+            Box::from_raw(this.dest)
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized, R: ResumableInit<T>> Drop for Resumable<T, R> {
+    fn drop(&mut self) {
+        unsafe {
+            match self.state {
+                State::Complete => ptr::drop_in_place(self.dest),
+                State::Building | State::Poisoned => self.init.cancel(self.dest),
+            }
+            raw::deallocate(self.addr, self.layout);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Placement regions backed by virtual memory.
+//!
+//! A [`ScratchRegion`] is memory mapped directly from the operating system,
+//! rather than taken from the heap, for very large temporary values that
+//! must not be built on the stack. Its pages are only backed by physical
+//! memory once touched, and it can be surrounded by inaccessible guard
+//! pages, so that code writing past either end of it faults rather than
+//! corrupting other memory.
+
+use core::alloc::Layout;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+use std::io;
+
+use crate::slot::Slot;
+
+#[cfg(unix)]
+#[path = "scratch/unix.rs"]
+mod sys;
+#[cfg(windows)]
+#[path = "scratch/windows.rs"]
+mod sys;
+
+/// A region of virtual memory which values can be constructed in.
+///
+/// Values are constructed at the start of the region through a [`Slot`],
+/// which borrows the region until the value is dropped, so the region can be
+/// reused for one value after another.
+///
+/// # Example
+///
+/// ```
+/// use inplace::init;
+/// use inplace::scratch::ScratchRegion;
+///
+/// let mut region = ScratchRegion::with_guard_pages(64 << 20)?;
+/// let zeroed = unsafe { init::from_raw(|p: *mut [u64; 1 << 23]| p.write_bytes(0, 1)) };
+/// let matrix = region.slot::<[u64; 1 << 23]>().emplace(zeroed);
+/// assert_eq!(matrix.len(), 1 << 23);
+/// # Ok::<_, std::io::Error>(())
+/// ```
+pub struct ScratchRegion {
+    base: NonNull<u8>,
+    len: usize,
+    guard: usize,
+}
+
+unsafe impl Send for ScratchRegion {}
+unsafe impl Sync for ScratchRegion {}
+
+impl ScratchRegion {
+    /// Maps a region of at least `len` bytes.
+    pub fn new(len: usize) -> io::Result<Self> {
+        Self::map(len, 0)
+    }
+
+    /// Maps a region of at least `len` bytes, with an inaccessible guard page
+    /// on either side of it.
+    pub fn with_guard_pages(len: usize) -> io::Result<Self> {
+        Self::map(len, sys::page_size())
+    }
+
+    fn map(len: usize, guard: usize) -> io::Result<Self> {
+        let page = sys::page_size();
+        let len = len
+            .max(1)
+            .checked_next_multiple_of(page)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "region too large"))?;
+        let base = sys::map(len, guard)?;
+        Ok(ScratchRegion { base, len, guard })
+    }
+
+    /// Returns the size of the region, rounded up to a whole number of pages.
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+    /// Returns a pointer to the start of the region.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.base.as_ptr()
+    }
+
+    /// Returns true if a value with the given layout fits in the region.
+    pub fn fits(&self, layout: Layout) -> bool {
+        layout.size() <= self.len && layout.align() <= sys::page_size()
+    }
+
+    /// Returns a slot for a `T` at the start of the region.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a `T` does not fit in the region.
+    pub fn slot<T>(&mut self) -> Slot<'_, T> {
+        self.try_slot()
+            .expect("value does not fit in the scratch region")
+    }
+
+    /// Returns a slot for a `T` at the start of the region, or `None` if a
+    /// `T` does not fit in it.
+    pub fn try_slot<T>(&mut self) -> Option<Slot<'_, T>> {
+        self.fits(Layout::new::<T>())
+            .then(|| Slot::new(unsafe { &mut *self.base.as_ptr().cast::<MaybeUninit<T>>() }))
+    }
+}
+
+impl Drop for ScratchRegion {
+    fn drop(&mut self) {
+        unsafe { sys::unmap(self.base, self.len, self.guard) }
+    }
+}
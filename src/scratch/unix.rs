@@ -0,0 +1,42 @@
+use core::ptr::{self, NonNull};
+
+use std::io;
+
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Maps `len` readable and writable bytes, between two inaccessible regions
+/// of `guard` bytes.
+pub fn map(len: usize, guard: usize) -> io::Result<NonNull<u8>> {
+    let total = len + 2 * guard;
+    unsafe {
+        let addr = libc::mmap(
+            ptr::null_mut(),
+            total,
+            if guard == 0 {
+                libc::PROT_READ | libc::PROT_WRITE
+            } else {
+                libc::PROT_NONE
+            },
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let base = addr.cast::<u8>().add(guard);
+        if guard != 0 && libc::mprotect(base.cast(), len, libc::PROT_READ | libc::PROT_WRITE) != 0 {
+            let error = io::Error::last_os_error();
+            libc::munmap(addr, total);
+            return Err(error);
+        }
+        Ok(NonNull::new_unchecked(base))
+    }
+}
+
+/// Unmaps a region returned by [`map`].
+pub unsafe fn unmap(base: NonNull<u8>, len: usize, guard: usize) {
+    libc::munmap(base.as_ptr().sub(guard).cast(), len + 2 * guard);
+}
@@ -0,0 +1,50 @@
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+
+use std::io;
+
+use windows_sys::Win32::System::Memory::{
+    VirtualAlloc, VirtualFree, VirtualProtect, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_NOACCESS,
+    PAGE_READWRITE,
+};
+use windows_sys::Win32::System::SystemInformation::GetSystemInfo;
+
+pub fn page_size() -> usize {
+    let mut info = MaybeUninit::uninit();
+    unsafe {
+        GetSystemInfo(info.as_mut_ptr());
+        info.assume_init().dwPageSize as usize
+    }
+}
+
+/// Maps `len` readable and writable bytes, between two inaccessible regions
+/// of `guard` bytes.
+pub fn map(len: usize, guard: usize) -> io::Result<NonNull<u8>> {
+    let total = len + 2 * guard;
+    unsafe {
+        let addr = VirtualAlloc(ptr::null(), total, MEM_RESERVE | MEM_COMMIT, PAGE_READWRITE);
+        if addr.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let mut old = 0;
+        if guard != 0
+            && (VirtualProtect(addr, guard, PAGE_NOACCESS, &mut old) == 0
+                || VirtualProtect(
+                    addr.cast::<u8>().add(guard + len).cast(),
+                    guard,
+                    PAGE_NOACCESS,
+                    &mut old,
+                ) == 0)
+        {
+            let error = io::Error::last_os_error();
+            VirtualFree(addr, 0, MEM_RELEASE);
+            return Err(error);
+        }
+        Ok(NonNull::new_unchecked(addr.cast::<u8>().add(guard)))
+    }
+}
+
+/// Unmaps a region returned by [`map`].
+pub unsafe fn unmap(base: NonNull<u8>, _len: usize, guard: usize) {
+    VirtualFree(base.as_ptr().sub(guard).cast(), 0, MEM_RELEASE);
+}
@@ -0,0 +1,188 @@
+//! Placement targets in specific linker sections.
+//!
+//! On embedded targets, memory is often split into several regions, such as
+//! tightly-coupled RAM, a slower external RAM, or a region which the startup
+//! code leaves alone so that its contents survive a reset. A `static` is
+//! placed in one of these by giving it a `#[link_section]`, and the statics
+//! declared with [`in_section!`] can then be initialized in place at runtime.
+//!
+//! Storage in a section that is not initialized at startup, conventionally
+//! called `.noinit`, is declared with [`NoInit`], which checks whether a value
+//! was preserved from before the reset before handing it out.
+//!
+//! [`in_section!`]: crate::in_section!
+
+use core::cell::UnsafeCell;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use crate::init::Init;
+
+/// Declares statics in linker sections.
+///
+/// Each static is given a `#[link_section]` attribute and initialized with
+/// its type's `new` constructor, so this is used with placement targets
+/// such as [`StaticInPlace`](crate::cell::StaticInPlace) and [`NoInit`].
+///
+/// The section must be one that the linker script provides. Apart from a
+/// `.noinit` section, it must be initialized by the startup code like
+/// `.data`, or the static's own state will not be valid.
+///
+/// # Example
+///
+/// ```
+/// use inplace::cell::StaticInPlace;
+/// use inplace::init;
+///
+/// inplace::in_section! {
+///     ".sram2" => static DMA_BUFFER: StaticInPlace<[u8; 4096]>;
+/// }
+///
+/// let buffer = DMA_BUFFER.emplace(init::with(|| [0; 4096]));
+/// assert_eq!(buffer.len(), 4096);
+/// ```
+#[macro_export]
+macro_rules! in_section {
+    ($($section:literal => $(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty;)*) => {
+        $(
+            $(#[$attr])*
+            #[link_section = $section]
+            $vis static $name: $ty = <$ty>::new();
+        )*
+    };
+}
+
+/// Storage for a `T` which is preserved across a reset.
+///
+/// This is declared in a section which is not initialized at startup, so
+/// that a value constructed in it, such as a crash log or a boot counter,
+/// can be found again after the processor is reset. After a power-on its
+/// contents are arbitrary, so a marker is kept alongside the value, which is
+/// only written once the value is complete, and removed before it is
+/// constructed again.
+///
+/// # Example
+///
+/// ```
+/// use inplace::init;
+/// use inplace::section::{NoInit, Retained};
+///
+/// struct BootLog {
+///     resets: u32,
+///     last_fault: u32,
+/// }
+///
+/// inplace::in_section! {
+///     ".noinit" => static BOOT_LOG: NoInit<BootLog>;
+/// }
+///
+/// // Early in startup, before anything else uses `BOOT_LOG`.
+/// let fresh = init::with(|| BootLog { resets: 0, last_fault: 0 });
+/// let log = match unsafe { BOOT_LOG.recover_or_emplace(|log| log.resets < 1000, fresh) } {
+///     Retained::Preserved(log) => log,
+///     Retained::Initialized(log) => log,
+/// };
+/// log.resets += 1;
+/// ```
+pub struct NoInit<T> {
+    marker: UnsafeCell<MaybeUninit<u32>>,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send> Sync for NoInit<T> {}
+
+/// The value found in a [`NoInit`].
+#[derive(Debug)]
+pub enum Retained<'a, T> {
+    /// The value was preserved from before the reset.
+    Preserved(&'a mut T),
+    /// No valid value was found, so it was constructed again.
+    Initialized(&'a mut T),
+}
+
+impl<'a, T> Retained<'a, T> {
+    /// Returns the value, whether or not it was preserved.
+    pub fn into_inner(self) -> &'a mut T {
+        match self {
+            Retained::Preserved(value) | Retained::Initialized(value) => value,
+        }
+    }
+
+    /// Returns true if the value was preserved from before the reset.
+    pub fn is_preserved(&self) -> bool {
+        matches!(self, Retained::Preserved(_))
+    }
+}
+
+impl<T> NoInit<T> {
+    /// The marker written once a value is complete. It includes the size of
+    /// the value, so that storage left by firmware with a different layout is
+    /// less likely to be accepted.
+    const MARKER: u32 = 0x5afe_b007 ^ (mem::size_of::<T>() as u32).rotate_left(16);
+
+    /// Creates storage with no value.
+    pub const fn new() -> Self {
+        NoInit {
+            marker: UnsafeCell::new(MaybeUninit::uninit()),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns the preserved value, or constructs a new one in place.
+    ///
+    /// The value is preserved if the marker is present and `is_valid`
+    /// accepts it. Otherwise, the marker is removed, the initializer is run,
+    /// and the marker is written again when it completes, so that a reset
+    /// during initialization does not leave a partial value to be found.
+    ///
+    /// # Safety
+    ///
+    /// This must be called at most once after each reset, before any other
+    /// access to the storage. The static must be placed in a section which
+    /// is not initialized at startup, and the firmware which wrote the
+    /// marker must have used the same layout for `T`.
+    pub unsafe fn recover_or_emplace<F, I>(
+        &'static self,
+        is_valid: F,
+        init: I,
+    ) -> Retained<'static, T>
+    where
+        F: FnOnce(&T) -> bool,
+        I: Init<T>,
+    {
+        let marker = self.marker.get().cast::<u32>();
+        let value = self.value.get().cast::<T>();
+        unsafe {
+            // The compiler cannot know what the startup code left here, so
+            // the marker is read without assumptions about its contents.
+            if ptr::read_volatile(marker) == Self::MARKER && is_valid(&*value) {
+                return Retained::Preserved(&mut *value);
+            }
+            ptr::write_volatile(marker, 0);
+            init.initialize(value);
+            ptr::write_volatile(marker, Self::MARKER);
+            Retained::Initialized(&mut *value)
+        }
+    }
+
+    /// Removes the marker, so that the value is constructed again after the
+    /// next reset.
+    ///
+    /// The value is not dropped.
+    ///
+    /// # Safety
+    ///
+    /// The marker is written without synchronization, so this must not be
+    /// called while another thread or interrupt handler may be calling
+    /// `invalidate` or [`recover_or_emplace`](NoInit::recover_or_emplace)
+    /// on the same storage.
+    pub unsafe fn invalidate(&self) {
+        unsafe { ptr::write_volatile(self.marker.get().cast::<u32>(), 0) };
+    }
+}
+
+impl<T> Default for NoInit<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
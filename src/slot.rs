@@ -0,0 +1,381 @@
+//! Placement into storage owned by the caller, such as on the stack.
+//!
+//! A [`Slot`] is uninitialized storage for a `T` which an initializer can be
+//! run on, giving an [`Emplaced`] which owns the value and drops it when it
+//! goes out of scope. This is the counterpart of `moveit`'s slots for this
+//! crate's initializers. Slots on the stack are declared with [`slot!`],
+//! which also makes them usable for pinned values.
+//!
+//! Existing storage can also be initialized directly with [`emplace_into`],
+//! which gives a reference to the value without taking ownership of it.
+//!
+//! [`slot!`]: crate::slot!
+
+use core::cell::Cell;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+
+use crate::init::{Init, PinInit, TryInit};
+
+/// Uninitialized storage for a `T`, borrowed for `'a`.
+pub struct Slot<'a, T> {
+    pub(crate) storage: &'a mut MaybeUninit<T>,
+    /// Set while the storage holds a value, if the storage will drop a value
+    /// left in it before it is reused. Pinning is only possible if it will.
+    pub(crate) live: Option<&'a Cell<bool>>,
+    pub(crate) pinnable: bool,
+}
+
+impl<'a, T> Slot<'a, T> {
+    /// Creates a slot in borrowed storage.
+    ///
+    /// A slot created this way cannot be used for pinned values, since the
+    /// storage could be reused without the value being dropped, if the
+    /// [`Emplaced`] were forgotten. Use [`slot!`](crate::slot!) or
+    /// [`Slot::new_static`] for those.
+    pub fn new(storage: &'a mut MaybeUninit<T>) -> Self {
+        Slot {
+            storage,
+            live: None,
+            pinnable: false,
+        }
+    }
+
+    /// Creates a slot in storage which is never reused, and so can be used
+    /// for pinned values.
+    pub fn new_static(storage: &'static mut MaybeUninit<T>) -> Slot<'static, T> {
+        Slot {
+            storage,
+            live: None,
+            pinnable: true,
+        }
+    }
+
+    /// Constructs a value in the slot.
+    ///
+    /// If the initializer panics, the slot is left uninitialized.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::mem::MaybeUninit;
+    /// use inplace::init;
+    /// use inplace::slot::Slot;
+    ///
+    /// let mut storage = MaybeUninit::<[u64; 1024]>::uninit();
+    /// let table = Slot::new(&mut storage).emplace(init::with(|| [3; 1024]));
+    /// assert_eq!(table[1023], 3);
+    /// ```
+    pub fn emplace<I>(self, init: I) -> Emplaced<'a, T>
+    where
+        I: Init<T>,
+    {
+        unsafe { init.initialize(self.storage.as_mut_ptr()) };
+        self.into_emplaced()
+    }
+
+    /// Attempts to construct a value in the slot.
+    ///
+    /// If the initializer fails, its error is returned, and the slot is
+    /// consumed without holding a value.
+    pub fn try_emplace<I, E>(self, init: I) -> Result<Emplaced<'a, T>, E>
+    where
+        I: TryInit<T, E>,
+    {
+        unsafe { init.try_initialize(self.storage.as_mut_ptr())? };
+        Ok(self.into_emplaced())
+    }
+
+    /// Constructs a pinned value in the slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slot was created with [`Slot::new`], since the storage
+    /// is not guaranteed to outlive the value.
+    pub fn pin_emplace<I>(self, init: I) -> Pin<Emplaced<'a, T>>
+    where
+        I: PinInit<T>,
+    {
+        assert!(
+            self.pinnable,
+            "slot cannot hold a pinned value; declare it with `slot!`"
+        );
+        unsafe {
+            init.pin_initialize(self.storage.as_mut_ptr());
+            Pin::new_unchecked(self.into_emplaced())
+        }
+    }
+
+    fn into_emplaced(self) -> Emplaced<'a, T> {
+        if let Some(live) = self.live {
+            live.set(true);
+        }
+        Emplaced {
+            value: unsafe { self.storage.assume_init_mut() },
+            live: self.live,
+        }
+    }
+}
+
+/// A value constructed in a [`Slot`], which it owns.
+///
+/// The value is dropped when this is, and can also be moved out with
+/// [`Emplaced::into_inner`] unless it is pinned.
+pub struct Emplaced<'a, T> {
+    value: &'a mut T,
+    live: Option<&'a Cell<bool>>,
+}
+
+impl<T> Emplaced<'_, T> {
+    /// Moves the value out of the slot.
+    pub fn into_inner(this: Self) -> T {
+        let this = mem::ManuallyDrop::new(this);
+        if let Some(live) = this.live {
+            live.set(false);
+        }
+        unsafe { (&*this.value as *const T).read() }
+    }
+}
+
+impl<T> Drop for Emplaced<'_, T> {
+    fn drop(&mut self) {
+        if let Some(live) = self.live {
+            live.set(false);
+        }
+        unsafe { (self.value as *mut T).drop_in_place() }
+    }
+}
+
+impl<T> Deref for Emplaced<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Emplaced<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Emplaced<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Constructs a value in existing uninitialized storage.
+///
+/// The value is not dropped by the storage, so it is leaked unless the
+/// caller later drops it, for example with
+/// [`MaybeUninit::assume_init_drop`]. If the initializer panics, the storage
+/// is left uninitialized.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use inplace::init;
+/// use inplace::slot::emplace_into;
+///
+/// let mut storage = MaybeUninit::<[u16; 4096]>::uninit();
+/// let samples = emplace_into(&mut storage, init::with(|| [0x8000; 4096]));
+/// samples[0] = 0;
+/// assert_eq!(samples[1], 0x8000);
+/// ```
+pub fn emplace_into<T, I>(storage: &mut MaybeUninit<T>, init: I) -> &mut T
+where
+    I: Init<T>,
+{
+    unsafe {
+        init.initialize(storage.as_mut_ptr());
+        storage.assume_init_mut()
+    }
+}
+
+/// Attempts to construct a value in existing uninitialized storage.
+///
+/// If the initializer fails, its error is returned, and the storage is left
+/// uninitialized.
+pub fn try_emplace_into<T, I, E>(storage: &mut MaybeUninit<T>, init: I) -> Result<&mut T, E>
+where
+    I: TryInit<T, E>,
+{
+    unsafe {
+        init.try_initialize(storage.as_mut_ptr())?;
+        Ok(storage.assume_init_mut())
+    }
+}
+
+/// Constructs a pinned value in existing uninitialized storage.
+///
+/// Pinning requires that the value is dropped before its storage is reused,
+/// which borrowed storage cannot guarantee, so the storage must be borrowed
+/// forever, such as a leaked allocation or a `static`. The value is never
+/// dropped.
+pub fn pin_emplace_into<T, I>(storage: &'static mut MaybeUninit<T>, init: I) -> Pin<&'static mut T>
+where
+    I: PinInit<T>,
+{
+    unsafe {
+        init.pin_initialize(storage.as_mut_ptr());
+        Pin::new_unchecked(storage.assume_init_mut())
+    }
+}
+
+/// Owned storage for a `T` which can be emptied and reused.
+///
+/// The value is stored inline, and [`replace_with`](ReuseSlot::replace_with)
+/// drops it in place and constructs the next one at the same address, which
+/// suits object pools and ring buffers of large entries. If an initializer
+/// panics, the slot is left empty.
+///
+/// # Example
+///
+/// ```
+/// use inplace::init;
+/// use inplace::slot::ReuseSlot;
+///
+/// let mut packet: ReuseSlot<[u8; 1500]> = ReuseSlot::new();
+/// assert!(packet.is_empty());
+///
+/// packet.replace_with(init::with(|| [0xaa; 1500]));
+/// let payload = packet.replace_with(init::with(|| [0x55; 1500]));
+/// assert_eq!(payload[0], 0x55);
+///
+/// packet.clear();
+/// assert!(packet.get().is_none());
+/// ```
+pub struct ReuseSlot<T> {
+    storage: MaybeUninit<T>,
+    live: bool,
+}
+
+impl<T> ReuseSlot<T> {
+    /// Creates an empty slot.
+    pub const fn new() -> Self {
+        ReuseSlot {
+            storage: MaybeUninit::uninit(),
+            live: false,
+        }
+    }
+
+    /// Returns true if the slot holds no value.
+    pub fn is_empty(&self) -> bool {
+        !self.live
+    }
+
+    /// Returns a reference to the value, if there is one.
+    pub fn get(&self) -> Option<&T> {
+        self.live.then(|| unsafe { self.storage.assume_init_ref() })
+    }
+
+    /// Returns a mutable reference to the value, if there is one.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.live.then(|| unsafe { self.storage.assume_init_mut() })
+    }
+
+    /// Drops the value, if there is one, leaving the slot empty.
+    pub fn clear(&mut self) {
+        if mem::replace(&mut self.live, false) {
+            unsafe { self.storage.assume_init_drop() }
+        }
+    }
+
+    /// Moves the value out, leaving the slot empty.
+    pub fn take(&mut self) -> Option<T> {
+        mem::replace(&mut self.live, false).then(|| unsafe { self.storage.assume_init_read() })
+    }
+
+    /// Drops the current value, if there is one, and constructs a new value
+    /// in its place.
+    pub fn replace_with<I>(&mut self, init: I) -> &mut T
+    where
+        I: Init<T>,
+    {
+        self.clear();
+        unsafe {
+            init.initialize(self.storage.as_mut_ptr());
+            self.live = true;
+            self.storage.assume_init_mut()
+        }
+    }
+
+    /// Drops the current value, if there is one, and attempts to construct a
+    /// new value in its place.
+    ///
+    /// If the initializer fails, its error is returned and the slot is left
+    /// empty.
+    pub fn try_replace_with<I, E>(&mut self, init: I) -> Result<&mut T, E>
+    where
+        I: TryInit<T, E>,
+    {
+        self.clear();
+        unsafe {
+            init.try_initialize(self.storage.as_mut_ptr())?;
+            self.live = true;
+            Ok(self.storage.assume_init_mut())
+        }
+    }
+}
+
+impl<T> Default for ReuseSlot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for ReuseSlot<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ReuseSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReuseSlot").field(&self.get()).finish()
+    }
+}
+
+/// Declares slots on the stack.
+///
+/// Each name is bound to a [`Slot`] in storage belonging to the current
+/// scope. If the value constructed in the slot is forgotten rather than
+/// dropped, it is dropped when the storage goes out of scope instead, so these
+/// slots can hold pinned values.
+///
+/// # Example
+///
+/// ```
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use inplace::slot::Emplaced;
+/// use inplace::{init, pin_init, slot};
+///
+/// struct Timer {
+///     ticks: u32,
+///     _pin: PhantomPinned,
+/// }
+///
+/// slot!(buf, timer);
+/// let buf: Emplaced<[u8; 8192]> = buf.emplace(init::with(|| [0; 8192]));
+/// let timer: Pin<Emplaced<Timer>> = timer.pin_emplace(pin_init!(Timer {
+///     ticks: 0,
+///     _pin: PhantomPinned,
+/// }));
+/// assert_eq!(buf.len(), 8192);
+/// assert_eq!(timer.ticks, 0);
+/// ```
+#[macro_export]
+macro_rules! slot {
+    ($($name:ident),+ $(,)?) => {
+        $(
+            let mut storage = $crate::__private::SlotStorage::new();
+            let $name = storage.slot();
+        )+
+    };
+}
@@ -0,0 +1,358 @@
+//! Initializers for `str` values.
+//!
+//! Building a large string in a `String` and then converting it to a
+//! `Box<str>` or `Arc<str>` grows and reallocates the buffer along the way,
+//! and copies it again at the end. These initializers instead determine the
+//! length up front, so the final allocation is made once and the text is
+//! written straight into it.
+//!
+//! [`format_emplaced!`](crate::format_emplaced) formats straight into a new
+//! `Box<str>`, `Rc<str>` or `Arc<str>`, measuring the text first. Text which
+//! is expensive to format can instead be written once into an allocation of
+//! a known upper bound with [`format_bounded`], which shrinks it to fit.
+
+use core::alloc::Layout;
+use core::fmt::{self, Write};
+use core::ptr;
+use core::str::{self, Utf8Error};
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use alloc::rc::Rc;
+#[cfg(all(
+    feature = "alloc",
+    target_has_atomic = "ptr",
+    not(inplace_no_global_oom_handling)
+))]
+use alloc::sync::Arc;
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use crate::boxed::BoxExt;
+#[cfg(feature = "alloc")]
+use crate::error::InplaceError;
+use crate::init::{Init, PinInit, TryInit};
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+use crate::rc::RcExt;
+#[cfg(all(
+    feature = "alloc",
+    target_has_atomic = "ptr",
+    not(inplace_no_global_oom_handling)
+))]
+use crate::sync::ArcExt;
+#[cfg(feature = "alloc")]
+use crate::write::{self, Overflow, Sink};
+
+/// An initializer for the output of formatting.
+///
+/// Created by [`format`].
+pub struct Format<'a> {
+    args: fmt::Arguments<'a>,
+    len: usize,
+}
+
+/// Creates an initializer for the text produced by formatting `args`.
+///
+/// The arguments are formatted twice: once now, to measure the length of
+/// the output, and again when the initializer is run, writing directly into
+/// the destination. Formatting implementations which produce different
+/// output on each call cause a panic.
+///
+/// # Panics
+///
+/// Panics if a formatting trait implementation returns an error.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::{string, BoxExt};
+///
+/// let name = "ferris";
+/// let greeting: Box<str> = Box::emplace(string::format(format_args!("hello, {name}!")));
+/// assert_eq!(&*greeting, "hello, ferris!");
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn format(args: fmt::Arguments<'_>) -> Format<'_> {
+    let mut counter = Counter(0);
+    counter
+        .write_fmt(args)
+        .expect("a formatting trait implementation returned an error");
+    Format {
+        args,
+        len: counter.0,
+    }
+}
+
+/// Measures formatted output without storing it.
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// Writes formatted output into a fixed-size buffer.
+struct Buffer {
+    ptr: *mut u8,
+    len: usize,
+    pos: usize,
+}
+
+impl Write for Buffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.len() > self.len - self.pos {
+            return Err(fmt::Error);
+        }
+        unsafe { ptr::copy_nonoverlapping(s.as_ptr(), self.ptr.add(self.pos), s.len()) };
+        self.pos += s.len();
+        Ok(())
+    }
+}
+
+unsafe impl PinInit<str> for Format<'_> {
+    fn layout(&self) -> Layout {
+        Layout::array::<u8>(self.len).expect("string length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut str {
+        ptr::slice_from_raw_parts_mut(addr, self.len) as *mut str
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut str) {
+        self.initialize(dest)
+    }
+}
+
+unsafe impl Init<str> for Format<'_> {
+    unsafe fn initialize(self, dest: *mut str) {
+        let mut buffer = Buffer {
+            ptr: dest.cast(),
+            len: self.len,
+            pos: 0,
+        };
+        let result = buffer.write_fmt(self.args);
+        assert!(
+            result.is_ok() && buffer.pos == self.len,
+            "formatting produced different output when repeated"
+        );
+    }
+}
+
+/// String containers which formatted text can be written straight into.
+pub trait FromFormat: Sized {
+    /// Measures the text produced by formatting `args`, allocates space for
+    /// it, and formats it again into the allocation.
+    ///
+    /// As with [`format`], formatting implementations which produce different
+    /// output on each call cause a panic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a formatting trait implementation returns an error.
+    fn from_format(args: fmt::Arguments<'_>) -> Self;
+}
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+impl FromFormat for Box<str> {
+    fn from_format(args: fmt::Arguments<'_>) -> Self {
+        Box::emplace(format(args))
+    }
+}
+
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+impl FromFormat for Rc<str> {
+    fn from_format(args: fmt::Arguments<'_>) -> Self {
+        Rc::emplace(format(args))
+    }
+}
+
+#[cfg(all(
+    feature = "alloc",
+    target_has_atomic = "ptr",
+    not(inplace_no_global_oom_handling)
+))]
+impl FromFormat for Arc<str> {
+    fn from_format(args: fmt::Arguments<'_>) -> Self {
+        Arc::emplace(format(args))
+    }
+}
+
+/// Formats text straight into a new `Box<str>`, `Rc<str>` or `Arc<str>`.
+///
+/// This takes the same arguments as `format!`, but skips the intermediate
+/// `String`: the text is measured, the final allocation is made at exactly
+/// that length, and the text is formatted again into it, as with
+/// [`string::format`](crate::string::format). The container is chosen by
+/// inference, from any type implementing
+/// [`FromFormat`](crate::string::FromFormat).
+///
+/// # Panics
+///
+/// Panics if a formatting trait implementation returns an error, or produces
+/// different output the second time.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::sync::Arc;
+/// use inplace::format_emplaced;
+///
+/// let (host, port) = ("example.com", 8443);
+/// let origin: Arc<str> = format_emplaced!("https://{host}:{port}");
+/// assert_eq!(&*origin, "https://example.com:8443");
+///
+/// let padded: Box<str> = format_emplaced!("{:>8}", origin.len());
+/// assert_eq!(&*padded, "      24");
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! format_emplaced {
+    ($($arg:tt)*) => {
+        <_ as $crate::string::FromFormat>::from_format(::core::format_args!($($arg)*))
+    };
+}
+
+/// Formats `args` once, into an allocation of `max_len` bytes, and shrinks
+/// it to the text written.
+///
+/// Unlike [`format_emplaced!`](crate::format_emplaced), the arguments are
+/// only formatted once, which suits text which is expensive to produce and
+/// whose length has a known bound. If the text is longer than `max_len`,
+/// the allocation is freed and [`Overflow`] is returned. Allocation failure
+/// is passed to the global allocation error handler.
+///
+/// # Panics
+///
+/// Panics if a formatting trait implementation returns an error.
+///
+/// # Example
+///
+/// ```
+/// use inplace::string;
+///
+/// let id = u64::MAX;
+/// // A `u64` has at most 20 digits.
+/// let key = string::format_bounded(24, format_args!("key:{id}")).unwrap();
+/// assert_eq!(&*key, "key:18446744073709551615");
+///
+/// assert!(string::format_bounded(8, format_args!("key:{id}")).is_err());
+/// ```
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+pub fn format_bounded(max_len: usize, args: fmt::Arguments<'_>) -> Result<Box<str>, Overflow> {
+    try_format_bounded(max_len, args).map_err(|error| match error {
+        InplaceError::Alloc(layout) => alloc::alloc::handle_alloc_error(layout),
+        InplaceError::Init(error) => error,
+    })
+}
+
+/// Attempts to format `args` once, into an allocation of `max_len` bytes,
+/// and shrinks it to the text written.
+///
+/// If the bytes cannot be allocated, [`InplaceError::Alloc`] is returned and
+/// the arguments are not formatted. Otherwise this behaves as
+/// [`format_bounded`], and shrinks the allocation as
+/// [`write::try_bounded`] does, which may also fail with
+/// [`InplaceError::Alloc`].
+#[cfg(feature = "alloc")]
+pub fn try_format_bounded(
+    max_len: usize,
+    args: fmt::Arguments<'_>,
+) -> Result<Box<str>, InplaceError<Overflow>> {
+    /// Keeps the overflow, which `fmt::Write` would reduce to `fmt::Error`.
+    struct Bounded<'a, 'b> {
+        sink: &'a mut Sink<'b>,
+        overflow: Option<Overflow>,
+    }
+
+    impl Write for Bounded<'_, '_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.sink.write_all(s.as_bytes()).map_err(|overflow| {
+                self.overflow = Some(overflow);
+                fmt::Error
+            })
+        }
+    }
+
+    let bytes = write::try_bounded(max_len, |sink| {
+        let mut bounded = Bounded {
+            sink,
+            overflow: None,
+        };
+        match bounded.write_fmt(args) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(bounded
+                .overflow
+                .expect("a formatting trait implementation returned an error")),
+        }
+    })?;
+    // Only whole `str`s were written.
+    Ok(unsafe { Box::from_raw(Box::into_raw(bytes) as *mut str) })
+}
+
+/// An initializer for text written as bytes by a closure.
+///
+/// Created by [`from_utf8_fn`].
+pub struct FromUtf8Fn<F> {
+    len: usize,
+    f: F,
+}
+
+/// Creates an initializer for `len` bytes of text written by `f`.
+///
+/// The closure receives the destination as a zero-filled `&mut [u8]`, and
+/// the bytes it leaves there are validated as UTF-8 once it returns. If they
+/// are not valid, the [`Utf8Error`] is returned.
+///
+/// # Example
+///
+/// ```
+/// use inplace::{string, BoxExt};
+///
+/// let text: Box<str> = Box::try_emplace(string::from_utf8_fn(4, |bytes| {
+///     bytes.copy_from_slice(b"rust");
+///     bytes.make_ascii_uppercase();
+/// }))
+/// .unwrap();
+/// assert_eq!(&*text, "RUST");
+///
+/// let invalid = Box::<str>::try_emplace(string::from_utf8_fn(1, |bytes| bytes[0] = 0xff));
+/// assert!(invalid.is_err());
+/// ```
+pub fn from_utf8_fn<F>(len: usize, f: F) -> FromUtf8Fn<F>
+where
+    F: FnOnce(&mut [u8]),
+{
+    FromUtf8Fn { len, f }
+}
+
+unsafe impl<F> TryInit<str, Utf8Error> for FromUtf8Fn<F>
+where
+    F: FnOnce(&mut [u8]),
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<u8>(self.len).expect("string length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut str {
+        ptr::slice_from_raw_parts_mut(addr, self.len) as *mut str
+    }
+
+    unsafe fn try_initialize(self, dest: *mut str) -> Result<(), Utf8Error> {
+        let bytes = dest.cast::<u8>();
+        bytes.write_bytes(0, self.len);
+        let bytes = &mut *ptr::slice_from_raw_parts_mut(bytes, self.len);
+        (self.f)(bytes);
+        str::from_utf8(bytes)?;
+        Ok(())
+    }
+}
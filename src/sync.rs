@@ -0,0 +1,364 @@
+//! Emplacement into `Arc`.
+
+#[cfg(feature = "nightly")]
+use core::alloc::{Allocator, Layout};
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use core::mem::MaybeUninit;
+#[cfg(all(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use core::pin::Pin;
+#[cfg(not(inplace_no_global_oom_handling))]
+use core::ptr;
+
+use alloc::sync::Arc;
+#[cfg(all(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use alloc::sync::UniqueArc;
+#[cfg(feature = "nightly")]
+use alloc::sync::Weak;
+
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use crate::error::InplaceError;
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use crate::init::Init;
+#[cfg(feature = "nightly")]
+use crate::init::PinInit;
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+use crate::init::TryInit;
+
+/// Extension methods for constructing an `Arc` in place.
+///
+/// The value is written directly into the shared allocation, after the
+/// reference counts, so large shared values never pass through the stack.
+/// This is implemented for sized types, slices and `str`. An `Arc<dyn Trait>`
+/// can be obtained by emplacing the concrete type and coercing the result.
+pub trait ArcExt<T: ?Sized>: Sized {
+    /// Allocates a shared value and constructs it in place.
+    ///
+    /// If the initializer panics, the allocation is freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use inplace::{init, ArcExt};
+    ///
+    /// let table: Arc<[u32; 4096]> = Arc::emplace(init::with(|| [1; 4096]));
+    /// assert_eq!(table[4095], 1);
+    ///
+    /// let name: Arc<str> = Arc::emplace("shared");
+    /// assert_eq!(&*name, "shared");
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<T>;
+
+    /// Allocates a shared value and attempts to construct it in place.
+    ///
+    /// If the initializer fails, the allocation is freed and the error is
+    /// returned as [`InplaceError::Init`]. `Arc` has no stable fallible
+    /// allocation API, so allocation failure aborts, except for sized values
+    /// with the `nightly` feature, where it is reported as
+    /// [`InplaceError::Alloc`].
+    #[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+}
+
+/// Allocates an uninitialized `Arc`, reporting allocation failure if the
+/// standard library allows it.
+#[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+fn try_new_uninit<T, E>() -> Result<Arc<MaybeUninit<T>>, InplaceError<E>> {
+    #[cfg(feature = "nightly")]
+    return Arc::try_new_uninit().map_err(|_| InplaceError::Alloc(Layout::new::<T>()));
+    #[cfg(not(feature = "nightly"))]
+    Ok(Arc::new_uninit())
+}
+
+impl<T> ArcExt<T> for Arc<T> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<T>,
+    {
+        let mut arc = Arc::<T>::new_uninit();
+        let slot = Arc::get_mut(&mut arc).unwrap();
+        unsafe {
+            init.initialize(slot.as_mut_ptr());
+            arc.assume_init()
+        }
+    }
+
+    #[cfg(any(feature = "nightly", not(inplace_no_global_oom_handling)))]
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let mut arc = try_new_uninit::<T, E>()?;
+        let slot = Arc::get_mut(&mut arc).unwrap();
+        unsafe {
+            init.try_initialize(slot.as_mut_ptr())
+                .map_err(InplaceError::Init)?;
+            Ok(arc.assume_init())
+        }
+    }
+}
+
+#[cfg(not(inplace_no_global_oom_handling))]
+impl<T> ArcExt<[T]> for Arc<[T]> {
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<[T]>,
+    {
+        let len = init.dest(ptr::null_mut()).len();
+        let mut arc = Arc::<[T]>::new_uninit_slice(len);
+        let slots = Arc::get_mut(&mut arc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.initialize(dest);
+            arc.assume_init()
+        }
+    }
+
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<[T], E>,
+    {
+        let len = init.dest(ptr::null_mut()).len();
+        let mut arc = Arc::<[T]>::new_uninit_slice(len);
+        let slots = Arc::get_mut(&mut arc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            Ok(arc.assume_init())
+        }
+    }
+}
+
+#[cfg(not(inplace_no_global_oom_handling))]
+impl ArcExt<str> for Arc<str> {
+    fn emplace<I>(init: I) -> Self
+    where
+        I: Init<str>,
+    {
+        let len = init.layout().size();
+        let mut arc = Arc::<[u8]>::new_uninit_slice(len);
+        let slots: &mut [MaybeUninit<u8>] = Arc::get_mut(&mut arc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.initialize(dest);
+            // `str` has the same layout as `[u8]`, and the initializer wrote
+            // valid UTF-8.
+            Arc::from_raw(Arc::into_raw(arc.assume_init()) as *const str)
+        }
+    }
+
+    fn try_emplace<I, E>(init: I) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<str, E>,
+    {
+        let len = init.layout().size();
+        let mut arc = Arc::<[u8]>::new_uninit_slice(len);
+        let slots: &mut [MaybeUninit<u8>] = Arc::get_mut(&mut arc).unwrap();
+        let dest = init.dest(slots.as_mut_ptr().cast());
+        unsafe {
+            init.try_initialize(dest).map_err(InplaceError::Init)?;
+            Ok(Arc::from_raw(Arc::into_raw(arc.assume_init()) as *const str))
+        }
+    }
+}
+
+/// A weak reference to an `Arc` which is still being constructed.
+///
+/// This is passed to the closure given to [`emplace_cyclic`] and
+/// [`pin_emplace_cyclic`]. Its only use is as an initializer for a `Weak<T>`
+/// field of that value. The allocation has no strong references until
+/// construction is complete, so these weak references cannot be upgraded
+/// before then, even by code run by the initializer. Afterwards, they are
+/// ordinary weak references to the new `Arc`.
+#[cfg(feature = "nightly")]
+pub struct CyclicWeak<T> {
+    weak: Weak<T>,
+}
+
+#[cfg(feature = "nightly")]
+impl<T> Clone for CyclicWeak<T> {
+    fn clone(&self) -> Self {
+        CyclicWeak {
+            weak: self.weak.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<T> PinInit<Weak<T>> for CyclicWeak<T> {
+    fn layout(&self) -> Layout {
+        Layout::new::<Weak<T>>()
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut Weak<T> {
+        addr.cast()
+    }
+
+    unsafe fn pin_initialize(self, dest: *mut Weak<T>) {
+        self.initialize(dest)
+    }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<T> Init<Weak<T>> for CyclicWeak<T> {
+    unsafe fn initialize(self, dest: *mut Weak<T>) {
+        dest.write(self.weak)
+    }
+}
+
+/// Allocates a uniquely owned, uninitialized `Arc`, and returns it along with
+/// a weak reference to the value it will hold.
+///
+/// A `UniqueArc` has no strong references, so the weak reference cannot be
+/// upgraded until it is converted into an `Arc`.
+#[cfg(all(feature = "nightly", not(inplace_no_global_oom_handling)))]
+fn uninit_cyclic<T>() -> (UniqueArc<MaybeUninit<T>>, CyclicWeak<T>) {
+    let unique = UniqueArc::new(MaybeUninit::<T>::uninit());
+    // `MaybeUninit<T>` has the same layout as `T`, which is all that is
+    // required to convert between weak pointers to them.
+    let weak = unsafe { Weak::from_raw(Weak::into_raw(UniqueArc::downgrade(&unique)).cast::<T>()) };
+    (unique, CyclicWeak { weak })
+}
+
+/// Constructs an `Arc` in place from an initializer which may hold weak
+/// references to the `Arc` itself.
+///
+/// This is the in-place counterpart of `Arc::new_cyclic`. The closure is
+/// given a [`CyclicWeak`] for the allocation, and returns the initializer for
+/// the value, which can use the `CyclicWeak` to initialize `Weak<T>` fields.
+/// The value is then constructed directly in the allocation. It requires the
+/// `nightly` feature, since only the unstable `UniqueArc` can hold an
+/// allocation with no strong references.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, Weak};
+/// use inplace::{init, sync};
+///
+/// struct Node {
+///     this: Weak<Node>,
+///     payload: [u8; 4096],
+/// }
+///
+/// let node: Arc<Node> = sync::emplace_cyclic(|this| {
+///     init!(Node {
+///         this <- this,
+///         payload <- init::with(|| [3; 4096]),
+///     })
+/// });
+/// assert!(Arc::ptr_eq(&node.this.upgrade().unwrap(), &node));
+/// ```
+#[cfg(all(feature = "nightly", not(inplace_no_global_oom_handling)))]
+pub fn emplace_cyclic<T, F, I>(f: F) -> Arc<T>
+where
+    F: FnOnce(CyclicWeak<T>) -> I,
+    I: Init<T>,
+{
+    let (mut unique, weak) = uninit_cyclic();
+    let init = f(weak);
+    unsafe {
+        init.initialize(unique.as_mut_ptr());
+        UniqueArc::into_arc(unique).assume_init()
+    }
+}
+
+/// Constructs a pinned `Arc` in place from a pinned initializer which may hold
+/// weak references to the `Arc` itself.
+///
+/// This is the pinned form of [`emplace_cyclic`], for values which must not be
+/// moved once constructed.
+#[cfg(all(feature = "nightly", not(inplace_no_global_oom_handling)))]
+pub fn pin_emplace_cyclic<T, F, I>(f: F) -> Pin<Arc<T>>
+where
+    F: FnOnce(CyclicWeak<T>) -> I,
+    I: PinInit<T>,
+{
+    let (mut unique, weak) = uninit_cyclic();
+    let init = f(weak);
+    unsafe {
+        init.pin_initialize(unique.as_mut_ptr());
+        Pin::new_unchecked(UniqueArc::into_arc(unique).assume_init())
+    }
+}
+
+/// Extension methods for constructing an `Arc` in place with a custom
+/// allocator.
+///
+/// These are the [`ArcExt`](crate::ArcExt) methods for sized values in an
+/// `Arc` which uses an [`Allocator`] other than the global one, and require
+/// the unstable `allocator_api`.
+///
+/// # Example
+///
+/// ```
+/// #![feature(allocator_api)]
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::alloc::System;
+/// use std::sync::Arc;
+/// use inplace::{init, ArcInExt};
+///
+/// let table: Arc<[u32; 4096], System> = Arc::emplace_in(init::with(|| [1; 4096]), System);
+/// assert_eq!(table[0], 1);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "nightly")]
+pub trait ArcInExt<T, A: Allocator>: Sized {
+    /// Allocates a shared value with `alloc` and constructs it in place.
+    ///
+    /// If the initializer panics, the allocation is freed.
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_in<I>(init: I, alloc: A) -> Self
+    where
+        I: Init<T>;
+
+    /// Allocates a shared value with `alloc` and attempts to construct it in
+    /// place.
+    ///
+    /// If the value cannot be allocated, [`InplaceError::Alloc`] is returned
+    /// and the initializer is not run. If the initializer fails, the
+    /// allocation is freed and the error is returned as
+    /// [`InplaceError::Init`].
+    fn try_emplace_in<I, E>(init: I, alloc: A) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+}
+
+#[cfg(feature = "nightly")]
+impl<T, A: Allocator> ArcInExt<T, A> for Arc<T, A> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn emplace_in<I>(init: I, alloc: A) -> Self
+    where
+        I: Init<T>,
+    {
+        let mut arc = Arc::<T, A>::new_uninit_in(alloc);
+        let slot = Arc::get_mut(&mut arc).unwrap();
+        unsafe {
+            init.initialize(slot.as_mut_ptr());
+            arc.assume_init()
+        }
+    }
+
+    fn try_emplace_in<I, E>(init: I, alloc: A) -> Result<Self, InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let mut arc = Arc::<T, A>::try_new_uninit_in(alloc)
+            .map_err(|_| InplaceError::Alloc(Layout::new::<T>()))?;
+        let slot = Arc::get_mut(&mut arc).unwrap();
+        unsafe {
+            init.try_initialize(slot.as_mut_ptr())
+                .map_err(InplaceError::Init)?;
+            Ok(arc.assume_init())
+        }
+    }
+}
@@ -0,0 +1,527 @@
+//! Allocators for testing emplacement code.
+//!
+//! The sketch points out that allocation may fail before an initializer is
+//! ever run, and the `try_` methods throughout this crate report that case
+//! rather than aborting. [`FailingAlloc`] makes it happen on demand, so that
+//! each such path can be covered deterministically.
+//!
+//! The point of emplacement is that a value is built where it will live, so
+//! [`CountingAlloc`] and [`assert_allocations!`](crate::assert_allocations!)
+//! check that a construction allocates exactly what it should, and no
+//! temporaries besides.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::Cell;
+use core::ptr;
+
+use std::alloc::System;
+
+#[cfg(feature = "nightly")]
+use core::alloc::{AllocError, Allocator};
+#[cfg(feature = "nightly")]
+use core::ptr::NonNull;
+
+std::thread_local! {
+    /// The number of allocations still to succeed before one fails, if
+    /// failures are armed on this thread.
+    static FAIL_AFTER: Cell<Option<usize>> = const { Cell::new(None) };
+    /// Whether an allocation has been failed since failures were armed.
+    static FAILED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns whether the current allocation should fail, counting it.
+fn should_fail() -> bool {
+    // The state is only read on threads which can still access it, so an
+    // allocation made while a thread is being torn down simply succeeds.
+    FAIL_AFTER
+        .try_with(|remaining| match remaining.get() {
+            Some(0) => {
+                remaining.set(None);
+                let _ = FAILED.try_with(|failed| failed.set(true));
+                true
+            }
+            Some(n) => {
+                remaining.set(Some(n - 1));
+                false
+            }
+            None => false,
+        })
+        .unwrap_or(false)
+}
+
+/// An allocator which fails a chosen allocation.
+///
+/// Allocations are passed through to the inner allocator, [`System`] by
+/// default, except while [`fail_nth`] or [`for_each_failure`] is running on
+/// the same thread, when the chosen allocation returns null instead. Other
+/// threads, such as those of the test harness, are unaffected. Reallocations
+/// count as allocations, but deallocations do not.
+///
+/// It can be installed as the `#[global_allocator]`, which covers `Box`,
+/// `Vec` and everything else using the global allocator, or, on nightly,
+/// passed to the `_in` methods as an [`Allocator`].
+///
+/// # Example
+///
+/// ```
+/// use core::convert::Infallible;
+/// use inplace::testing::{self, FailingAlloc};
+/// use inplace::{init, BoxExt, InplaceError};
+///
+/// #[global_allocator]
+/// static ALLOC: FailingAlloc = FailingAlloc::new();
+///
+/// let result: Result<Box<[u8; 256]>, InplaceError<Infallible>> =
+///     testing::fail_nth(0, || Box::try_emplace(init::fallible([0; 256])));
+/// assert!(matches!(result, Err(InplaceError::Alloc(_))));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FailingAlloc<A = System> {
+    inner: A,
+}
+
+impl FailingAlloc {
+    /// Creates an allocator which passes allocations through to [`System`].
+    pub const fn new() -> Self {
+        FailingAlloc { inner: System }
+    }
+}
+
+impl<A> FailingAlloc<A> {
+    /// Creates an allocator which passes allocations through to `inner`.
+    pub const fn wrap(inner: A) -> Self {
+        FailingAlloc { inner }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for FailingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if should_fail() {
+            return ptr::null_mut();
+        }
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if should_fail() {
+            return ptr::null_mut();
+        }
+        self.inner.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if should_fail() {
+            return ptr::null_mut();
+        }
+        self.inner.realloc(ptr, layout, new_size)
+    }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<A: Allocator> Allocator for FailingAlloc<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if should_fail() {
+            return Err(AllocError);
+        }
+        self.inner.allocate(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if should_fail() {
+            return Err(AllocError);
+        }
+        self.inner.allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.inner.deallocate(ptr, layout)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if should_fail() {
+            return Err(AllocError);
+        }
+        self.inner.grow(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if should_fail() {
+            return Err(AllocError);
+        }
+        self.inner.grow_zeroed(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if should_fail() {
+            return Err(AllocError);
+        }
+        self.inner.shrink(ptr, old_layout, new_layout)
+    }
+}
+
+/// Disarms failures when dropped, even if the closure panics.
+struct Disarm;
+
+impl Drop for Disarm {
+    fn drop(&mut self) {
+        FAIL_AFTER.with(|remaining| remaining.set(None));
+    }
+}
+
+/// Runs `f`, failing the `n`th allocation it makes on this thread through a
+/// [`FailingAlloc`], counting from zero.
+///
+/// Allocations before and after the failed one succeed. Infallible
+/// allocations abort the process when they fail, so `f` should only make
+/// the chosen allocation through a fallible path.
+pub fn fail_nth<R>(n: usize, f: impl FnOnce() -> R) -> R {
+    FAILED.with(|failed| failed.set(false));
+    FAIL_AFTER.with(|remaining| remaining.set(Some(n)));
+    let _disarm = Disarm;
+    f()
+}
+
+/// Runs `f` repeatedly, failing its first allocation, then its second, and
+/// so on, until a run completes without reaching the allocation to be failed.
+///
+/// This covers the failure of every allocation `f` makes through a
+/// [`FailingAlloc`], provided that it makes the same allocations each time.
+/// `f` is passed the index of the allocation which will fail, and the number
+/// of runs in which one failed is returned.
+///
+/// # Example
+///
+/// ```
+/// use core::convert::Infallible;
+/// use inplace::testing::{self, FailingAlloc};
+/// use inplace::{init, BoxExt, InplaceError};
+///
+/// #[global_allocator]
+/// static ALLOC: FailingAlloc = FailingAlloc::new();
+///
+/// type Emplaced = Result<Box<[u8; 64]>, InplaceError<Infallible>>;
+///
+/// let failures = testing::for_each_failure(|n| {
+///     let first: Emplaced = Box::try_emplace(init::fallible([0; 64]));
+///     let second: Emplaced = Box::try_emplace(init::fallible([0; 64]));
+///     assert_eq!(first.is_err(), n == 0);
+///     assert_eq!(second.is_err(), n == 1);
+/// });
+/// assert_eq!(failures, 2);
+/// ```
+pub fn for_each_failure(mut f: impl FnMut(usize)) -> usize {
+    let mut n = 0;
+    loop {
+        fail_nth(n, || f(n));
+        if !FAILED.with(Cell::get) {
+            return n;
+        }
+        n += 1;
+    }
+}
+
+std::thread_local! {
+    /// The allocations counted so far, if counting on this thread.
+    static COUNTS: Cell<Option<Counts>> = const { Cell::new(None) };
+}
+
+/// Running totals, which unlike [`AllocStats`] track the bytes currently
+/// allocated, since those can go negative when memory allocated before
+/// counting started is freed.
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    allocations: usize,
+    reallocations: usize,
+    deallocations: usize,
+    current: isize,
+    peak: isize,
+}
+
+/// Updates the counts on this thread, if counting.
+fn record(f: impl FnOnce(&mut Counts)) {
+    let _ = COUNTS.try_with(|counts| {
+        if let Some(mut current) = counts.get() {
+            f(&mut current);
+            current.peak = current.peak.max(current.current);
+            counts.set(Some(current));
+        }
+    });
+}
+
+/// An allocator which counts allocations.
+///
+/// Allocations are passed through to the inner allocator, [`System`] by
+/// default, and those made on a thread while it is running
+/// [`count_allocations`] are counted. Other threads are unaffected. The
+/// inner allocator may be a [`FailingAlloc`], to count and fail allocations
+/// in the same program.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::testing::{self, CountingAlloc};
+/// use inplace::{init, BoxExt};
+///
+/// #[global_allocator]
+/// static ALLOC: CountingAlloc = CountingAlloc::new();
+///
+/// let (buf, stats) = testing::count_allocations(|| {
+///     Box::<[u8; 1 << 20]>::emplace(init::with(|| [0; 1 << 20]))
+/// });
+/// assert_eq!(stats.allocations, 1);
+/// assert_eq!(stats.peak_bytes, 1 << 20);
+/// # drop(buf);
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountingAlloc<A = System> {
+    inner: A,
+}
+
+impl CountingAlloc {
+    /// Creates an allocator which passes allocations through to [`System`].
+    pub const fn new() -> Self {
+        CountingAlloc { inner: System }
+    }
+}
+
+impl<A> CountingAlloc<A> {
+    /// Creates an allocator which passes allocations through to `inner`.
+    pub const fn wrap(inner: A) -> Self {
+        CountingAlloc { inner }
+    }
+}
+
+/// Records a successful allocation of `size` bytes.
+fn record_alloc(ptr: *mut u8, size: usize) {
+    if !ptr.is_null() {
+        record(|counts| {
+            counts.allocations += 1;
+            counts.current += size as isize;
+        });
+    }
+}
+
+/// Records a successful reallocation from `old` to `new` bytes.
+fn record_realloc(ptr: *mut u8, old: usize, new: usize) {
+    if !ptr.is_null() {
+        record(|counts| {
+            counts.reallocations += 1;
+            counts.current += new as isize - old as isize;
+        });
+    }
+}
+
+/// Records a deallocation of `size` bytes.
+fn record_dealloc(size: usize) {
+    record(|counts| {
+        counts.deallocations += 1;
+        counts.current -= size as isize;
+    });
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        record_alloc(ptr, layout.size());
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        record_alloc(ptr, layout.size());
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let ptr = self.inner.realloc(ptr, layout, new_size);
+        record_realloc(ptr, layout.size(), new_size);
+        ptr
+    }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl<A: Allocator> Allocator for CountingAlloc<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.inner.allocate(layout)?;
+        record_alloc(ptr.as_ptr().cast(), layout.size());
+        Ok(ptr)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.inner.allocate_zeroed(layout)?;
+        record_alloc(ptr.as_ptr().cast(), layout.size());
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.inner.deallocate(ptr, layout);
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.inner.grow(ptr, old_layout, new_layout)?;
+        record_realloc(ptr.as_ptr().cast(), old_layout.size(), new_layout.size());
+        Ok(ptr)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.inner.grow_zeroed(ptr, old_layout, new_layout)?;
+        record_realloc(ptr.as_ptr().cast(), old_layout.size(), new_layout.size());
+        Ok(ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.inner.shrink(ptr, old_layout, new_layout)?;
+        record_realloc(ptr.as_ptr().cast(), old_layout.size(), new_layout.size());
+        Ok(ptr)
+    }
+}
+
+/// The allocations counted by [`count_allocations`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AllocStats {
+    /// The number of successful allocations.
+    pub allocations: usize,
+    /// The number of successful reallocations, whether growing or shrinking.
+    pub reallocations: usize,
+    /// The number of deallocations.
+    pub deallocations: usize,
+    /// The greatest number of bytes allocated at once, over what was
+    /// allocated when counting started.
+    pub peak_bytes: usize,
+}
+
+/// Restores the counts of an enclosing call when dropped, adding the counts
+/// of this one to them.
+struct Restore(Option<Counts>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let inner = COUNTS.with(Cell::get).unwrap_or_default();
+        let outer = self.0.map(|mut outer| {
+            outer.allocations += inner.allocations;
+            outer.reallocations += inner.reallocations;
+            outer.deallocations += inner.deallocations;
+            outer.peak = outer.peak.max(outer.current + inner.peak);
+            outer.current += inner.current;
+            outer
+        });
+        COUNTS.with(|counts| counts.set(outer));
+    }
+}
+
+/// Runs `f`, counting the allocations it makes on this thread through a
+/// [`CountingAlloc`].
+///
+/// Calls may be nested, in which case the enclosing call counts the
+/// allocations of the inner one as well.
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+    let restore = Restore(COUNTS.with(|counts| counts.replace(Some(Counts::default()))));
+    let result = f();
+    let counts = COUNTS.with(Cell::get).unwrap_or_default();
+    drop(restore);
+    let stats = AllocStats {
+        allocations: counts.allocations,
+        reallocations: counts.reallocations,
+        deallocations: counts.deallocations,
+        peak_bytes: counts.peak as usize,
+    };
+    (result, stats)
+}
+
+/// Evaluates an expression, asserting how it allocates.
+///
+/// The expression is run under [`count_allocations`], so a [`CountingAlloc`]
+/// must be installed as the global allocator, and each named field of the
+/// resulting [`AllocStats`] is compared with the value given. The value of
+/// the expression is returned.
+///
+/// [`count_allocations`]: crate::testing::count_allocations
+/// [`CountingAlloc`]: crate::testing::CountingAlloc
+/// [`AllocStats`]: crate::testing::AllocStats
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use inplace::testing::CountingAlloc;
+/// use inplace::{assert_allocations, init, BoxExt};
+///
+/// #[global_allocator]
+/// static ALLOC: CountingAlloc = CountingAlloc::new();
+///
+/// struct Table {
+///     len: usize,
+///     entries: [u64; 1 << 16],
+/// }
+///
+/// // One allocation of exactly the table, with no temporary copy of it.
+/// let table = assert_allocations!(allocations: 1, reallocations: 0, peak_bytes: 8 + (8 << 16);
+///     Box::emplace(init!(Table { len: 0, entries <- init::with(|| [0; 1 << 16]) }))
+/// );
+/// let _: &Box<Table> = &table;
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! assert_allocations {
+    ($($field:ident: $expected:expr),+ $(,)?; $body:expr $(,)?) => {{
+        let (value, stats) = $crate::testing::count_allocations(|| $body);
+        $(
+            ::core::assert_eq!(
+                stats.$field,
+                $expected,
+                ::core::concat!("unexpected `", ::core::stringify!($field), "` in {:?}"),
+                stats,
+            );
+        )+
+        value
+    }};
+}
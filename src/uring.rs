@@ -0,0 +1,357 @@
+//! Buffers registered with io_uring, which I/O completes into directly.
+//!
+//! Registering buffers with an io_uring instance lets the kernel map them
+//! once, rather than on every operation. A [`FixedRing`] allocates its
+//! buffers uninitialized and registers them, so that a fixed read constructs
+//! a value in place: the bytes go from the device into the buffer, and the
+//! buffer is then the value, with no copy in between. In the other
+//! direction, a value constructed in a buffer by an initializer is written
+//! out from where it was built.
+
+use core::alloc::Layout;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use std::io::{self, ErrorKind};
+use std::os::fd::{AsFd, AsRawFd};
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::init::{AnyBitPattern, Init};
+use crate::raw;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Empty,
+    Full,
+    Reading,
+    Writing,
+}
+
+/// The outcome of an operation on a buffer of a [`FixedRing`].
+#[derive(Debug)]
+pub struct Completion {
+    index: usize,
+    result: io::Result<()>,
+}
+
+impl Completion {
+    /// Returns the index of the buffer the operation used.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the index of the buffer if the operation succeeded, or its
+    /// error.
+    ///
+    /// A read or write which transferred fewer bytes than the buffer holds
+    /// fails with [`ErrorKind::UnexpectedEof`] or [`ErrorKind::WriteZero`].
+    pub fn into_result(self) -> io::Result<usize> {
+        self.result.map(|()| self.index)
+    }
+}
+
+/// An io_uring instance with buffers for values of `T` registered with it.
+///
+/// Each buffer is empty until a value is constructed in it, by a completed
+/// [`read`](FixedRing::read) or by [`emplace`](FixedRing::emplace). A buffer
+/// with an operation in flight can't be accessed until the operation is
+/// reaped with [`complete`](FixedRing::complete). Dropping the ring waits for
+/// any operations still in flight before the buffers are freed.
+///
+/// # Example
+///
+/// ```
+/// use std::fs::File;
+/// use inplace::{init, uring::FixedRing};
+///
+/// let path = std::env::temp_dir().join("inplace-uring-example");
+/// let file = File::options().read(true).write(true).create(true).truncate(true).open(&path)?;
+/// let mut ring = FixedRing::<[u64; 512]>::new(8, 2)?;
+///
+/// // Construct a page in place in buffer 0, and write it out from there.
+/// ring.emplace(0, init::array_from_fn(|i| i as u64));
+/// ring.write(0, &file, 0)?;
+/// assert_eq!(ring.complete()?.into_result()?, 0);
+///
+/// // Read it back into buffer 1, where the read constructs the value.
+/// ring.read(1, &file, 0)?;
+/// assert_eq!(ring.complete()?.into_result()?, 1);
+/// assert_eq!(ring.get(1).unwrap()[511], 511);
+///
+/// drop(file);
+/// std::fs::remove_file(path)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct FixedRing<T> {
+    ring: IoUring,
+    buffers: NonNull<T>,
+    states: Box<[State]>,
+    in_flight: usize,
+}
+
+unsafe impl<T: Send> Send for FixedRing<T> {}
+unsafe impl<T: Sync> Sync for FixedRing<T> {}
+
+impl<T> FixedRing<T> {
+    /// Creates an io_uring instance with `entries` submission queue entries,
+    /// and registers `count` empty buffers for values of `T` with it.
+    ///
+    /// Failure to allocate the buffers is reported as
+    /// [`ErrorKind::OutOfMemory`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero-sized or larger than `u32::MAX` bytes, or if
+    /// `count` is larger than `u16::MAX`.
+    pub fn new(entries: u32, count: usize) -> io::Result<Self> {
+        let size = mem::size_of::<T>();
+        assert!(size != 0, "buffers must not be zero-sized");
+        assert!(u32::try_from(size).is_ok(), "buffers must fit in a u32");
+        assert!(count <= u16::MAX as usize, "too many buffers");
+        let ring = IoUring::new(entries)?;
+        let layout = Self::layout(count);
+        let buffers = raw::try_allocate(layout)
+            .ok_or_else(|| io::Error::from(ErrorKind::OutOfMemory))?
+            .cast::<T>();
+        let iovecs: Vec<libc::iovec> = (0..count)
+            .map(|index| libc::iovec {
+                iov_base: unsafe { buffers.as_ptr().add(index).cast() },
+                iov_len: size,
+            })
+            .collect();
+        // The buffers are only freed after the ring is dropped, which
+        // unregisters them.
+        if let Err(error) = unsafe { ring.submitter().register_buffers(&iovecs) } {
+            unsafe { raw::deallocate(buffers.cast(), layout) };
+            return Err(error);
+        }
+        Ok(FixedRing {
+            ring,
+            buffers,
+            states: (0..count).map(|_| State::Empty).collect(),
+            in_flight: 0,
+        })
+    }
+
+    fn layout(count: usize) -> Layout {
+        Layout::array::<T>(count).expect("buffers overflow layout")
+    }
+
+    /// Returns the number of buffers.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Returns true if there are no buffers.
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    fn buffer(&self, index: usize) -> *mut T {
+        assert!(index < self.len(), "buffer index out of bounds");
+        unsafe { self.buffers.as_ptr().add(index) }
+    }
+
+    /// Returns the value in a buffer, if it holds one and has no operation
+    /// in flight.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let buffer = self.buffer(index);
+        (self.states[index] == State::Full).then(|| unsafe { &*buffer })
+    }
+
+    /// Returns the value in a buffer mutably, if it holds one and has no
+    /// operation in flight.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let buffer = self.buffer(index);
+        (self.states[index] == State::Full).then(|| unsafe { &mut *buffer })
+    }
+
+    /// Constructs a value in place in a buffer, dropping any value it held.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer has an operation in flight.
+    pub fn emplace<I>(&mut self, index: usize, init: I) -> &mut T
+    where
+        I: Init<T>,
+    {
+        self.clear(index);
+        let buffer = self.buffer(index);
+        unsafe {
+            init.initialize(buffer);
+            self.states[index] = State::Full;
+            &mut *buffer
+        }
+    }
+
+    /// Drops the value in a buffer, if it holds one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer has an operation in flight.
+    pub fn clear(&mut self, index: usize) {
+        let buffer = self.buffer(index);
+        match self.states[index] {
+            State::Empty => {}
+            State::Full => {
+                self.states[index] = State::Empty;
+                unsafe { ptr::drop_in_place(buffer) };
+            }
+            State::Reading | State::Writing => panic!("buffer has an operation in flight"),
+        }
+    }
+
+    /// Submits a write of the value in a buffer to `fd` at `offset`.
+    ///
+    /// The buffer can't be accessed until the write completes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer holds no value, or has an operation in flight.
+    pub fn write(&mut self, index: usize, fd: &impl AsFd, offset: u64) -> io::Result<()> {
+        let buffer = self.buffer(index);
+        match self.states[index] {
+            State::Full => {}
+            State::Empty => panic!("buffer holds no value"),
+            State::Reading | State::Writing => panic!("buffer has an operation in flight"),
+        }
+        let entry = opcode::WriteFixed::new(
+            types::Fd(fd.as_fd().as_raw_fd()),
+            buffer.cast(),
+            mem::size_of::<T>() as u32,
+            index as u16,
+        )
+        .offset(offset)
+        .build();
+        self.submit(index, entry, State::Writing)
+    }
+
+    /// Queues an operation on a buffer and submits it to the kernel.
+    ///
+    /// If only the submission fails, the operation stays queued, and is
+    /// submitted by the next call to `complete`.
+    fn submit(
+        &mut self,
+        index: usize,
+        entry: io_uring::squeue::Entry,
+        state: State,
+    ) -> io::Result<()> {
+        let entry = entry.user_data(index as u64);
+        if self.ring.submission().is_full() {
+            self.ring.submit()?;
+        }
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|_| io::Error::from(ErrorKind::WouldBlock))?;
+        self.states[index] = state;
+        self.in_flight += 1;
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    /// Waits for an operation to complete, and returns its outcome.
+    ///
+    /// A read which filled its buffer leaves a value in it. A read which
+    /// failed leaves the buffer empty, and a write leaves its value in the
+    /// buffer whatever the outcome.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no operation is in flight.
+    pub fn complete(&mut self) -> io::Result<Completion> {
+        assert!(self.in_flight > 0, "no operation in flight");
+        loop {
+            let entry = self.ring.completion().next();
+            if let Some(entry) = entry {
+                return Ok(self.reap(entry));
+            }
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    fn reap(&mut self, entry: io_uring::cqueue::Entry) -> Completion {
+        let index = entry.user_data() as usize;
+        let res = entry.result();
+        let size = mem::size_of::<T>();
+        self.in_flight -= 1;
+        let reading = self.states[index] == State::Reading;
+        let result = if res < 0 {
+            Err(io::Error::from_raw_os_error(-res))
+        } else if res as usize != size && reading {
+            Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ))
+        } else if res as usize != size {
+            Err(io::Error::new(
+                ErrorKind::WriteZero,
+                "failed to write whole buffer",
+            ))
+        } else {
+            Ok(())
+        };
+        self.states[index] = match (reading, &result) {
+            (true, Ok(())) => State::Full,
+            (true, Err(_)) => State::Empty,
+            (false, _) => State::Full,
+        };
+        Completion { index, result }
+    }
+}
+
+impl<T: AnyBitPattern> FixedRing<T> {
+    /// Submits a read from `fd` at `offset` into a buffer, dropping any
+    /// value it held.
+    ///
+    /// Once the read completes, the bytes read are the value. `T` must be
+    /// valid for any bytes, since the file may contain anything.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer has an operation in flight.
+    pub fn read(&mut self, index: usize, fd: &impl AsFd, offset: u64) -> io::Result<()> {
+        self.clear(index);
+        let buffer = self.buffer(index);
+        let entry = opcode::ReadFixed::new(
+            types::Fd(fd.as_fd().as_raw_fd()),
+            buffer.cast(),
+            mem::size_of::<T>() as u32,
+            index as u16,
+        )
+        .offset(offset)
+        .build();
+        self.submit(index, entry, State::Reading)
+    }
+}
+
+impl<T> Drop for FixedRing<T> {
+    fn drop(&mut self) {
+        while self.in_flight > 0 {
+            let entry = self.ring.completion().next();
+            if let Some(entry) = entry {
+                self.reap(entry);
+                continue;
+            }
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                // The kernel may still write to the buffers, so they can
+                // only be leaked.
+                Err(_) => return,
+            }
+        }
+        for index in 0..self.len() {
+            self.clear(index);
+        }
+        unsafe {
+            let _ = self.ring.submitter().unregister_buffers();
+            raw::deallocate(self.buffers.cast(), Self::layout(self.len()));
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! Emplacement into `Vec`.
+
+use core::alloc::Layout;
+use core::convert::Infallible;
+#[cfg(feature = "nightly")]
+use core::io::{BorrowedBuf, BorrowedCursor};
+use core::mem;
+use core::ptr;
+
+use alloc::vec::Vec;
+
+use crate::error::InplaceError;
+use crate::init::{Init, TryInit};
+
+/// Extension methods for constructing `Vec` elements in place.
+///
+/// `Vec::push` cannot be changed to accept `?inplace T` as the sketch
+/// proposes, so these methods provide the in-place path alongside it.
+pub trait VecExt<T> {
+    /// Appends an element, constructing it directly in the vector's buffer.
+    ///
+    /// Capacity is reserved first, then the initializer is run on the first
+    /// spare slot. If the initializer panics, the vector is unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::{init, VecExt};
+    ///
+    /// let mut blocks: Vec<[u8; 4096]> = Vec::new();
+    /// blocks.push_init(init::with(|| [0xee; 4096]));
+    /// assert_eq!(blocks[0][4095], 0xee);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_init<I>(&mut self, init: I)
+    where
+        I: Init<T>;
+
+    /// Attempts to append an element, constructing it directly in the
+    /// vector's buffer.
+    ///
+    /// If capacity cannot be reserved, [`InplaceError::Alloc`] is returned
+    /// with the layout of the element, and the initializer is not run. If the
+    /// initializer fails, its error is returned and the vector is unchanged.
+    fn try_push_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+
+    /// Inserts an element at `index`, constructing it directly in the gap.
+    ///
+    /// The elements after `index` are shifted up by one, and the initializer
+    /// is then run on the vacated slot. If the initializer panics, the
+    /// elements are shifted back and the vector is unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::{init, VecExt};
+    ///
+    /// let mut rows = vec![[1u16; 512], [3; 512]];
+    /// rows.insert_init(1, init::with(|| [2; 512]));
+    /// assert_eq!([rows[0][0], rows[1][0], rows[2][0]], [1, 2, 3]);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn insert_init<I>(&mut self, index: usize, init: I)
+    where
+        I: Init<T>;
+
+    /// Attempts to insert an element at `index`, constructing it directly in
+    /// the gap.
+    ///
+    /// If capacity cannot be reserved, [`InplaceError::Alloc`] is returned
+    /// with the layout of the element, and the initializer is not run. If the
+    /// initializer fails, the elements are shifted back and its error is
+    /// returned. In either case the vector is unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    fn try_insert_init<I, E>(&mut self, index: usize, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>;
+
+    /// Appends an element for each initializer yielded by `iter`,
+    /// constructing each directly in the vector's buffer.
+    ///
+    /// Capacity for the iterator's lower size bound is reserved once, up
+    /// front. The length is updated after each element, so if an initializer
+    /// or the iterator panics, the vector holds the elements appended before
+    /// it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::{init, VecExt};
+    ///
+    /// let mut tiles: Vec<[u8; 1024]> = Vec::new();
+    /// tiles.extend_init((0..4).map(|i| init::with(move || [i; 1024])));
+    /// assert_eq!(tiles.len(), 4);
+    /// assert_eq!(tiles[3][1023], 3);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn extend_init<It, I>(&mut self, iter: It)
+    where
+        It: IntoIterator<Item = I>,
+        I: Init<T>;
+
+    /// Attempts to append an element for each initializer yielded by `iter`,
+    /// constructing each directly in the vector's buffer.
+    ///
+    /// Stops at the first initializer which cannot be allocated for or which
+    /// fails, and returns the error as [`try_push_init`](VecExt::try_push_init)
+    /// does. The elements appended before it remain in the vector.
+    fn try_extend_init<It, I, E>(&mut self, iter: It) -> Result<(), InplaceError<E>>
+    where
+        It: IntoIterator<Item = I>,
+        I: TryInit<T, E>;
+
+    /// Reserves space for `additional` elements and returns a placement
+    /// target for constructing them in the vector's spare capacity.
+    ///
+    /// Elements constructed through the returned [`Spare`] are not part of
+    /// the vector until [`Spare::commit`] is called, which adds them all with
+    /// a single length update. If the `Spare` is dropped without committing,
+    /// including during a panic, the elements constructed so far are dropped
+    /// and the vector is unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use inplace::{init, VecExt};
+    ///
+    /// let mut pages: Vec<[u8; 4096]> = Vec::new();
+    /// let mut spare = pages.spare(3);
+    /// for byte in 0..3 {
+    ///     spare.push_init(init::with(move || [byte; 4096]));
+    /// }
+    /// spare.commit();
+    /// assert_eq!(pages.len(), 3);
+    /// assert_eq!(pages[2][0], 2);
+    /// ```
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn spare(&mut self, additional: usize) -> Spare<'_, T>;
+
+    /// Attempts to reserve space for `additional` elements and return a
+    /// placement target for constructing them, as [`spare`](VecExt::spare)
+    /// does.
+    ///
+    /// If capacity cannot be reserved, [`InplaceError::Alloc`] is returned
+    /// with the layout of the additional elements.
+    fn try_spare(&mut self, additional: usize) -> Result<Spare<'_, T>, InplaceError<Infallible>>;
+}
+
+/// A placement target for constructing elements in a vector's spare
+/// capacity.
+///
+/// Created by [`VecExt::spare`].
+pub struct Spare<'a, T> {
+    vec: &'a mut Vec<T>,
+    additional: usize,
+    written: usize,
+}
+
+impl<T> Spare<'_, T> {
+    /// Returns the number of elements constructed so far.
+    pub fn len(&self) -> usize {
+        self.written
+    }
+
+    /// Returns true if no elements have been constructed yet.
+    pub fn is_empty(&self) -> bool {
+        self.written == 0
+    }
+
+    /// Returns the number of further elements which can be constructed.
+    pub fn remaining(&self) -> usize {
+        self.additional - self.written
+    }
+
+    /// Constructs the next element in place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the space reserved has been used up.
+    pub fn push_init<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        assert!(self.remaining() > 0, "no spare capacity remaining");
+        unsafe {
+            let slot = self.vec.as_mut_ptr().add(self.vec.len() + self.written);
+            init.initialize(slot);
+        }
+        self.written += 1;
+    }
+
+    /// Adds the elements constructed so far to the vector.
+    pub fn commit(mut self) {
+        unsafe { self.vec.set_len(self.vec.len() + self.written) };
+        self.written = 0;
+    }
+}
+
+#[cfg(feature = "nightly")]
+impl Spare<'_, u8> {
+    /// Passes the remaining space to `f` as a [`BorrowedCursor`], and counts
+    /// the bytes written through the cursor as constructed.
+    ///
+    /// This lets [`Read::read_buf`](std::io::Read::read_buf), and other APIs
+    /// which write bytes into uninitialized memory, fill the spare capacity
+    /// directly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #![feature(read_buf)]
+    /// # #[cfg(not(inplace_no_global_oom_handling))]
+    /// # fn main() {
+    /// use std::io::Read;
+    /// use inplace::VecExt;
+    ///
+    /// let mut received = b"GET ".to_vec();
+    /// let mut socket: &[u8] = b"/index.html";
+    /// let mut spare = received.spare(1024);
+    /// let read = spare.fill_with(|cursor| socket.read_buf(cursor));
+    /// assert!(read.is_ok());
+    /// assert_eq!(spare.len(), 11);
+    /// spare.commit();
+    /// assert_eq!(received, b"GET /index.html");
+    /// # }
+    /// # #[cfg(inplace_no_global_oom_handling)]
+    /// # fn main() {}
+    /// ```
+    pub fn fill_with<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(BorrowedCursor<'_>) -> R,
+    {
+        let unfilled = &mut self.vec.spare_capacity_mut()[self.written..self.additional];
+        let mut buf = BorrowedBuf::from(unfilled);
+        let result = f(buf.unfilled());
+        self.written += buf.len();
+        result
+    }
+}
+
+impl<T> Drop for Spare<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            let start = self.vec.as_mut_ptr().add(self.vec.len());
+            ptr::slice_from_raw_parts_mut(start, self.written).drop_in_place();
+        }
+    }
+}
+
+/// Shifts the tail of a vector back down over a gap, unless forgotten.
+struct CloseGap<'a, T> {
+    vec: &'a mut Vec<T>,
+    index: usize,
+    len: usize,
+}
+
+impl<T> Drop for CloseGap<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            let gap = self.vec.as_mut_ptr().add(self.index);
+            ptr::copy(gap.add(1), gap, self.len - self.index);
+            self.vec.set_len(self.len);
+        }
+    }
+}
+
+impl<T> VecExt<T> for Vec<T> {
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn push_init<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        self.reserve(1);
+        let len = self.len();
+        unsafe {
+            init.initialize(self.as_mut_ptr().add(len));
+            self.set_len(len + 1);
+        }
+    }
+
+    fn try_push_init<I, E>(&mut self, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        self.try_reserve(1)
+            .map_err(|_| InplaceError::Alloc(init.layout()))?;
+        let len = self.len();
+        unsafe {
+            init.try_initialize(self.as_mut_ptr().add(len))
+                .map_err(InplaceError::Init)?;
+            self.set_len(len + 1);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn insert_init<I>(&mut self, index: usize, init: I)
+    where
+        I: Init<T>,
+    {
+        let len = self.len();
+        assert!(
+            index <= len,
+            "insertion index (is {index}) should be <= len (is {len})"
+        );
+        self.reserve(1);
+        unsafe {
+            let gap = self.as_mut_ptr().add(index);
+            // While the gap is open, the vector only owns the elements
+            // before it, so that nothing is dropped twice if we panic.
+            self.set_len(index);
+            ptr::copy(gap, gap.add(1), len - index);
+            let guard = CloseGap {
+                vec: self,
+                index,
+                len,
+            };
+            init.initialize(gap);
+            mem::forget(guard);
+            self.set_len(len + 1);
+        }
+    }
+
+    fn try_insert_init<I, E>(&mut self, index: usize, init: I) -> Result<(), InplaceError<E>>
+    where
+        I: TryInit<T, E>,
+    {
+        let len = self.len();
+        assert!(
+            index <= len,
+            "insertion index (is {index}) should be <= len (is {len})"
+        );
+        self.try_reserve(1)
+            .map_err(|_| InplaceError::Alloc(init.layout()))?;
+        unsafe {
+            let gap = self.as_mut_ptr().add(index);
+            self.set_len(index);
+            ptr::copy(gap, gap.add(1), len - index);
+            let guard = CloseGap {
+                vec: self,
+                index,
+                len,
+            };
+            // On failure, the guard closes the gap again.
+            init.try_initialize(gap).map_err(InplaceError::Init)?;
+            mem::forget(guard);
+            self.set_len(len + 1);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn extend_init<It, I>(&mut self, iter: It)
+    where
+        It: IntoIterator<Item = I>,
+        I: Init<T>,
+    {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for init in iter {
+            self.push_init(init);
+        }
+    }
+
+    fn try_extend_init<It, I, E>(&mut self, iter: It) -> Result<(), InplaceError<E>>
+    where
+        It: IntoIterator<Item = I>,
+        I: TryInit<T, E>,
+    {
+        let iter = iter.into_iter();
+        // Reserving for the lower bound is only an optimization, so failure
+        // is left to be reported by the element which needs the space.
+        let _ = self.try_reserve(iter.size_hint().0);
+        for init in iter {
+            self.try_push_init(init)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(inplace_no_global_oom_handling))]
+    fn spare(&mut self, additional: usize) -> Spare<'_, T> {
+        self.reserve(additional);
+        Spare {
+            vec: self,
+            additional,
+            written: 0,
+        }
+    }
+
+    fn try_spare(&mut self, additional: usize) -> Result<Spare<'_, T>, InplaceError<Infallible>> {
+        self.try_reserve(additional).map_err(|_| {
+            InplaceError::Alloc(Layout::array::<T>(additional).unwrap_or(Layout::new::<T>()))
+        })?;
+        Ok(Spare {
+            vec: self,
+            additional,
+            written: 0,
+        })
+    }
+}
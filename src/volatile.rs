@@ -0,0 +1,155 @@
+//! Placement into memory-mapped or device-shared regions.
+//!
+//! Memory which a device reads, such as a register block or a descriptor
+//! ring, must be written with volatile writes, or the compiler may merge,
+//! reorder or remove them. An initializer writes its destination with
+//! ordinary writes, so here the value is constructed in a local buffer, and
+//! then copied out with volatile writes, in units of its alignment. This is
+//! restricted to [`PlainData`] types, whose bytes can be copied this way.
+
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+
+use crate::init::{Init, TryInit};
+
+/// A `#[repr(C)]` type made only of plain data.
+///
+/// # Safety
+///
+/// The type must be `#[repr(C)]` or a primitive integer, with no padding
+/// bytes, and every bit pattern must be a valid value, since a device may
+/// have written anything to its memory.
+pub unsafe trait PlainData: Copy + 'static {}
+
+macro_rules! plain_data {
+    ($($ty:ty),*) => {
+        $(unsafe impl PlainData for $ty {})*
+    };
+}
+
+plain_data!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {}
+
+/// A place in device memory which holds a `T`.
+///
+/// # Example
+///
+/// ```
+/// use inplace::init;
+/// use inplace::volatile::{PlainData, VolatilePlace};
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Descriptor {
+///     addr: u32,
+///     len: u16,
+///     flags: u16,
+/// }
+///
+/// unsafe impl PlainData for Descriptor {}
+///
+/// // Stands in for a descriptor in memory shared with a DMA engine.
+/// let mut shared = core::mem::MaybeUninit::<Descriptor>::zeroed();
+///
+/// let mut place = unsafe { VolatilePlace::new(shared.as_mut_ptr()) };
+/// place.emplace(init::with(|| Descriptor { addr: 0x2000_0000, len: 512, flags: 1 }));
+/// assert_eq!(place.read().len, 512);
+/// ```
+pub struct VolatilePlace<T: PlainData> {
+    ptr: NonNull<T>,
+}
+
+unsafe impl<T: PlainData + Send> Send for VolatilePlace<T> {}
+
+impl<T: PlainData> VolatilePlace<T> {
+    /// Creates a place at the given address.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null, aligned, and valid for volatile reads and
+    /// writes of a `T` for as long as the place is used, and no other code
+    /// may access it through Rust references meanwhile. The memory must be
+    /// initialized, as it is read with [`read`](VolatilePlace::read).
+    pub const unsafe fn new(ptr: *mut T) -> Self {
+        VolatilePlace {
+            ptr: NonNull::new_unchecked(ptr),
+        }
+    }
+
+    /// Returns the address of the place.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    /// Constructs a value and writes it to the place.
+    ///
+    /// If the initializer panics, the place is not written.
+    pub fn emplace<I>(&mut self, init: I)
+    where
+        I: Init<T>,
+    {
+        let mut staged = MaybeUninit::<T>::uninit();
+        unsafe {
+            init.initialize(staged.as_mut_ptr());
+            self.copy_out(&staged);
+        }
+    }
+
+    /// Attempts to construct a value and write it to the place.
+    ///
+    /// If the initializer fails, the place is not written.
+    pub fn try_emplace<I, E>(&mut self, init: I) -> Result<(), E>
+    where
+        I: TryInit<T, E>,
+    {
+        let mut staged = MaybeUninit::<T>::uninit();
+        unsafe {
+            init.try_initialize(staged.as_mut_ptr())?;
+            self.copy_out(&staged);
+        }
+        Ok(())
+    }
+
+    /// Writes a value to the place.
+    pub fn write(&mut self, value: T) {
+        unsafe { self.copy_out(&MaybeUninit::new(value)) }
+    }
+
+    /// Reads the value in the place, in units of its alignment.
+    pub fn read(&self) -> T {
+        let mut value = MaybeUninit::<T>::uninit();
+        unsafe {
+            copy_volatile(self.ptr.as_ptr(), value.as_mut_ptr(), false);
+            value.assume_init()
+        }
+    }
+
+    /// Copies a complete value to the place.
+    unsafe fn copy_out(&mut self, staged: &MaybeUninit<T>) {
+        copy_volatile(staged.as_ptr(), self.ptr.as_ptr(), true);
+    }
+}
+
+/// Copies a `T` in units of its alignment, up to the size of a `u64`, making
+/// the accesses on the device side volatile: writes to `dst` if `to_device`,
+/// and reads from `src` otherwise.
+unsafe fn copy_volatile<T: PlainData>(src: *const T, dst: *mut T, to_device: bool) {
+    unsafe fn copy_units<T, U: Copy>(src: *const T, dst: *mut T, to_device: bool) {
+        let (src, dst) = (src.cast::<U>(), dst.cast::<U>());
+        for i in 0..mem::size_of::<T>() / mem::size_of::<U>() {
+            if to_device {
+                ptr::write_volatile(dst.add(i), src.add(i).read());
+            } else {
+                dst.add(i).write(ptr::read_volatile(src.add(i)));
+            }
+        }
+    }
+
+    match mem::align_of::<T>() {
+        1 => copy_units::<T, u8>(src, dst, to_device),
+        2 => copy_units::<T, u16>(src, dst, to_device),
+        4 => copy_units::<T, u32>(src, dst, to_device),
+        _ => copy_units::<T, u64>(src, dst, to_device),
+    }
+}
@@ -0,0 +1,351 @@
+//! Byte slices written by a closure through a sink.
+//!
+//! Serializers write their output through a writer, one piece at a time, so
+//! their output usually lands in a growing `Vec<u8>` and is copied into its
+//! final allocation afterwards. Here the serializer is instead given a
+//! [`Sink`] over the final allocation itself, which is uninitialized and
+//! bounded by its size. [`exact`] is an initializer for output whose length
+//! is known in advance, and [`bounded`] allocates a `Box<[u8]>` for output
+//! whose length is only bounded, and shrinks it to fit once written.
+
+use core::alloc::Layout;
+use core::error::Error;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::slice;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "alloc")]
+use crate::error::InplaceError;
+use crate::init::TryInit;
+#[cfg(feature = "alloc")]
+use crate::raw::{self, DeallocGuard};
+
+/// A writer into uninitialized bytes, which can hold no more than they do.
+///
+/// Bytes are appended with [`write_all`](Sink::write_all), or through
+/// [`fmt::Write`], or with the `std` feature, through `std::io::Write`. A
+/// write which would not fit fails, and writes nothing.
+pub struct Sink<'a> {
+    buf: &'a mut [MaybeUninit<u8>],
+    len: usize,
+}
+
+impl<'a> Sink<'a> {
+    /// Creates a sink writing to the start of `buf`.
+    pub fn new(buf: &'a mut [MaybeUninit<u8>]) -> Self {
+        Sink { buf, len: 0 }
+    }
+
+    /// Returns the number of bytes the sink can hold in total.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the number of bytes written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes which can still be written.
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.len
+    }
+
+    /// Returns the bytes written so far.
+    pub fn written(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.buf.as_ptr().cast(), self.len) }
+    }
+
+    /// Appends `bytes`, if they fit.
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), Overflow> {
+        if bytes.len() > self.remaining() {
+            return Err(Overflow {
+                capacity: self.capacity(),
+            });
+        }
+        unsafe {
+            let dest = self.buf.as_mut_ptr().add(self.len).cast::<u8>();
+            ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+        }
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    /// Returns the number of bytes written, having checked that this is
+    /// still the sink created over the bytes at `start`.
+    ///
+    /// A closure given `&mut Sink` can replace the sink with one over other
+    /// memory, whose length says nothing about the bytes at `start`.
+    fn len_over(&self, start: *const MaybeUninit<u8>) -> usize {
+        assert!(
+            ptr::eq(self.buf.as_ptr(), start),
+            "the closure replaced the sink it was given"
+        );
+        self.len
+    }
+}
+
+impl fmt::Debug for Sink<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sink")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl fmt::Write for Sink<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Write for Sink<'_> {
+    /// Writes as many of the bytes as fit, which is none once the sink is
+    /// full, so `write_all` then fails with
+    /// [`WriteZero`](io::ErrorKind::WriteZero).
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining());
+        self.write_all(&buf[..len]).map_err(io::Error::from)?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The error returned when a write doesn't fit in a [`Sink`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overflow {
+    capacity: usize,
+}
+
+impl Overflow {
+    /// Returns the capacity of the sink which overflowed.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "write overflows a sink of {} bytes", self.capacity)
+    }
+}
+
+impl Error for Overflow {}
+
+impl From<Overflow> for fmt::Error {
+    fn from(_: Overflow) -> fmt::Error {
+        fmt::Error
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Overflow> for io::Error {
+    fn from(overflow: Overflow) -> io::Error {
+        io::Error::new(io::ErrorKind::WriteZero, overflow)
+    }
+}
+
+/// The error returned when the closure of [`exact`] fails, or writes too few
+/// bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteError<E> {
+    /// The closure returned an error.
+    Write(E),
+    /// The closure returned successfully, having written only this many
+    /// bytes.
+    Short(usize),
+}
+
+impl<E: fmt::Display> fmt::Display for WriteError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::Write(error) => write!(f, "writer failed: {error}"),
+            WriteError::Short(len) => write!(f, "writer wrote only {len} bytes"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for WriteError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WriteError::Write(error) => Some(error),
+            WriteError::Short(_) => None,
+        }
+    }
+}
+
+/// An initializer for a byte slice written through a [`Sink`].
+///
+/// Created by [`exact`].
+pub struct Exact<F, E> {
+    len: usize,
+    f: F,
+    _marker: PhantomData<fn() -> E>,
+}
+
+/// Creates an initializer for a byte slice of `len` bytes, written by `f`
+/// through a sink over the destination.
+///
+/// The closure must write exactly `len` bytes. If it writes fewer, the
+/// initializer fails with [`WriteError::Short`], and the sink rejects any
+/// write which would take it past `len`. Errors of the closure are returned
+/// as [`WriteError::Write`].
+///
+/// # Panics
+///
+/// The initializer panics if the closure replaces the sink it is given.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(not(inplace_no_global_oom_handling))]
+/// # fn main() {
+/// use std::fmt::Write;
+/// use std::sync::Arc;
+/// use inplace::{write, ArcExt};
+///
+/// let (id, score) = (7_u32, 95_u8);
+/// let record: Arc<[u8]> = Arc::try_emplace(write::exact(10, |sink| {
+///     sink.write_all(b"REC")?;
+///     sink.write_all(&id.to_be_bytes())?;
+///     write!(sink, "{score:3}")
+/// }))
+/// .unwrap();
+/// assert_eq!(&record[..], b"REC\0\0\0\x07 95");
+/// # }
+/// # #[cfg(inplace_no_global_oom_handling)]
+/// # fn main() {}
+/// ```
+pub fn exact<F, E>(len: usize, f: F) -> Exact<F, E>
+where
+    F: FnOnce(&mut Sink<'_>) -> Result<(), E>,
+{
+    Exact {
+        len,
+        f,
+        _marker: PhantomData,
+    }
+}
+
+unsafe impl<F, E> TryInit<[u8], WriteError<E>> for Exact<F, E>
+where
+    F: FnOnce(&mut Sink<'_>) -> Result<(), E>,
+{
+    fn layout(&self) -> Layout {
+        Layout::array::<u8>(self.len).expect("slice length overflows layout")
+    }
+
+    fn dest(&self, addr: *mut u8) -> *mut [u8] {
+        ptr::slice_from_raw_parts_mut(addr, self.len)
+    }
+
+    unsafe fn try_initialize(self, dest: *mut [u8]) -> Result<(), WriteError<E>> {
+        let buf = slice::from_raw_parts_mut(dest.cast::<MaybeUninit<u8>>(), self.len);
+        let start = buf.as_ptr();
+        let mut sink = Sink::new(buf);
+        (self.f)(&mut sink).map_err(WriteError::Write)?;
+        let len = sink.len_over(start);
+        if len != self.len {
+            return Err(WriteError::Short(len));
+        }
+        Ok(())
+    }
+}
+
+/// Allocates `max_len` bytes, lets `f` write into them through a sink, and
+/// returns what it wrote as a `Box<[u8]>`.
+///
+/// The allocation is shrunk to the length written, which the allocator can
+/// usually do in place, so the bytes are still written only once. The sink
+/// rejects any write which would take it past `max_len`. If `f` fails, the
+/// allocation is freed and its error returned. Allocation failure is passed
+/// to the global allocation error handler.
+///
+/// # Panics
+///
+/// Panics if `f` replaces the sink it is given.
+///
+/// # Example
+///
+/// ```
+/// use inplace::write::{self, Overflow};
+///
+/// // LEB128 encodes each value in at most 10 bytes.
+/// let values = [1_u64, 300, u64::MAX];
+/// let encoded = write::bounded(values.len() * 10, |sink| {
+///     for mut value in values {
+///         while value >= 0x80 {
+///             sink.write_all(&[value as u8 | 0x80])?;
+///             value >>= 7;
+///         }
+///         sink.write_all(&[value as u8])?;
+///     }
+///     Ok::<(), Overflow>(())
+/// })
+/// .unwrap();
+/// assert_eq!(encoded.len(), 1 + 2 + 10);
+/// assert_eq!(encoded[1..3], [0xac, 0x02]);
+/// ```
+#[cfg(all(feature = "alloc", not(inplace_no_global_oom_handling)))]
+pub fn bounded<F, E>(max_len: usize, f: F) -> Result<Box<[u8]>, E>
+where
+    F: FnOnce(&mut Sink<'_>) -> Result<(), E>,
+{
+    try_bounded(max_len, f).map_err(|error| match error {
+        InplaceError::Alloc(layout) => alloc::alloc::handle_alloc_error(layout),
+        InplaceError::Init(error) => error,
+    })
+}
+
+/// Attempts to allocate `max_len` bytes, lets `f` write into them through a
+/// sink, and returns what it wrote as a `Box<[u8]>`.
+///
+/// If the bytes cannot be allocated, [`InplaceError::Alloc`] is returned and
+/// `f` is not called. Otherwise this behaves as [`bounded`], with the error
+/// of `f` returned as [`InplaceError::Init`]. If the allocator can't shrink
+/// the allocation, the bytes are copied into one of the length written, and
+/// if that can't be allocated either, [`InplaceError::Alloc`] is returned.
+#[cfg(feature = "alloc")]
+pub fn try_bounded<F, E>(max_len: usize, f: F) -> Result<Box<[u8]>, InplaceError<E>>
+where
+    F: FnOnce(&mut Sink<'_>) -> Result<(), E>,
+{
+    let layout = Layout::array::<u8>(max_len).expect("slice length overflows layout");
+    let addr = raw::try_allocate(layout).ok_or(InplaceError::Alloc(layout))?;
+    let guard = DeallocGuard { addr, layout };
+    let buf = unsafe { slice::from_raw_parts_mut(addr.as_ptr().cast(), max_len) };
+    let start = buf.as_ptr();
+    let mut sink = Sink::new(buf);
+    f(&mut sink).map_err(InplaceError::Init)?;
+    let len = sink.len_over(start);
+    core::mem::forget(guard);
+    let exact = Layout::array::<u8>(len).unwrap();
+    let addr = match unsafe { raw::shrink(addr, layout, len) } {
+        (addr, shrunk) if shrunk == exact => addr,
+        (addr, layout) => {
+            // The box must be freed with the layout of its length.
+            let _guard = DeallocGuard { addr, layout };
+            let copy = raw::try_allocate(exact).ok_or(InplaceError::Alloc(exact))?;
+            unsafe { ptr::copy_nonoverlapping(addr.as_ptr(), copy.as_ptr(), len) };
+            copy
+        }
+    };
+    Ok(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(addr.as_ptr(), len)) })
+}
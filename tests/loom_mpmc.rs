@@ -0,0 +1,152 @@
+//! Model checks of the claim and commit protocol of the MPMC queue.
+//!
+//! Run with `RUSTFLAGS="--cfg inplace_loom" cargo test --release --test loom_mpmc`.
+
+#![cfg(inplace_loom)]
+
+use std::sync::Arc;
+
+use inplace::queue::mpmc::Queue;
+use inplace::ArcExt;
+use loom::thread;
+
+fn pop_spin<T, const N: usize>(queue: &Queue<T, N>) -> T {
+    loop {
+        match queue.pop() {
+            Some(value) => return value,
+            None => thread::yield_now(),
+        }
+    }
+}
+
+fn push_spin<const N: usize>(queue: &Queue<u32, N>, value: u32) {
+    while queue.push_init(value).is_err() {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn producers_claim_distinct_slots() {
+    loom::model(|| {
+        let queue: Arc<Queue<u32, 2>> = Arc::emplace(Queue::new());
+        let producers: Vec<_> = (1..=2)
+            .map(|value| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.push_init(value).unwrap())
+            })
+            .collect();
+        let mut popped = [pop_spin(&*queue), pop_spin(&*queue)];
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        popped.sort();
+        assert_eq!(popped, [1, 2]);
+        assert!(queue.pop().is_none());
+    });
+}
+
+#[test]
+fn consumers_claim_distinct_values() {
+    loom::model(|| {
+        let queue: Arc<Queue<u32, 2>> = Arc::emplace(Queue::new());
+        queue.push_init(1).unwrap();
+        queue.push_init(2).unwrap();
+        let consumers: Vec<_> = (0..2)
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.pop().unwrap())
+            })
+            .collect();
+        let mut popped: Vec<_> = consumers.into_iter().map(|c| c.join().unwrap()).collect();
+        popped.sort();
+        assert_eq!(popped, [1, 2]);
+    });
+}
+
+#[test]
+fn values_wrap_around_in_order() {
+    loom::model(|| {
+        let queue: Arc<Queue<u32, 1>> = Arc::emplace(Queue::new());
+        let producer = {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                push_spin(&queue, 1);
+                push_spin(&queue, 2);
+            })
+        };
+        let first = queue.pop_in_place().map(|value| *value);
+        let rest = match first {
+            Some(1) => [pop_spin(&*queue)],
+            None => {
+                assert_eq!(pop_spin(&*queue), 1);
+                [pop_spin(&*queue)]
+            }
+            other => panic!("popped {other:?}"),
+        };
+        assert_eq!(rest, [2]);
+        producer.join().unwrap();
+    });
+}
+
+#[test]
+fn full_queue_rejects_push() {
+    loom::model(|| {
+        let queue: Arc<Queue<u32, 1>> = Arc::emplace(Queue::new());
+        let producers: Vec<_> = (1..=2)
+            .map(|value| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.push_init(value).is_ok())
+            })
+            .collect();
+        let pushed = producers
+            .into_iter()
+            .map(|p| p.join().unwrap())
+            .filter(|&ok| ok)
+            .count();
+        assert_eq!(pushed, 1);
+        assert!(queue.pop().is_some());
+        assert!(queue.pop().is_none());
+    });
+}
+
+#[test]
+fn abandoned_slots_are_skipped() {
+    loom::model(|| {
+        let queue: Arc<Queue<u32, 2>> = Arc::emplace(Queue::new());
+        let producers: Vec<_> = [Err(()), Ok(7)]
+            .into_iter()
+            .map(|init| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    let slot = queue.reserve().unwrap();
+                    slot.try_emplace(init).ok();
+                })
+            })
+            .collect();
+        assert_eq!(pop_spin(&*queue), 7);
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert!(queue.pop().is_none());
+    });
+}
+
+#[test]
+fn remaining_values_are_dropped() {
+    loom::model(|| {
+        let value = Arc::new(());
+        let queue: Arc<Queue<Arc<()>, 2>> = Arc::emplace(Queue::new());
+        let producer = {
+            let queue = queue.clone();
+            let value = value.clone();
+            thread::spawn(move || {
+                queue.push_init(value.clone()).unwrap();
+                queue.push_init(value).unwrap();
+            })
+        };
+        drop(queue.pop());
+        producer.join().unwrap();
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    });
+}